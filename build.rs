@@ -1,11 +1,18 @@
+#[cfg(all(target_os = "windows", any(feature = "rist-native", feature = "srt-native")))]
 use std::fs;
-use std::path::{Path, PathBuf};
+#[cfg(any(feature = "rist-native", feature = "srt-native"))]
+use std::path::Path;
+#[cfg(all(target_os = "windows", any(feature = "rist-native", feature = "srt-native")))]
+use std::path::PathBuf;
+#[cfg(any(feature = "rist-native", feature = "srt-native"))]
 use std::process::Command;
+#[cfg(all(target_os = "windows", any(feature = "rist-native", feature = "srt-native")))]
 use std::env;
 
 // Petite fonction utilitaire pour exécuter une commande système (ex: meson, cmake).
 // - On lance la commande
 // - Si elle échoue (code de retour ≠ 0), on arrête le build avec un message clair
+#[cfg(any(feature = "rist-native", feature = "srt-native"))]
 fn run(cmd: &mut Command) {
     let status = cmd.status().expect("failed to spawn command");
     if !status.success() {
@@ -13,13 +20,13 @@ fn run(cmd: &mut Command) {
     }
 }
 
-#[cfg(target_os = "windows")]
+#[cfg(all(target_os = "windows", any(feature = "rist-native", feature = "srt-native")))]
 fn target_profile_dir() -> PathBuf {
     let profile = env::var("PROFILE").unwrap_or_else(|_| "debug".to_string());
     Path::new(env!("CARGO_MANIFEST_DIR")).join("target").join(profile)
 }
 
-#[cfg(target_os = "windows")]
+#[cfg(all(target_os = "windows", any(feature = "rist-native", feature = "srt-native")))]
 fn copy_dll_if_exists(src: &Path, dst_dir: &Path) {
     if src.exists() {
         if let Err(e) = fs::create_dir_all(dst_dir) {
@@ -36,7 +43,7 @@ fn copy_dll_if_exists(src: &Path, dst_dir: &Path) {
     }
 }
 
-#[cfg(feature = "rist")]
+#[cfg(feature = "rist-native")]
 fn build_and_link_librist() {
     let target_os = std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_else(|_| String::new());
     // Dossier racine du sous-module librist (chemin relatif au Cargo.toml du projet)
@@ -98,7 +105,7 @@ fn build_and_link_librist() {
     }
 }
 
-#[cfg(feature = "srt")]
+#[cfg(feature = "srt-native")]
 fn build_and_link_srt() {
     let target_os = std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_else(|_| String::new());
     // Dossier racine du sous-module srt
@@ -160,11 +167,11 @@ fn build_and_link_srt() {
 }
 
 fn main() {
-    // Si la feature "rist" est activée, on construit et on link librist
-    #[cfg(feature = "rist")]
+    // Si la feature "rist-native" est activée, on construit et on link librist
+    #[cfg(feature = "rist-native")]
     build_and_link_librist();
 
-    // Si la feature "srt" est activée, on construit et on link srt
-    #[cfg(feature = "srt")]
+    // Si la feature "srt-native" est activée, on construit et on link srt
+    #[cfg(feature = "srt-native")]
     build_and_link_srt();
 }
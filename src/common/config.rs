@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer};
+use thiserror::Error;
+
+use crate::common::features;
+use crate::common::uri::{redact_uri_secrets, split_uris};
+use crate::relay::overrun::OverrunAction;
+use crate::relay::watchdog::WatchdogAction;
+use crate::structures::StatsFields;
+
+// Chargement validé de la configuration: fichier TOML (--config) et probes automatiques (variables d'environnement).
+// Une valeur invalide produit une erreur nommant la variable et la valeur, jamais un repli silencieux.
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("invalid value for {var}: {value:?} ({reason})")]
+    InvalidEnv { var: &'static str, value: String, reason: String },
+
+    #[error("cannot load config file {path}: {reason}")]
+    File { path: String, reason: String },
+}
+
+// Modèle commenté affiché par `stream-relay init-config`
+pub const EXAMPLE_CONFIG: &str = include_str!("example_config.toml");
+
+// Fichier de configuration TOML: une liste de relais [[relays]] démarrés au lancement du serveur HTTP
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    #[serde(default)]
+    pub relays: Vec<RelayConfig>,
+    // Projection par défaut de GET /stats (clés de data, plus "relays"); ?fields= la remplace
+    #[serde(default)]
+    pub stats_fields: Option<StatsFields>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RelayConfig {
+    pub input: String,
+    pub output: String,
+    #[serde(default = "default_latency_ms")]
+    pub latency_ms: u64,
+    #[serde(default)]
+    pub max_reconnects: Option<u32>,
+    // Arrêt automatique après cette durée (secondes); prioritaire sur ?max_runtime
+    #[serde(default)]
+    pub max_runtime_secs: Option<u64>,
+    // Arrêt si aucune donnée n'arrive pendant cette durée (secondes); prioritaire sur ?idle_timeout
+    #[serde(default)]
+    pub idle_timeout_secs: Option<u64>,
+    // Timeouts de lecture consécutifs avant reconnexion; prioritaire sur ?max_recv_timeouts
+    #[serde(default)]
+    pub max_recv_timeouts: Option<u32>,
+    // Plafonds d'émission (bits/s, paquets/s); prioritaires sur ?max_bitrate / ?max_pps
+    #[serde(default)]
+    pub max_bitrate: Option<u64>,
+    #[serde(default)]
+    pub max_pps: Option<u64>,
+    // Seuils d'alerte sur le débit entrant lissé (bits/s): /stats indique bitrate_status under/over
+    #[serde(default)]
+    pub alert_min_bitrate: Option<u64>,
+    #[serde(default)]
+    pub alert_max_bitrate: Option<u64>,
+    // Plafond de débit entrant lissé (bits/s) au-delà duquel le relais passe en overrun; prioritaire
+    // sur ?overrun_bitrate / ?overrun_action
+    #[serde(default)]
+    pub overrun_bitrate: Option<u64>,
+    #[serde(default)]
+    pub overrun_action: Option<OverrunAction>,
+    // Silence (secondes) au-delà duquel le relais est jugé bloqué; prioritaire sur ?watchdog /
+    // ?watchdog_action (reconnect par défaut)
+    #[serde(default)]
+    pub watchdog_secs: Option<u64>,
+    #[serde(default)]
+    pub watchdog_action: Option<WatchdogAction>,
+    // Sonde d'accessibilité des sorties SRT à l'ouverture (avertissement seulement)
+    #[serde(default)]
+    pub reachability_check: bool,
+    // Période de chauffe après chaque (re)démarrage du pipe: /ready et bitrate_status indiquent "warming"
+    #[serde(default)]
+    pub warmup_secs: Option<u64>,
+    // Étiquettes libres (customer = "acme"), reprises dans /relays, les logs et la série relay_labels
+    #[serde(default, deserialize_with = "deserialize_labels")]
+    pub labels: HashMap<String, String>,
+}
+
+// Noms d'étiquettes Prometheus: [a-zA-Z_][a-zA-Z0-9_]*, sans le préfixe réservé "__";
+// relay_id est déjà porté par toutes les séries du relais
+pub fn validate_label_name(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!("invalid label name {:?}: expected [a-zA-Z_][a-zA-Z0-9_]*", name));
+    }
+    if name.starts_with("__") || name == "relay_id" {
+        return Err(format!("label name {:?} is reserved", name));
+    }
+    Ok(())
+}
+
+fn deserialize_labels<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<String, String>, D::Error> {
+    let labels = HashMap::<String, String>::deserialize(deserializer)?;
+    for name in labels.keys() {
+        validate_label_name(name).map_err(serde::de::Error::custom)?;
+    }
+    Ok(labels)
+}
+
+// Forme compacte et stable pour les logs: "customer=acme,event=finals"
+pub fn format_labels(labels: &HashMap<String, String>) -> String {
+    let mut pairs: Vec<_> = labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    pairs.sort();
+    pairs.join(",")
+}
+
+fn default_latency_ms() -> u64 {
+    80
+}
+
+impl FileConfig {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let file_err = |reason: String| ConfigError::File { path: path.display().to_string(), reason };
+        let text = std::fs::read_to_string(path).map_err(|e| file_err(e.to_string()))?;
+        Self::parse(&text).map_err(file_err)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+}
+
+// Liste complète de relais en un seul JSON (SRTRIST_RELAYS='[{"input":...,"output":...}]'), pratique pour
+// les orchestrateurs qui passent la configuration comme un unique secret. Mêmes champs que [[relays]].
+// None si la variable est absente: les variables SRTRIST_AUTO_* / SRTRIST_SRT_* / SRTRIST_RIST_* s'appliquent.
+pub fn relays_from_env() -> Result<Option<Vec<RelayConfig>>, ConfigError> {
+    relays_from_lookup(|k| std::env::var(k).ok())
+}
+
+pub fn relays_from_lookup(get: impl Fn(&str) -> Option<String>) -> Result<Option<Vec<RelayConfig>>, ConfigError> {
+    const VAR: &str = "SRTRIST_RELAYS";
+    let Some(value) = env_raw(&get, VAR) else { return Ok(None) };
+    serde_json::from_str(&value).map(Some).map_err(|e| ConfigError::InvalidEnv {
+        var: VAR,
+        // Les URIs peuvent porter des secrets (psk, passphrase): jamais journalisés en clair
+        value: redact_uri_secrets(&value),
+        reason: format!("expected a JSON array of relays: {}", e),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrtAutoConfig {
+    pub enabled: bool,
+    pub input: String,
+    pub output: String,
+    pub latency_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RistAutoConfig {
+    pub enabled: bool,
+    pub input: String,
+    pub output: String,
+}
+
+impl SrtAutoConfig {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_lookup(|k| std::env::var(k).ok())
+    }
+
+    pub fn from_lookup(get: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        Ok(Self {
+            enabled: env_bool(&get, "SRTRIST_AUTO_SRT", features::SRT_NATIVE)?,
+            input: env_uri(&get, "SRTRIST_SRT_INPUT", "srt://", "srt://@:9000?mode=listener")?,
+            output: env_uri(&get, "SRTRIST_SRT_OUTPUT", "srt://", "srt://127.0.0.1:10000?mode=caller")?,
+            latency_ms: env_parse(&get, "SRTRIST_SRT_LATENCY_MS", 80)?,
+        })
+    }
+}
+
+impl RistAutoConfig {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_lookup(|k| std::env::var(k).ok())
+    }
+
+    pub fn from_lookup(get: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        Ok(Self {
+            enabled: env_bool(&get, "SRTRIST_AUTO_RIST", features::RIST_NATIVE)?,
+            input: env_uri(&get, "SRTRIST_RIST_INPUT", "rist://", "rist://@:10000?mode=listener")?,
+            output: env_uri(&get, "SRTRIST_RIST_OUTPUT", "rist://", "rist://127.0.0.1:11000?mode=caller")?,
+        })
+    }
+}
+
+// Valeur brute, espaces retirés; une variable vide est traitée comme absente
+fn env_raw(get: &impl Fn(&str) -> Option<String>, var: &str) -> Option<String> {
+    get(var).map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+fn env_bool(get: &impl Fn(&str) -> Option<String>, var: &'static str, default: bool) -> Result<bool, ConfigError> {
+    let Some(value) = env_raw(get, var) else { return Ok(default) };
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(ConfigError::InvalidEnv { var, value, reason: "expected 0/1, true/false, yes/no or on/off".into() }),
+    }
+}
+
+fn env_parse<T>(get: &impl Fn(&str) -> Option<String>, var: &'static str, default: T) -> Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let Some(value) = env_raw(get, var) else { return Ok(default) };
+    value.parse().map_err(|e: T::Err| ConfigError::InvalidEnv { var, reason: e.to_string(), value })
+}
+
+// Accepte aussi une liste séparée par des virgules (sorties multiples), chaque URI devant porter le schéma
+fn env_uri(get: &impl Fn(&str) -> Option<String>, var: &'static str, scheme: &str, default: &str) -> Result<String, ConfigError> {
+    let Some(value) = env_raw(get, var) else { return Ok(default.to_string()) };
+    if !split_uris(&value).iter().all(|uri| uri.starts_with(scheme)) {
+        return Err(ConfigError::InvalidEnv { var, value, reason: format!("expected a {} URI", scheme) });
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(pairs: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |k| pairs.iter().find(|(n, _)| *n == k).map(|(_, v)| v.to_string())
+    }
+
+    #[test]
+    fn example_config_parses() {
+        let cfg = FileConfig::parse(EXAMPLE_CONFIG).unwrap();
+        assert_eq!(cfg.relays.len(), 2);
+        assert_eq!(cfg.relays[0].latency_ms, 80);
+        assert!(cfg.relays[1].input.starts_with("rist://"));
+        assert!(FileConfig::parse("[[relays]]\ninput = \"srt://@:1\"\noutput = \"srt://h:2\"\nbogus = 1").is_err());
+        let fields = FileConfig::parse("stats_fields = [\"bitrate\", \"rtt\"]").unwrap().stats_fields.unwrap();
+        assert!(fields.contains("rtt") && !fields.contains("uptime"));
+        assert!(FileConfig::parse("stats_fields = [\"bitrate\", \"latency\"]").unwrap_err().contains("latency"));
+    }
+
+    #[test]
+    fn relays_from_json_env() {
+        assert!(relays_from_lookup(lookup(&[])).unwrap().is_none());
+        let relays = relays_from_lookup(lookup(&[(
+            "SRTRIST_RELAYS",
+            r#"[{"input":"srt://@:9000","output":"rist://h:1"},{"input":"rist://@:1","output":"srt://h:2","latency_ms":200}]"#,
+        )]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(relays.len(), 2);
+        assert_eq!(relays[0].latency_ms, 80);
+        assert_eq!(relays[1].latency_ms, 200);
+
+        let err = relays_from_lookup(lookup(&[("SRTRIST_RELAYS", r#"[{"input":"srt://@:1?psk=hunter2"}"#)])).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("SRTRIST_RELAYS"));
+        assert!(!msg.contains("hunter2"));
+    }
+
+    #[test]
+    fn labels_are_validated() {
+        let cfg = FileConfig::parse("[[relays]]\ninput = \"srt://@:1\"\noutput = \"srt://h:2\"\nlabels = { customer = \"acme\", event = \"finals\" }").unwrap();
+        assert_eq!(format_labels(&cfg.relays[0].labels), "customer=acme,event=finals");
+        for bad in ["9lives", "bad-name", "__meta", "relay_id", ""] {
+            assert!(validate_label_name(bad).is_err(), "{}", bad);
+        }
+        assert!(relays_from_lookup(lookup(&[("SRTRIST_RELAYS", r#"[{"input":"srt://@:1","output":"srt://h:2","labels":{"a-b":"x"}}]"#)])).is_err());
+    }
+
+    #[test]
+    fn defaults_when_unset() {
+        let cfg = SrtAutoConfig::from_lookup(lookup(&[])).unwrap();
+        assert_eq!(cfg.latency_ms, 80);
+        assert_eq!(cfg.input, "srt://@:9000?mode=listener");
+    }
+
+    #[test]
+    fn invalid_latency_names_variable() {
+        let err = SrtAutoConfig::from_lookup(lookup(&[("SRTRIST_SRT_LATENCY_MS", "eighty")])).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("SRTRIST_SRT_LATENCY_MS"));
+        assert!(msg.contains("eighty"));
+    }
+
+    #[test]
+    fn tolerant_bool_and_scheme_check() {
+        let cfg = RistAutoConfig::from_lookup(lookup(&[("SRTRIST_AUTO_RIST", " Off ")])).unwrap();
+        assert!(!cfg.enabled);
+        assert!(RistAutoConfig::from_lookup(lookup(&[("SRTRIST_AUTO_RIST", "maybe")])).is_err());
+        assert!(RistAutoConfig::from_lookup(lookup(&[("SRTRIST_RIST_INPUT", "srt://@:1")])).is_err());
+        let cfg = SrtAutoConfig::from_lookup(lookup(&[("SRTRIST_SRT_OUTPUT", "srt://a:1,srt://b:2")])).unwrap();
+        assert_eq!(cfg.output, "srt://a:1,srt://b:2");
+        assert!(SrtAutoConfig::from_lookup(lookup(&[("SRTRIST_SRT_OUTPUT", "srt://a:1,rist://b:2")])).is_err());
+    }
+}
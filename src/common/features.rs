@@ -0,0 +1,64 @@
+// Ce que ce binaire sait faire, fixé à la compilation.
+// Les features Cargo `srt` et `rist` (par défaut) compilent les transports srt:// et rist://; `srt-native`
+// et `rist-native` ajoutent l'édition de liens avec libsrt/librist (build.rs) et activent par défaut
+// les probes automatiques SRTRIST_AUTO_SRT / SRTRIST_AUTO_RIST.
+
+use crate::structures::TransportError;
+
+pub const SRT_TRANSPORT: bool = cfg!(feature = "srt");
+pub const RIST_TRANSPORT: bool = cfg!(feature = "rist");
+pub const SRT_NATIVE: bool = cfg!(feature = "srt-native");
+pub const RIST_NATIVE: bool = cfg!(feature = "rist-native");
+pub const DEBUG_ENDPOINTS: bool = cfg!(feature = "debug-endpoints");
+
+// Schémas connus du projet et la feature Cargo qui les fournit
+const TRANSPORTS: [(&str, &str, bool); 2] = [("srt", "srt", SRT_TRANSPORT), ("rist", "rist", RIST_TRANSPORT)];
+
+// Features Cargo actives, pour le log de démarrage ("srt,rist", "none")
+pub fn enabled_features() -> String {
+    let enabled: Vec<&str> = [("srt", SRT_TRANSPORT), ("rist", RIST_TRANSPORT), ("srt-native", SRT_NATIVE), ("rist-native", RIST_NATIVE), ("debug-endpoints", DEBUG_ENDPOINTS)]
+        .iter()
+        .filter(|(_, on)| *on)
+        .map(|(name, _)| *name)
+        .collect();
+    if enabled.is_empty() { "none".to_string() } else { enabled.join(",") }
+}
+
+// Un transport est-il compilé dans ce binaire ? None pour un schéma inconnu du projet
+pub fn transport_enabled(scheme: &str) -> Option<bool> {
+    TRANSPORTS.iter().find(|(s, _, _)| s.eq_ignore_ascii_case(scheme)).map(|(_, _, on)| *on)
+}
+
+// Erreur explicite pour un transport connu mais absent du binaire (ou du registre utilisé)
+pub fn not_compiled_in(scheme: &str) -> Option<TransportError> {
+    let (_, feature, _) = TRANSPORTS.iter().find(|(s, _, _)| s.eq_ignore_ascii_case(scheme))?;
+    Some(TransportError::UnsupportedScheme(format!(
+        "{} support not compiled in; rebuild with --features {}",
+        scheme.to_ascii_uppercase(),
+        feature
+    )))
+}
+
+// Vérification d'une sous-commande dédiée à un transport (srt2srt, rist2rist)
+pub fn require_transport(scheme: &str) -> Result<(), TransportError> {
+    match not_compiled_in(scheme) {
+        Some(e) if transport_enabled(scheme) == Some(false) => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_missing_transports_clearly() {
+        assert_eq!(transport_enabled("SRT"), Some(SRT_TRANSPORT));
+        assert_eq!(transport_enabled("udp"), None);
+        let e = not_compiled_in("srt").unwrap();
+        assert_eq!(e.to_string(), "Unsupported URI scheme: SRT support not compiled in; rebuild with --features srt");
+        assert!(not_compiled_in("udp").is_none());
+        assert_eq!(require_transport("rist").is_ok(), RIST_TRANSPORT);
+        assert_eq!(enabled_features().split(',').any(|f| f == "srt"), cfg!(feature = "srt"));
+    }
+}
@@ -0,0 +1,62 @@
+// Compression gzip des réponses HTTP volumineuses (/metrics) via flate2. Le texte Prometheus (noms de
+// séries et labels répétés) se réduit de plusieurs fois; niveau rapide, la réponse est recalculée à
+// chaque scrape.
+
+use std::io::Write;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::with_capacity(data.len() / 4), Compression::fast());
+    // Écrire dans un Vec ne peut pas échouer
+    encoder.write_all(data).expect("gzip into memory");
+    encoder.finish().expect("gzip into memory")
+}
+
+// Accept-Encoding autorise-t-il gzip ? ("gzip", "x-gzip" ou "*", sauf q=0)
+pub fn accepts_gzip(accept_encoding: &str) -> bool {
+    accept_encoding.split(',').any(|item| {
+        let mut parts = item.split(';').map(str::trim);
+        let coding = parts.next().unwrap_or("");
+        let refused = parts.any(|p| {
+            p.strip_prefix("q=").is_some_and(|q| q.parse::<f32>().is_ok_and(|q| q <= 0.0))
+        });
+        !refused && ["gzip", "x-gzip", "*"].iter().any(|c| coding.eq_ignore_ascii_case(c))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn gunzip(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        flate2::read::GzDecoder::new(data).read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn parses_accept_encoding() {
+        assert!(accepts_gzip("gzip"));
+        assert!(accepts_gzip("deflate, GZIP;q=0.8"));
+        assert!(accepts_gzip("*"));
+        assert!(!accepts_gzip("identity"));
+        assert!(!accepts_gzip("gzip;q=0, br"));
+        assert!(!accepts_gzip(""));
+    }
+
+    #[test]
+    fn gzip_round_trips_and_compresses() {
+        let text = "relay_bytes_in_total{input_protocol=\"srt\",output_protocol=\"srt\"} 1234\n".repeat(200);
+        let out = gzip(text.as_bytes());
+        assert_eq!(&out[..3], &[0x1f, 0x8b, 8]);
+        assert!(out.len() * 10 < text.len());
+        assert_eq!(gunzip(&out), text.as_bytes());
+        assert!(gunzip(&gzip(b"")).is_empty());
+        // Plusieurs blocs DEFLATE: données peu compressibles au-delà de la fenêtre de 32 Kio
+        let noisy: Vec<u8> = (0u32..300_000).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+        assert_eq!(gunzip(&gzip(&noisy)), noisy);
+    }
+}
@@ -1,28 +1,106 @@
-use tracing_subscriber::{fmt, EnvFilter};
+use std::path::PathBuf;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{fmt, EnvFilter, Layer, Registry};
+use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::fmt::time::UtcTime;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use time::macros::format_description;
 
-pub fn init() {
+// Format de sortie des logs: JSON (défaut, production) ou lisible pour le développement local
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Json,
+    Pretty,
+    Compact,
+}
+
+// Destinations des logs: stdout et/ou fichier à rotation quotidienne dans `file_dir`
+#[derive(Debug, Clone, Default)]
+pub struct LogOptions {
+    pub format: LogFormat,
+    pub file_dir: Option<PathBuf>,
+    pub stdout: bool,
+}
+
+const LOG_FILE_PREFIX: &str = "stream-relay.log";
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+// Le WorkerGuard renvoyé doit rester vivant jusqu'à la fin du programme, sinon les derniers logs fichier sont perdus
+pub fn init(opts: &LogOptions) -> Option<WorkerGuard> {
     // Default to info if RUST_LOG not set
     let env_filter = EnvFilter::try_from_default_env()
         .or_else(|_| EnvFilter::try_new("info"))
         .expect("env filter");
 
-    // RFC3339-like with UTC
-    let timer = UtcTime::new(format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]Z"));
+    let mut layers: Vec<BoxedLayer> = Vec::new();
+    if opts.stdout || opts.file_dir.is_none() {
+        layers.push(fmt_layer(opts.format, std::io::stdout, true));
+    }
+    let guard = opts.file_dir.as_ref().map(|dir| {
+        let appender = tracing_appender::rolling::daily(dir, LOG_FILE_PREFIX);
+        let (writer, guard) = tracing_appender::non_blocking(appender);
+        layers.push(fmt_layer(opts.format, writer, false));
+        guard
+    });
 
-    let fmt_layer = fmt::layer()
-        .event_format(fmt::format().json().with_current_span(false).with_span_list(false))
-        .fmt_fields(fmt::format::JsonFields::new())
-        .with_timer(timer)
-        .with_writer(std::io::stdout);
+    // Mémoire des derniers événements par relais (GET /relays/<id>/logs), quelle que soit la sortie
+    layers.push(Box::new(crate::common::relay_logs::RelayLogLayer));
 
     tracing_subscriber::registry()
+        .with(layers)
         .with(env_filter)
-        .with(fmt_layer)
         .init();
+    guard
+}
+
+fn fmt_layer<W>(format: LogFormat, writer: W, ansi: bool) -> BoxedLayer
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    // RFC3339-like with UTC
+    let timer = UtcTime::new(format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]Z"));
+    match format {
+        LogFormat::Json => fmt::layer()
+            // Les champs du span courant (ex: relay_id du pipe) sont inclus sous "span"
+            .event_format(fmt::format().json().with_current_span(true).with_span_list(false))
+            .fmt_fields(fmt::format::JsonFields::new())
+            .with_timer(timer)
+            .with_writer(writer)
+            .boxed(),
+        LogFormat::Pretty => fmt::layer().pretty().with_ansi(ansi).with_timer(timer).with_writer(writer).boxed(),
+        LogFormat::Compact => fmt::layer().compact().with_ansi(ansi).with_timer(timer).with_writer(writer).boxed(),
+    }
+}
+
+// Limite la fréquence d'un log répétitif: le premier passe, puis au plus un par intervalle.
+// Le nombre d'occurrences supprimées entre deux logs est renvoyé pour être inclus dans le message.
+pub struct LogThrottle {
+    interval: std::time::Duration,
+    last: Option<std::time::Instant>,
+    suppressed: u64,
+}
+
+impl LogThrottle {
+    pub fn new(interval: std::time::Duration) -> Self {
+        Self { interval, last: None, suppressed: 0 }
+    }
+
+    pub fn allow(&mut self) -> Option<u64> {
+        let now = std::time::Instant::now();
+        match self.last {
+            Some(last) if now.duration_since(last) < self.interval => {
+                self.suppressed += 1;
+                None
+            }
+            _ => {
+                self.last = Some(now);
+                Some(std::mem::take(&mut self.suppressed))
+            }
+        }
+    }
 }
 
 pub fn short_uuid() -> String {
@@ -34,16 +112,49 @@ pub mod events {
     pub const APP_START: &str = "app_start";
     pub const APP_READY: &str = "app_ready";
     pub const APP_SHUTDOWN: &str = "app_shutdown";
+    pub const CONFIG_ERROR: &str = "config_error";
 
     pub const HTTP_REQUEST: &str = "http_request";
     pub const HTTP_RESPONSE: &str = "http_response";
+    pub const HTTP_SLOW_REQUEST: &str = "http_slow_request";
+    pub const HTTP_TIMEOUT: &str = "http_timeout";
 
     pub const RELAY_START: &str = "relay_start";
     pub const RELAY_STOP: &str = "relay_stop";
+    pub const METRICS_RESET: &str = "metrics_reset";
+    pub const CONTROL_COMMAND: &str = "control_command";
     pub const RELAY_ERROR: &str = "relay_error";
+    pub const RELAY_LOSS: &str = "relay_loss";
+    pub const DATAGRAM_TRUNCATED: &str = "datagram_truncated";
+    pub const DATAGRAM_OVERSIZED: &str = "datagram_oversized";
+    pub const SEND_QUEUE_FULL: &str = "send_queue_full";
+    pub const SOURCE_REJECTED: &str = "source_rejected";
+    pub const CAPTURE_STARTED: &str = "capture_started";
+    pub const CAPTURE_FINISHED: &str = "capture_finished";
+    pub const RECV_BUFFER_RESIZED: &str = "recv_buffer_resized";
+
+    pub const SOCKET_OPTION: &str = "socket_option";
 
     pub const PEER_CONNECTED: &str = "peer_connected";
     pub const PEER_DISCONNECTED: &str = "peer_disconnected";
+    pub const PEER_LIMIT: &str = "peer_limit";
+
+    pub const TARGET_UNREACHABLE: &str = "target_unreachable";
+    pub const TARGET_RESOLVED: &str = "target_resolved";
+    pub const OUTPUT_SWITCHED: &str = "output_switched";
+    pub const OUTPUT_RETIRED: &str = "output_retired";
+    pub const OUTPUT_PROBED: &str = "output_probed";
+    pub const RELAY_RECONFIGURED: &str = "relay_reconfigured";
+    pub const RELAY_PAUSED: &str = "relay_paused";
+    pub const RELAY_RESUMED: &str = "relay_resumed";
+    pub const RELAY_OVERRUN: &str = "relay_overrun";
+    pub const RELAY_OVERRUN_CLEARED: &str = "relay_overrun_cleared";
+    pub const RELAY_STATS: &str = "relay_stats";
+    pub const RELAY_WATCHDOG: &str = "relay_watchdog";
+    pub const RELAY_STALLED: &str = "relay_stalled";
+    pub const RELAY_STALL_CLEARED: &str = "relay_stall_cleared";
+    pub const STATSD_STARTED: &str = "statsd_started";
+    pub const STATSD_ERROR: &str = "statsd_error";
 
     pub const RECONNECT_SCHEDULED: &str = "reconnect_scheduled";
     pub const RECONNECT_ATTEMPT: &str = "reconnect_attempt";
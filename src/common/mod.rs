@@ -1,2 +1,6 @@
+pub mod config;
+pub mod features;
+pub mod gzip;
 pub mod logging;
+pub mod relay_logs;
 pub mod uri;
@@ -0,0 +1,168 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use time::OffsetDateTime;
+use time::macros::format_description;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+// Derniers événements de log de chaque relais, en mémoire, pour GET /relays/<id>/logs.
+// Un événement est rattaché au relais par son champ relay_id, ou celui d'un span englobant
+// (span "relay" du pipe), donc les logs des transports sont capturés aussi. Les événements d'un
+// relais arrêté restent consultables jusqu'à ce que MAX_RELAYS relais plus récents les évincent.
+
+const EVENTS_PER_RELAY: usize = 200;
+// Au-delà, le relais dont le dernier événement est le plus ancien est oublié
+const MAX_RELAYS: usize = 256;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayLogEntry {
+    pub timestamp: String,
+    pub level: &'static str,
+    pub fields: Map<String, Value>,
+}
+
+struct RelayLog {
+    entries: VecDeque<RelayLogEntry>,
+    seq: u64,
+}
+
+#[derive(Default)]
+pub struct RelayLogBuffer {
+    relays: Mutex<HashMap<String, RelayLog>>,
+    seq: std::sync::atomic::AtomicU64,
+}
+
+static GLOBAL_BUFFER: Lazy<RelayLogBuffer> = Lazy::new(RelayLogBuffer::default);
+
+impl RelayLogBuffer {
+    pub fn global() -> &'static RelayLogBuffer {
+        &GLOBAL_BUFFER
+    }
+
+    fn push(&self, relay_id: &str, entry: RelayLogEntry) {
+        let seq = self.seq.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let mut relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        if !relays.contains_key(relay_id) && relays.len() >= MAX_RELAYS {
+            let oldest = relays.iter().min_by_key(|(_, log)| log.seq).map(|(id, _)| id.clone());
+            if let Some(id) = oldest {
+                relays.remove(&id);
+            }
+        }
+        let log = relays.entry(relay_id.to_string()).or_insert_with(|| RelayLog { entries: VecDeque::with_capacity(EVENTS_PER_RELAY), seq });
+        if log.entries.len() == EVENTS_PER_RELAY {
+            log.entries.pop_front();
+        }
+        log.entries.push_back(entry);
+        log.seq = seq;
+    }
+
+    // Du plus ancien au plus récent; None si aucun événement n'a été vu pour ce relais
+    pub fn entries(&self, relay_id: &str) -> Option<Vec<RelayLogEntry>> {
+        let relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        relays.get(relay_id).map(|log| log.entries.iter().cloned().collect())
+    }
+}
+
+// relay_id d'un span, mémorisé dans ses extensions à la création
+struct SpanRelayId(String);
+
+#[derive(Default)]
+struct FieldVisitor {
+    fields: Map<String, Value>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields.insert(field.name().to_string(), Value::from(value));
+    }
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields.insert(field.name().to_string(), Value::from(value));
+    }
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.insert(field.name().to_string(), Value::from(value));
+    }
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.fields.insert(field.name().to_string(), Value::from(value));
+    }
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields.insert(field.name().to_string(), Value::from(value));
+    }
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.fields.insert(field.name().to_string(), Value::from(format!("{:?}", value)));
+    }
+}
+
+fn relay_id_of(fields: &Map<String, Value>) -> Option<String> {
+    fields.get("relay_id").and_then(Value::as_str).map(str::to_string)
+}
+
+pub struct RelayLogLayer;
+
+impl<S> Layer<S> for RelayLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        if let (Some(relay_id), Some(span)) = (relay_id_of(&visitor.fields), ctx.span(id)) {
+            span.extensions_mut().insert(SpanRelayId(relay_id));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        values.record(&mut visitor);
+        if let (Some(relay_id), Some(span)) = (relay_id_of(&visitor.fields), ctx.span(id)) {
+            span.extensions_mut().replace(SpanRelayId(relay_id));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let relay_id = relay_id_of(&visitor.fields).or_else(|| {
+            ctx.event_scope(event)?.find_map(|span| span.extensions().get::<SpanRelayId>().map(|r| r.0.clone()))
+        });
+        let Some(relay_id) = relay_id else { return };
+        let timestamp = OffsetDateTime::now_utc()
+            .format(format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]Z"))
+            .unwrap_or_default();
+        let level = event.metadata().level().as_str();
+        RelayLogBuffer::global().push(&relay_id, RelayLogEntry { timestamp, level, fields: visitor.fields });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn captures_events_by_relay_id_and_bounds_the_buffer() {
+        let subscriber = tracing_subscriber::registry().with(RelayLogLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(relay_id = "t-direct", msg = "direct");
+            let span = tracing::info_span!("relay", relay_id = "t-span");
+            let _guard = span.enter();
+            for i in 0..EVENTS_PER_RELAY + 5 {
+                tracing::warn!(n = i as u64, "from span");
+            }
+        });
+        let buffer = RelayLogBuffer::global();
+        let direct = buffer.entries("t-direct").unwrap();
+        assert_eq!(direct[0].fields["msg"], "direct");
+        let spanned = buffer.entries("t-span").unwrap();
+        assert_eq!(spanned.len(), EVENTS_PER_RELAY);
+        assert_eq!(spanned[0].fields["n"], 5);
+        assert_eq!(spanned[0].level, "WARN");
+        assert!(buffer.entries("t-unknown").is_none());
+    }
+}
@@ -0,0 +1,229 @@
+// Interface de contrôle sur socket UNIX (--control-socket), alternative à HTTP sur les hôtes verrouillés.
+// Protocole: une commande JSON par ligne, une réponse JSON par ligne.
+//   {"cmd":"stats"}                                    -> même contenu que GET /stats
+//   {"cmd":"list"}                                     -> relais pilotés (relay_id, input, output, running)
+//   {"cmd":"start","input":"srt://...","output":"..."} -> mêmes champs qu'un [[relays]] du fichier TOML
+//   {"cmd":"stop","relay_id":"..."}                    -> arrêt propre, entre deux lectures
+//   {"cmd":"set_rate_limit","relay_id":"...","max_bitrate":8000000}  (null = illimité)
+//   {"cmd":"add_output","relay_id":"...","output":"srt://..."}
+//   {"cmd":"remove_output","relay_id":"...","output":"srt://..."}      (URI telle que listée)
+//   {"cmd":"switch_output","relay_id":"...","output":"srt://...","overlap_ms":500}
+// L'accès est contrôlé par les permissions du fichier socket (0660, posées avant qu'il soit joignable).
+
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::common::config::RelayConfig;
+use crate::relay::command::DEFAULT_SWITCH_OVERLAP_MS;
+use crate::relay::manager::{ManagedRelayInfo, RelayManager};
+use crate::structures::{Metrics, StatsResponse, TResult};
+
+#[derive(Debug)]
+pub enum ControlCommand {
+    Stats,
+    List,
+    Start(Box<RelayConfig>),
+    Stop { relay_id: String },
+    SetRateLimit { relay_id: String, max_bitrate: Option<u64> },
+    AddOutput { relay_id: String, output: String },
+    RemoveOutput { relay_id: String, output: String },
+    SwitchOutput { relay_id: String, output: String, overlap_ms: u64 },
+}
+
+pub fn parse_command(line: &str) -> Result<ControlCommand, String> {
+    let mut value: Value = serde_json::from_str(line).map_err(|e| format!("invalid JSON: {}", e))?;
+    let obj = value.as_object_mut().ok_or("expected a JSON object")?;
+    let cmd = obj.remove("cmd").and_then(|c| c.as_str().map(str::to_owned)).ok_or("missing \"cmd\"")?;
+    match cmd.as_str() {
+        "stats" => Ok(ControlCommand::Stats),
+        "list" => Ok(ControlCommand::List),
+        "start" => serde_json::from_value(value).map(|cfg| ControlCommand::Start(Box::new(cfg))).map_err(|e| format!("invalid relay definition: {}", e)),
+        "stop" => Ok(ControlCommand::Stop { relay_id: str_field(obj, "relay_id")? }),
+        "set_rate_limit" => {
+            let max_bitrate = match obj.get("max_bitrate") {
+                None | Some(Value::Null) => None,
+                Some(v) => Some(v.as_u64().ok_or("\"max_bitrate\" must be a non-negative integer or null")?),
+            };
+            Ok(ControlCommand::SetRateLimit { relay_id: str_field(obj, "relay_id")?, max_bitrate })
+        }
+        "add_output" => Ok(ControlCommand::AddOutput { relay_id: str_field(obj, "relay_id")?, output: str_field(obj, "output")? }),
+        "remove_output" => Ok(ControlCommand::RemoveOutput { relay_id: str_field(obj, "relay_id")?, output: str_field(obj, "output")? }),
+        "switch_output" => {
+            let overlap_ms = match obj.get("overlap_ms") {
+                None => DEFAULT_SWITCH_OVERLAP_MS,
+                Some(v) => v.as_u64().ok_or("\"overlap_ms\" must be a non-negative integer")?,
+            };
+            Ok(ControlCommand::SwitchOutput { relay_id: str_field(obj, "relay_id")?, output: str_field(obj, "output")?, overlap_ms })
+        }
+        other => Err(format!("unknown command {:?} (expected stats, list, start, stop, set_rate_limit, add_output, remove_output or switch_output)", other)),
+    }
+}
+
+fn str_field(obj: &serde_json::Map<String, Value>, key: &str) -> Result<String, String> {
+    obj.get(key).and_then(Value::as_str).map(str::to_owned).ok_or_else(|| format!("missing \"{}\"", key))
+}
+
+pub async fn execute(metrics: &Metrics, cmd: ControlCommand) -> Value {
+    let manager = RelayManager::global();
+    match cmd {
+        ControlCommand::Stats => serde_json::to_value(StatsResponse::collect(metrics)).unwrap_or_else(|e| error_reply(e.to_string())),
+        ControlCommand::List => json!({ "status": "ok", "relays": manager.list() }),
+        ControlCommand::Start(cfg) => match manager.start(*cfg).await {
+            Ok(relay_id) => json!({ "status": "ok", "relay_id": relay_id }),
+            Err(e) => error_reply(e.to_string()),
+        },
+        ControlCommand::Stop { relay_id } => {
+            if manager.stop(&relay_id) {
+                json!({ "status": "ok", "relay_id": relay_id })
+            } else {
+                error_reply(format!("unknown relay_id {}", relay_id))
+            }
+        }
+        ControlCommand::SetRateLimit { relay_id, max_bitrate } => relay_reply(&relay_id, manager.set_rate_limit(&relay_id, max_bitrate).await),
+        ControlCommand::AddOutput { relay_id, output } => relay_reply(&relay_id, manager.add_output(&relay_id, output).await),
+        ControlCommand::RemoveOutput { relay_id, output } => relay_reply(&relay_id, manager.remove_output(&relay_id, output).await),
+        ControlCommand::SwitchOutput { relay_id, output, overlap_ms } => {
+            relay_reply(&relay_id, manager.switch_output(&relay_id, output, Duration::from_millis(overlap_ms)).await)
+        }
+    }
+}
+
+fn relay_reply(relay_id: &str, result: TResult<Option<ManagedRelayInfo>>) -> Value {
+    match result {
+        Ok(Some(relay)) => json!({ "status": "ok", "relay": relay }),
+        Ok(None) => error_reply(format!("unknown relay_id {}", relay_id)),
+        Err(e) => error_reply(e.to_string()),
+    }
+}
+
+fn error_reply(error: String) -> Value {
+    json!({ "status": "error", "error": error })
+}
+
+#[cfg(unix)]
+pub use server::spawn_control_socket;
+
+#[cfg(unix)]
+mod server {
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{UnixListener, UnixStream};
+    use tracing::{debug, error, info, warn};
+
+    use crate::common::logging::events;
+    use crate::structures::Metrics;
+
+    pub fn spawn_control_socket(path: PathBuf, metrics: Arc<Metrics>) {
+        let listener = match bind(&path) {
+            Ok(l) => l,
+            Err(e) => {
+                error!(event = events::CONFIG_ERROR, subsystem = "control", path = %path.display(), error = %e, msg = "Cannot open control socket");
+                return;
+            }
+        };
+        info!(event = events::APP_READY, subsystem = "control", path = %path.display(), msg = "Control socket listening");
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        tokio::spawn(serve(stream, metrics.clone()));
+                    }
+                    Err(e) => warn!(event = events::CONFIG_ERROR, subsystem = "control", error = %e, msg = "Control socket accept failed"),
+                }
+            }
+        });
+    }
+
+    // Un socket laissé par une exécution précédente est remplacé; tout autre fichier est conservé.
+    // Le socket est créé dans un répertoire privé (0700) voisin, passé en 0660, puis renommé à sa place:
+    // il n'est jamais joignable avec les droits par défaut (umask), sans toucher à l'umask du processus
+    pub(super) fn bind(path: &Path) -> std::io::Result<UnixListener> {
+        if let Ok(meta) = std::fs::symlink_metadata(path) {
+            if !meta.file_type().is_socket() {
+                return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, "path exists and is not a socket"));
+            }
+            std::fs::remove_file(path)?;
+        }
+        let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let name = path.file_name().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "control socket path has no file name"))?;
+        let staging = parent.join(format!(".{}.{}", name.to_string_lossy(), std::process::id()));
+        std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+        let staged = staging.join("socket");
+        let bound = UnixListener::bind(&staged).and_then(|listener| {
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o660))?;
+            std::fs::rename(&staged, path)?;
+            Ok(listener)
+        });
+        let _ = std::fs::remove_dir_all(&staging);
+        bound
+    }
+
+    async fn serve(stream: UnixStream, metrics: Arc<Metrics>) {
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = match super::parse_command(&line) {
+                Ok(cmd) => {
+                    debug!(event = events::CONTROL_COMMAND, subsystem = "control", command = ?cmd, msg = "Control command");
+                    super::execute(&metrics, cmd).await
+                }
+                Err(e) => super::error_reply(e),
+            };
+            let mut out = reply.to_string();
+            out.push('\n');
+            if write.write_all(out.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert!(matches!(parse_command(r#"{"cmd":"stats"}"#), Ok(ControlCommand::Stats)));
+        assert!(matches!(parse_command(r#"{"cmd":"stop","relay_id":"ab12"}"#), Ok(ControlCommand::Stop { relay_id }) if relay_id == "ab12"));
+        assert!(matches!(parse_command(r#"{"cmd":"set_rate_limit","relay_id":"ab12","max_bitrate":null}"#), Ok(ControlCommand::SetRateLimit { max_bitrate: None, .. })));
+        assert!(matches!(parse_command(r#"{"cmd":"switch_output","relay_id":"ab12","output":"srt://h:1"}"#), Ok(ControlCommand::SwitchOutput { overlap_ms: 500, .. })));
+        match parse_command(r#"{"cmd":"start","input":"srt://@:9000","output":"rist://h:1","latency_ms":120}"#) {
+            Ok(ControlCommand::Start(cfg)) => assert_eq!(cfg.latency_ms, 120),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_bad_commands() {
+        assert!(parse_command("not json").is_err());
+        assert!(parse_command(r#"{"cmd":"reboot"}"#).is_err());
+        assert!(parse_command(r#"{"cmd":"stop"}"#).is_err());
+        assert!(parse_command(r#"{"cmd":"add_output","relay_id":"ab12"}"#).is_err());
+        assert!(parse_command(r#"{"cmd":"set_rate_limit","relay_id":"ab12","max_bitrate":-1}"#).is_err());
+        assert!(parse_command(r#"{"cmd":"start","input":"srt://@:1","output":"srt://h:2","bogus":1}"#).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn socket_is_in_place_with_restricted_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("stream-relay-control-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("control.sock");
+        let listener = server::bind(&path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o660);
+        // Seul le socket reste dans le répertoire: le répertoire de création a été retiré
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let client = tokio::net::UnixStream::connect(&path).await;
+        assert!(client.is_ok());
+        assert!(listener.accept().await.is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,76 +2,178 @@ mod structures;
 mod web;
 mod relay;
 mod common;
+mod control;
 
 use clap::{Parser, Subcommand};
-use rocket::{routes, Rocket, Build};
+use rocket::{catchers, routes, Rocket, Build};
 use rocket::fairing::AdHoc;
-use tracing::{info, debug};
-use crate::common::logging::{self, events};
+use tracing::{info, debug, error};
+use crate::common::features;
+use crate::common::config::{relays_from_env, FileConfig, RelayConfig, RistAutoConfig, SrtAutoConfig, EXAMPLE_CONFIG};
+use crate::common::logging::{self, events, LogFormat, LogOptions};
+use crate::relay::pipe::PipeOptions;
+use crate::relay::reconnect::ReconnectPolicy;
+use crate::relay::registry::TransportParams;
+
+// Réglages du serveur HTTP et de ses tâches de fond, tirés de la ligne de commande et du fichier --config
+struct ServerOptions {
+    prefix: web::HttpPrefix,
+    admin: web::auth::AdminAuth,
+    trust_proxy: bool,
+    limits: web::timeout::HttpLimits,
+    tls: Option<web::tls::TlsFiles>,
+    stats_fields: Option<structures::StatsFields>,
+    control_socket: Option<std::path::PathBuf>,
+    sample_interval: std::time::Duration,
+    relay_stats_interval: Option<std::time::Duration>,
+    stall_threshold: Option<std::time::Duration>,
+    statsd: Option<structures::statsd::StatsdConfig>,
+}
 
 // Constructeur de l'instance Rocket avec routes et fairings
-fn build_rocket() -> Rocket<Build> {
+fn build_rocket(opts: ServerOptions, relays: Vec<RelayConfig>) -> Rocket<Build> {
+    let ServerOptions { prefix, admin, trust_proxy, limits, tls, stats_fields, control_socket, sample_interval, relay_stats_interval, stall_threshold, statsd } = opts;
     let metrics = std::sync::Arc::new(structures::Metrics::new());
     structures::Metrics::set_global(metrics.clone());
+    let control_metrics = metrics.clone();
 
-    rocket::build()
+    // Configuration Rocket habituelle (Rocket.toml, ROCKET_*), plus le TLS de --tls-cert / --tls-key
+    let mut figment = rocket::Config::figment();
+    if let Some(tls) = tls {
+        figment = figment.merge(("tls.certs", tls.cert)).merge(("tls.key", tls.key));
+    }
+    let rocket = rocket::custom(figment)
         .manage(metrics)
-        .attach(web::HttpMetricsFairing)
-        .attach(AdHoc::on_liftoff("auto-probes", |rocket| Box::pin(async move {
-            // Lancement automatique des probes SRT/RIST après le démarrage du serveur HTTP
-            // Les valeurs par défaut peuvent être surchargées via des variables d'environnement.
-            // SRTRIST_AUTO_SRT=0 ou SRTRIST_AUTO_RIST=0 pour désactiver un protocole.
-            // SRT: SRTRIST_SRT_INPUT, SRTRIST_SRT_OUTPUT, SRTRIST_SRT_LATENCY_MS
-            // RIST: SRTRIST_RIST_INPUT, SRTRIST_RIST_OUTPUT
-            #[cfg(feature = "srt")]
-            {
-                let auto = std::env::var("SRTRIST_AUTO_SRT").ok().map(|v| v != "0").unwrap_or(true);
-                if auto {
-                    let input = std::env::var("SRTRIST_SRT_INPUT").unwrap_or_else(|_| "srt://@:9000?mode=listener".to_string());
-                    let output = std::env::var("SRTRIST_SRT_OUTPUT").unwrap_or_else(|_| "srt://127.0.0.1:10000?mode=caller".to_string());
-                    let latency_ms: u64 = std::env::var("SRTRIST_SRT_LATENCY_MS")
-                        .ok()
-                        .and_then(|s| s.parse().ok())
-                        .unwrap_or(80);
-                    info!(event = events::RELAY_START, subsystem = "srt", protocol = "srt", msg = "Auto SRT probe enabled");
-                    debug!(event = events::RELAY_START, subsystem = "srt", protocol = "srt", input = %input, output = %output, latency_ms = latency_ms, msg = "SRT defaults");
-                    crate::relay::start_srt_auto(input, output, latency_ms);
-                } else {
-                    info!(event = events::RELAY_STOP, subsystem = "srt", protocol = "srt", msg = "Auto SRT probe disabled via SRTRIST_AUTO_SRT=0");
+        .manage(prefix.clone())
+        .manage(admin)
+        .manage(web::routes::DefaultStatsFields(stats_fields))
+        .attach(web::HttpMetricsFairing { trust_proxy, slow_request: limits.slow_request })
+        .attach(AdHoc::on_liftoff("sampler", move |rocket| Box::pin(async move {
+            if let Some(metrics) = rocket.state::<std::sync::Arc<structures::Metrics>>() {
+                metrics.clone().spawn_sampler(sample_interval);
+                if let Some(period) = relay_stats_interval {
+                    metrics.clone().spawn_relay_summaries(period);
+                }
+                if let Some(threshold) = stall_threshold {
+                    metrics.clone().spawn_stall_supervisor(threshold);
                 }
             }
-
-            #[cfg(feature = "rist")]
+        })))
+        .attach(AdHoc::on_liftoff("statsd", move |rocket| Box::pin(async move {
+            // Export StatsD optionnel (--statsd-addr)
+            if let (Some(cfg), Some(metrics)) = (statsd, rocket.state::<std::sync::Arc<structures::Metrics>>()) {
+                structures::statsd::StatsdExporter::new(cfg).spawn(metrics.clone());
+            }
+        })))
+        .attach(AdHoc::on_liftoff("configured-relays", move |_| Box::pin(async move {
+            // Relais déclarés dans le fichier --config
+            for cfg in relays {
+                crate::relay::start_relay(cfg).await;
+            }
+        })))
+        .attach(AdHoc::on_liftoff("control-socket", move |_| Box::pin(async move {
+            // Socket de contrôle UNIX optionnel (--control-socket)
+            let Some(path) = control_socket else { return };
+            #[cfg(unix)]
+            control::spawn_control_socket(path, control_metrics);
+            #[cfg(not(unix))]
             {
-                let auto = std::env::var("SRTRIST_AUTO_RIST").ok().map(|v| v != "0").unwrap_or(true);
-                if auto {
-                    let input = std::env::var("SRTRIST_RIST_INPUT").unwrap_or_else(|_| "rist://@:10000?mode=listener".to_string());
-                    let output = std::env::var("SRTRIST_RIST_OUTPUT").unwrap_or_else(|_| "rist://127.0.0.1:11000?mode=caller".to_string());
-                    info!(event = events::RELAY_START, subsystem = "rist", protocol = "rist", msg = "Auto RIST probe enabled");
-                    debug!(event = events::RELAY_START, subsystem = "rist", protocol = "rist", input = %input, output = %output, msg = "RIST defaults");
-                    crate::relay::start_rist_auto(input, output);
-                } else {
-                    info!(event = events::RELAY_STOP, subsystem = "rist", protocol = "rist", msg = "Auto RIST probe disabled via SRTRIST_AUTO_RIST=0");
-                }
+                let _ = control_metrics;
+                tracing::warn!(event = events::CONFIG_ERROR, subsystem = "control", path = %path.display(), msg = "Control sockets are only supported on Unix");
             }
+        })))
+        .attach(AdHoc::on_liftoff("auto-probes", |rocket| Box::pin(async move {
+            start_env_relays().await;
 
             // Afficher l'adresse HTTP effective + URLs utiles
             let addr = rocket.config().address;
             let port = rocket.config().port;
-            info!(event = events::APP_READY, subsystem = "http", msg = "HTTP server listening", address = %addr, port = port);
-            debug!(event = events::APP_READY, subsystem = "http", msg = "Useful URLs", health = format!("http://{}:{}/health", addr, port), stats = format!("http://{}:{}/stats", addr, port), metrics = format!("http://{}:{}/metrics", addr, port));
+            let scheme = if rocket.config().tls_enabled() { "https" } else { "http" };
+            let prefix = rocket.state::<web::HttpPrefix>().cloned().unwrap_or_default();
+            info!(event = events::APP_READY, subsystem = "http", msg = "HTTP server listening", address = %addr, port = port, scheme = scheme);
+            debug!(event = events::APP_READY, subsystem = "http", msg = "Useful URLs", prefix = prefix.base(), health = format!("{}://{}:{}{}", scheme, addr, port, prefix.path("/health")), stats = format!("{}://{}:{}{}", scheme, addr, port, prefix.path("/stats")), metrics = format!("{}://{}:{}{}", scheme, addr, port, prefix.path("/metrics")));
         })))
         .attach(AdHoc::on_shutdown("log-shutdown", |_| Box::pin(async move {
             info!(event = events::APP_SHUTDOWN, msg = "Application shutting down");
         })))
         .mount(
-            "/",
-            routes![
+            prefix.base(),
+            web::timeout::with_timeout(routes![
                 web::routes::health,
+                web::routes::healthz,
+                web::routes::ready,
                 web::routes::stats_endpoint,
-                web::routes::metrics_export
-            ],
+                web::routes::stats_grouped,
+                web::routes::relays_list,
+                web::routes::relay_detail,
+                web::routes::relay_logs,
+                web::routes::relay_metrics,
+                web::routes::relay_switch_output,
+                web::routes::relay_pause,
+                web::routes::relay_resume,
+                web::routes::relay_capture,
+                web::routes::probe_output,
+                web::routes::metrics_export,
+                web::routes::metrics_reset
+            ], limits),
         )
+        .register(prefix.base(), catchers![web::error::default_catcher]);
+    #[cfg(feature = "debug-endpoints")]
+    let rocket = rocket.mount(prefix.base(), web::timeout::with_timeout(routes![web::debug::debug_tasks], limits));
+    rocket
+}
+
+// Relais lancés depuis l'environnement au démarrage du serveur HTTP
+async fn start_env_relays() {
+    // Lancement automatique des probes SRT/RIST après le démarrage du serveur HTTP
+    // Les valeurs par défaut peuvent être surchargées via des variables d'environnement.
+    // SRTRIST_AUTO_SRT=0 ou SRTRIST_AUTO_RIST=0 pour désactiver un protocole.
+    // Le parsing et la validation sont centralisés dans common::config.
+    // SRT: SRTRIST_SRT_INPUT, SRTRIST_SRT_OUTPUT, SRTRIST_SRT_LATENCY_MS
+    // RIST: SRTRIST_RIST_INPUT, SRTRIST_RIST_OUTPUT
+    // SRTRIST_RELAYS (liste JSON de relais), si présente, remplace toutes ces variables.
+    match relays_from_env() {
+        Err(e) => {
+            error!(event = events::CONFIG_ERROR, subsystem = "relay", error = %e, msg = "Invalid SRTRIST_RELAYS, no relay started from the environment");
+            return;
+        }
+        Ok(Some(relays)) => {
+            info!(event = events::RELAY_START, subsystem = "relay", count = relays.len(), msg = "Starting relays from SRTRIST_RELAYS (individual SRTRIST_* probe variables ignored)");
+            for cfg in relays {
+                crate::relay::start_relay(cfg).await;
+            }
+            return;
+        }
+        Ok(None) => {}
+    }
+
+    match SrtAutoConfig::from_env() {
+        Err(e) => error!(event = events::CONFIG_ERROR, subsystem = "srt", protocol = "srt", error = %e, msg = "Invalid auto SRT configuration, probe not started"),
+        Ok(cfg) if !cfg.enabled => info!(event = events::RELAY_STOP, subsystem = "srt", protocol = "srt", msg = "Auto SRT probe disabled (SRTRIST_AUTO_SRT)"),
+        Ok(cfg) => {
+            info!(event = events::RELAY_START, subsystem = "srt", protocol = "srt", msg = "Auto SRT probe enabled");
+            debug!(event = events::RELAY_START, subsystem = "srt", protocol = "srt", input = %cfg.input, output = %cfg.output, latency_ms = cfg.latency_ms, msg = "SRT defaults");
+            if features::SRT_NATIVE {
+                crate::relay::start_srt_auto(cfg.input, cfg.output, cfg.latency_ms);
+            } else {
+                tracing::warn!(event = events::CONFIG_ERROR, subsystem = "srt", protocol = "srt", msg = "SRTRIST_AUTO_SRT set but libsrt is not linked in; rebuild with --features srt-native");
+            }
+        }
+    }
+
+    match RistAutoConfig::from_env() {
+        Err(e) => error!(event = events::CONFIG_ERROR, subsystem = "rist", protocol = "rist", error = %e, msg = "Invalid auto RIST configuration, probe not started"),
+        Ok(cfg) if !cfg.enabled => info!(event = events::RELAY_STOP, subsystem = "rist", protocol = "rist", msg = "Auto RIST probe disabled (SRTRIST_AUTO_RIST)"),
+        Ok(cfg) => {
+            info!(event = events::RELAY_START, subsystem = "rist", protocol = "rist", msg = "Auto RIST probe enabled");
+            debug!(event = events::RELAY_START, subsystem = "rist", protocol = "rist", input = %cfg.input, output = %cfg.output, msg = "RIST defaults");
+            if features::RIST_NATIVE {
+                crate::relay::start_rist_auto(cfg.input, cfg.output);
+            } else {
+                tracing::warn!(event = events::CONFIG_ERROR, subsystem = "rist", protocol = "rist", msg = "SRTRIST_AUTO_RIST set but librist is not linked in; rebuild with --features rist-native");
+            }
+        }
+    }
 }
 
 #[derive(Debug, Parser)]
@@ -80,9 +182,87 @@ struct Cli {
     /// Global: HTTP bind address (not yet wired)
     #[arg(long, global = true, default_value = "127.0.0.1:8000")]
     http_addr: String,
+    /// Global: TOML configuration file declaring relays (see `init-config`)
+    #[arg(long, global = true, env = "SRTRIST_CONFIG")]
+    config: Option<std::path::PathBuf>,
+    /// Global: base path under which HTTP routes are mounted (e.g. /relay)
+    #[arg(long, global = true, env = "SRTRIST_HTTP_PREFIX", default_value = "/")]
+    http_prefix: String,
+    /// Global: bearer token required by admin routes (POST /metrics/reset, POST /relays/<id>/switch-output, /pause, /resume, GET /relays/<id>/capture, POST /probe-output); unset = admin routes disabled
+    #[arg(long, global = true, env = "SRTRIST_ADMIN_TOKEN", hide_env_values = true)]
+    admin_token: Option<String>,
+    /// Global: PEM certificate chain; with --tls-key, the HTTP API is served over HTTPS only
+    #[arg(long, global = true, env = "SRTRIST_TLS_CERT")]
+    tls_cert: Option<std::path::PathBuf>,
+    /// Global: PEM private key (PKCS#8, PKCS#1 or SEC1) matching --tls-cert
+    #[arg(long, global = true, env = "SRTRIST_TLS_KEY")]
+    tls_key: Option<std::path::PathBuf>,
+    /// Global: take the client IP, scheme and host of the http_response access log from the
+    /// X-Forwarded-For / X-Real-IP / X-Forwarded-Proto / X-Forwarded-Host headers of a reverse proxy.
+    /// Only enable behind a proxy that sets them: any direct client can forge these headers
+    #[arg(long, global = true, env = "SRTRIST_TRUST_PROXY")]
+    trust_proxy: bool,
+    /// Global: abort an HTTP handler still running after this many ms and answer 504 (0 = no limit;
+    /// GET /relays/<id>/capture is bounded by its own duration instead)
+    #[arg(long, global = true, env = "SRTRIST_HTTP_REQUEST_TIMEOUT_MS", default_value_t = 30_000)]
+    http_request_timeout_ms: u64,
+    /// Global: log a warning for HTTP requests slower than this many ms (0 = never)
+    #[arg(long, global = true, env = "SRTRIST_HTTP_SLOW_REQUEST_MS", default_value_t = 1000)]
+    http_slow_request_ms: u64,
+    /// Global: extra comma-separated query/fragment keys whose values are masked in logs, /relays and
+    /// errors (e.g. session_cookie), on top of psk, token, pass, passphrase, secret, key, auth, ...
+    #[arg(long, global = true, env = "SRTRIST_REDACT_KEYS")]
+    redact_keys: Option<String>,
+    /// Global: UNIX socket accepting newline-delimited JSON control commands (stats, list, start, stop)
+    #[arg(long, global = true, env = "SRTRIST_CONTROL_SOCKET")]
+    control_socket: Option<std::path::PathBuf>,
+    /// Global: Tokio worker threads (default: one per CPU core)
+    #[arg(long, global = true, env = "SRTRIST_WORKER_THREADS")]
+    worker_threads: Option<std::num::NonZeroUsize>,
+    /// Global: stack size of Tokio worker threads, in bytes (default: Tokio's 2 MiB)
+    #[arg(long, global = true, env = "SRTRIST_THREAD_STACK_SIZE")]
+    thread_stack_size: Option<std::num::NonZeroUsize>,
+    /// Global: upper bound of the blocking thread pool running blocking transport (FFI) calls (default: 512)
+    #[arg(long, global = true, env = "SRTRIST_MAX_BLOCKING_THREADS")]
+    max_blocking_threads: Option<std::num::NonZeroUsize>,
+    /// Global: interval of the background sampler refreshing derived metrics (bps gauges, jitter,
+    /// buffer occupancy), in ms. Keep it at or below the Prometheus scrape interval
+    #[arg(long, global = true, env = "SRTRIST_SAMPLE_INTERVAL_MS", default_value = "1000")]
+    sample_interval_ms: std::num::NonZeroU64,
+    /// Global: log a relay_stats summary line per active relay every this many seconds (traffic since
+    /// the previous line, Mbps, drops, RTT); 0 = off
+    #[arg(long, global = true, env = "SRTRIST_RELAY_STATS_INTERVAL_SECS", default_value_t = 10)]
+    relay_stats_interval_secs: u64,
+    /// Global: flag a relay as "stalled" in /health and /relays when its pipe loop has not run for
+    /// this many seconds (task deadlocked in a blocking call); 0 = off
+    #[arg(long, global = true, env = "SRTRIST_STALL_THRESHOLD_SECS", default_value_t = 10)]
+    stall_threshold_secs: u64,
+    /// Global: push key metrics to this StatsD / DogStatsD agent (host:port, UDP) with relay_id and
+    /// protocol tags; unset = off
+    #[arg(long, global = true, env = "SRTRIST_STATSD_ADDR")]
+    statsd_addr: Option<String>,
+    /// Global: StatsD push interval in seconds
+    #[arg(long, global = true, env = "SRTRIST_STATSD_INTERVAL_SECS", default_value = "10")]
+    statsd_interval_secs: std::num::NonZeroU64,
     /// Global: log level (not yet wired)
     #[arg(long, global = true, default_value = "info")]
     log_level: String,
+    /// Global: log output format
+    #[arg(long, global = true, env = "SRTRIST_LOG_FORMAT", value_enum, default_value_t = LogFormat::Json)]
+    log_format: LogFormat,
+    /// Global: also write logs to a daily-rotated file in this directory
+    #[arg(long, global = true, env = "SRTRIST_LOG_DIR")]
+    log_dir: Option<std::path::PathBuf>,
+    /// Global: with --log-dir, write to the file only (no stdout)
+    #[arg(long, global = true, env = "SRTRIST_LOG_FILE_ONLY")]
+    log_file_only: bool,
+    /// Global: when relay, srt2srt or rist2rist ends, print a JSON summary line to stdout (after the
+    /// logs); a failed run exits with status 1 with or without it
+    #[arg(long, global = true)]
+    json_summary: bool,
+    /// Global: like --json-summary, but write the summary to this file, away from the logs
+    #[arg(long, global = true)]
+    json_summary_file: Option<std::path::PathBuf>,
 
     #[command(subcommand)]
     command: Option<Commands>,
@@ -90,15 +270,34 @@ struct Cli {
 
 #[derive(Debug, Subcommand)]
 enum Commands {
+    /// Print a commented example configuration file to stdout
+    InitConfig,
+    /// Loop a known payload through every transport on loopback and report PASS/FAIL
+    Selftest,
+    /// Relay input->output, protocols inferred from the URI schemes (e.g. srt:// in, rist:// out)
+    Relay {
+        /// Input URI (e.g., srt://@:9000?mode=listener)
+        #[arg(long)]
+        input: String,
+        /// Output URI (e.g., rist://127.0.0.1:11000); several comma-separated URIs fan out to each target
+        #[arg(long)]
+        output: String,
+        /// Latency in milliseconds (SRT endpoints); a ?latency= URI parameter wins for that endpoint
+        #[arg(long, default_value_t = 80)]
+        latency_ms: u64,
+        /// Give up after this many consecutive reconnect attempts (default: retry forever)
+        #[arg(long)]
+        max_reconnects: Option<u32>,
+    },
     /// Probe SRT input->output without piping payloads
     Srt2srt {
         /// Input URI (e.g., srt://@:9000?mode=listener)
         #[arg(long)]
         input: String,
-        /// Output URI (e.g., srt://127.0.0.1:10000?mode=caller)
+        /// Output URI (e.g., srt://127.0.0.1:10000?mode=caller); comma-separated URIs fan out to each target
         #[arg(long)]
         output: String,
-        /// Latency in milliseconds
+        /// Latency in milliseconds; a ?latency= URI parameter wins for that endpoint
         #[arg(long, default_value_t = 80)]
         latency_ms: u64,
     },
@@ -107,39 +306,202 @@ enum Commands {
         /// Input URI (e.g., rist://@:9000?mode=listener)
         #[arg(long)]
         input: String,
-        /// Output URI
+        /// Output URI; comma-separated URIs fan out to each target
         #[arg(long)]
         output: String,
     },
 }
 
-#[rocket::main]
-async fn main() -> Result<(), rocket::Error> {
-    // Init JSON logger (stdout)
-    logging::init();
+// Destination du bilan JSON d'une commande relay / srt2srt / rist2rist
+enum SummaryOutput {
+    Disabled,
+    Stdout,
+    File(std::path::PathBuf),
+}
 
-    // Minimal audit log at start
-    info!(event = events::APP_START, msg = "Application starting", version = env!("CARGO_PKG_VERSION"), os = std::env::consts::OS);
+impl Cli {
+    fn json_summary_output(&self) -> SummaryOutput {
+        match (&self.json_summary_file, self.json_summary) {
+            (Some(path), _) => SummaryOutput::File(path.clone()),
+            (None, true) => SummaryOutput::Stdout,
+            (None, false) => SummaryOutput::Disabled,
+        }
+    }
+}
+
+// Exécution en ligne de commande: les compteurs globaux (sans serveur HTTP) alimentent le bilan final
+struct CliRun {
+    command: &'static str,
+    input: String,
+    output: String,
+    started: std::time::Instant,
+}
+
+impl CliRun {
+    fn start(command: &'static str, input: &str, output: &str) -> Self {
+        structures::Metrics::set_global(std::sync::Arc::new(structures::Metrics::new()));
+        Self {
+            command,
+            input: common::uri::redact_uri_secrets(input),
+            output: common::uri::redact_uri_list(output),
+            started: std::time::Instant::now(),
+        }
+    }
 
+    // Code de sortie de la commande, rendu par main: 1 si elle a échoué, avec ou sans bilan demandé
+    fn finish(self, out: &SummaryOutput, result: anyhow::Result<()>) -> std::process::ExitCode {
+        let code = if result.is_err() { std::process::ExitCode::FAILURE } else { std::process::ExitCode::SUCCESS };
+        let Some(metrics) = structures::Metrics::global() else { return code };
+        let error = result.err().map(|e| e.to_string());
+        let summary = structures::RunSummary::collect(self.command, self.input, self.output, self.started, metrics, error);
+        let json = serde_json::to_string(&summary).unwrap_or_default();
+        match out {
+            SummaryOutput::Disabled => return code,
+            SummaryOutput::Stdout => println!("{}", json),
+            SummaryOutput::File(path) => {
+                if let Err(e) = std::fs::write(path, format!("{}\n", json)) {
+                    error!(event = events::CONFIG_ERROR, path = %path.display(), error = %e, msg = "Cannot write the JSON summary file");
+                    return std::process::ExitCode::FAILURE;
+                }
+            }
+        }
+        std::process::ExitCode::from(u8::try_from(summary.exit_code).unwrap_or(1))
+    }
+}
+
+// Runtime Tokio construit explicitement (plutôt que #[rocket::main]) pour régler le nombre de
+// workers et la taille de pile depuis la ligne de commande / l'environnement
+fn main() -> Result<std::process::ExitCode, Box<rocket::Error>> {
     let cli = Cli::parse();
 
+    // Printed before logging starts so the output can be redirected to a file as is
+    if matches!(cli.command, Some(Commands::InitConfig)) {
+        print!("{}", EXAMPLE_CONFIG);
+        return Ok(std::process::ExitCode::SUCCESS);
+    }
+    // Avant toute URI journalisée: le jeu de clés est figé au premier masquage
+    if let Some(keys) = &cli.redact_keys {
+        common::uri::configure_secret_keys(keys);
+    }
+
+    let mut builder = rocket::tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all().thread_name("stream-relay-worker");
+    if let Some(n) = cli.worker_threads {
+        builder.worker_threads(n.get());
+    }
+    if let Some(size) = cli.thread_stack_size {
+        builder.thread_stack_size(size.get());
+    }
+    if let Some(n) = cli.max_blocking_threads {
+        builder.max_blocking_threads(n.get());
+    }
+    let runtime = match builder.build() {
+        Ok(rt) => rt,
+        Err(e) => {
+            eprintln!("cannot start the async runtime: {}", e);
+            std::process::exit(1);
+        }
+    };
+    runtime.block_on(run(cli))
+}
+
+// Une erreur de démarrage rend un code de sortie au lieu d'appeler process::exit: le garde des logs
+// est libéré (fichier vidé) avant la fin du processus
+async fn run(cli: Cli) -> Result<std::process::ExitCode, Box<rocket::Error>> {
+    // Init logger (stdout, JSON by default); the guard flushes the file writer on exit
+    let _log_guard = logging::init(&LogOptions {
+        format: cli.log_format,
+        file_dir: cli.log_dir.clone(),
+        stdout: !cli.log_file_only,
+    });
+
+    // Minimal audit log at start
+    info!(event = events::APP_START, msg = "Application starting", version = env!("CARGO_PKG_VERSION"), os = std::env::consts::OS, features = %features::enabled_features(), worker_threads = rocket::tokio::runtime::Handle::current().metrics().num_workers());
+
+    let summary_output = cli.json_summary_output();
     if let Some(cmd) = cli.command {
         match cmd {
+            Commands::InitConfig => unreachable!("handled before logging init"),
+            Commands::Selftest => {
+                let reports = relay::selftest::run_selftest().await;
+                for r in &reports {
+                    let verdict = if r.passed() { "PASS" } else { "FAIL" };
+                    println!("{} {}: sent={} received={} corrupted={}{}", verdict, r.scheme, r.sent_bytes, r.received_bytes, r.corrupted,
+                        r.error.as_deref().map(|e| format!(" error={}", e)).unwrap_or_default());
+                }
+                let passed = reports.iter().all(|r| r.passed());
+                return Ok(if passed { std::process::ExitCode::SUCCESS } else { std::process::ExitCode::FAILURE });
+            }
+            Commands::Relay { input, output, latency_ms, max_reconnects } => {
+                let policy = ReconnectPolicy { max_attempts: max_reconnects, ..ReconnectPolicy::default() };
+                let run = CliRun::start("relay", &input, &output);
+                let result = async {
+                    let opts = PipeOptions::from_uris(&input, &output)?;
+                    relay::run_relay(input, output, TransportParams { latency_ms, ..TransportParams::default() }, policy, opts).await
+                };
+                let result = result.await;
+                if let Err(e) = &result {
+                    tracing::error!(event = events::RELAY_ERROR, subsystem = "relay", error = %e, msg = "Relay failed");
+                }
+                return Ok(run.finish(&summary_output, result));
+            }
             Commands::Srt2srt { input, output, latency_ms } => {
-                if let Err(e) = relay::run_srt_probe(input, output, latency_ms).await {
+                let run = CliRun::start("srt2srt", &input, &output);
+                let result = relay::run_srt_probe(input, output, latency_ms).await;
+                if let Err(e) = &result {
                     tracing::error!(event = events::RELAY_ERROR, subsystem = "srt", protocol = "srt", error = %e, msg = "SRT probe failed");
                 }
-                return Ok(());
+                return Ok(run.finish(&summary_output, result));
             }
             Commands::Rist2rist { input, output } => {
-                if let Err(e) = relay::run_rist_probe(input, output).await {
+                let run = CliRun::start("rist2rist", &input, &output);
+                let result = relay::run_rist_probe(input, output).await;
+                if let Err(e) = &result {
                     tracing::error!(event = events::RELAY_ERROR, subsystem = "rist", protocol = "rist", error = %e, msg = "RIST probe failed");
                 }
-                return Ok(());
+                return Ok(run.finish(&summary_output, result));
             }
         }
     }
 
-    build_rocket().launch().await?;
-    Ok(())
+    let file_config = match cli.config.as_deref().map(FileConfig::load).transpose() {
+        Ok(cfg) => cfg.unwrap_or_default(),
+        Err(e) => {
+            tracing::error!(event = events::CONFIG_ERROR, error = %e, msg = "Invalid configuration file");
+            return Ok(std::process::ExitCode::from(2));
+        }
+    };
+
+    let tls = match web::tls::TlsFiles::from_args(cli.tls_cert, cli.tls_key).and_then(|tls| tls.map(|t| t.validate().map(|()| t)).transpose()) {
+        Ok(tls) => tls,
+        Err(e) => {
+            tracing::error!(event = events::CONFIG_ERROR, subsystem = "http", error = %e, msg = "Invalid TLS configuration");
+            return Ok(std::process::ExitCode::from(2));
+        }
+    };
+
+    let statsd = match structures::statsd::StatsdConfig::from_args(cli.statsd_addr, std::time::Duration::from_secs(cli.statsd_interval_secs.get())) {
+        Ok(statsd) => statsd,
+        Err(e) => {
+            tracing::error!(event = events::CONFIG_ERROR, subsystem = "statsd", error = %e, msg = "Invalid StatsD configuration");
+            return Ok(std::process::ExitCode::from(2));
+        }
+    };
+
+    let millis = |ms: u64| (ms > 0).then(|| std::time::Duration::from_millis(ms));
+    let opts = ServerOptions {
+        prefix: web::HttpPrefix::new(&cli.http_prefix),
+        admin: web::auth::AdminAuth(cli.admin_token.filter(|t| !t.trim().is_empty())),
+        trust_proxy: cli.trust_proxy,
+        limits: web::timeout::HttpLimits { request_timeout: millis(cli.http_request_timeout_ms), slow_request: millis(cli.http_slow_request_ms) },
+        tls,
+        stats_fields: file_config.stats_fields,
+        control_socket: cli.control_socket,
+        sample_interval: std::time::Duration::from_millis(cli.sample_interval_ms.get()),
+        relay_stats_interval: Some(std::time::Duration::from_secs(cli.relay_stats_interval_secs)).filter(|d| !d.is_zero()),
+        stall_threshold: Some(std::time::Duration::from_secs(cli.stall_threshold_secs)).filter(|d| !d.is_zero()),
+        statsd,
+    };
+    build_rocket(opts, file_config.relays).launch().await?;
+    Ok(std::process::ExitCode::SUCCESS)
 }
@@ -0,0 +1,125 @@
+// Liste d'adresses sources autorisées sur une entrée (?allow=203.0.113.0/24,198.51.100.5): les
+// datagrammes d'une autre source sont écartés à la réception et comptés dans rejected_by_acl_total
+// (et dans pktRcvDrop de /stats).
+// Contrôle d'accès léger, sans toucher au pare-feu; une adresse seule vaut un /32 (ou /128).
+
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use tracing::warn;
+
+use crate::common::logging::{events, LogThrottle};
+use crate::common::uri::{query_param, redact_addr};
+use crate::structures::{Metrics, TResult, TransportError};
+
+pub struct SourceAcl {
+    nets: Vec<(IpAddr, u8)>,
+    log: LogThrottle,
+}
+
+impl SourceAcl {
+    pub fn parse(list: &str) -> Result<Self, String> {
+        let nets = list
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(parse_net)
+            .collect::<Result<Vec<_>, _>>()?;
+        if nets.is_empty() {
+            return Err("allow list is empty".into());
+        }
+        Ok(Self { nets, log: LogThrottle::new(Duration::from_secs(10)) })
+    }
+
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.nets.iter().any(|(net, prefix)| in_net(ip, *net, *prefix))
+    }
+
+    // Vérifie la source d'un datagramme reçu; un refus est compté et journalisé (au plus toutes les 10 s)
+    pub fn admit(&mut self, peer: SocketAddr, protocol: &'static str) -> bool {
+        if self.allows(peer.ip()) {
+            return true;
+        }
+        if let Some(m) = Metrics::global() {
+            m.rejected_by_acl_total.inc();
+            m.inc_pkt_drop();
+        }
+        if let Some(suppressed) = self.log.allow() {
+            warn!(event = events::SOURCE_REJECTED, subsystem = protocol, protocol = protocol, peer = %redact_addr(&peer), suppressed = suppressed, msg = "Datagram from a source outside the allow list dropped");
+        }
+        false
+    }
+}
+
+fn parse_net(item: &str) -> Result<(IpAddr, u8), String> {
+    let (addr, prefix) = match item.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix)),
+        None => (item, None),
+    };
+    let ip: IpAddr = addr.parse().map_err(|_| format!("invalid address in allow list: {}", item))?;
+    let max = if ip.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        None => max,
+        Some(p) => p.parse::<u8>().ok().filter(|p| *p <= max).ok_or_else(|| format!("invalid prefix length in allow list: {}", item))?,
+    };
+    Ok((ip.to_canonical(), prefix))
+}
+
+fn in_net(ip: IpAddr, net: IpAddr, prefix: u8) -> bool {
+    match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+// ?allow=... sur une URI d'entrée; None = toute source acceptée
+pub fn acl_from_uri(uri: &str) -> TResult<Option<SourceAcl>> {
+    query_param(uri, "allow").map(|list| SourceAcl::parse(&list).map_err(TransportError::InvalidUri)).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_cidrs_and_single_addresses() {
+        let acl = SourceAcl::parse("203.0.113.0/24, 198.51.100.5,2001:db8::/32").unwrap();
+        assert!(acl.allows("203.0.113.77".parse().unwrap()));
+        assert!(!acl.allows("203.0.114.1".parse().unwrap()));
+        assert!(acl.allows("198.51.100.5".parse().unwrap()));
+        assert!(!acl.allows("198.51.100.6".parse().unwrap()));
+        assert!(acl.allows("2001:db8:1::9".parse().unwrap()));
+        // Adresse IPv4 vue par un socket IPv6 (::ffff:a.b.c.d)
+        assert!(acl.allows("::ffff:203.0.113.9".parse().unwrap()));
+        assert!(SourceAcl::parse("0.0.0.0/0").unwrap().allows("8.8.8.8".parse().unwrap()));
+    }
+
+    #[test]
+    fn rejects_malformed_lists() {
+        assert!(SourceAcl::parse("").is_err());
+        assert!(SourceAcl::parse("10.0.0.0/33").is_err());
+        assert!(SourceAcl::parse("not-an-ip").is_err());
+        assert!(acl_from_uri("srt://@:9000").unwrap().is_none());
+        assert!(acl_from_uri("srt://@:9000?allow=10.0.0.0/8").unwrap().is_some());
+    }
+
+    #[test]
+    fn refused_sources_count_as_drops_not_loss() {
+        Metrics::set_global(std::sync::Arc::new(Metrics::new()));
+        let m = Metrics::global().unwrap();
+        let mut acl = SourceAcl::parse("10.0.0.0/8").unwrap();
+        let drops = m.pkt_rcv_drop_total.load(std::sync::atomic::Ordering::Relaxed);
+        assert!(acl.admit("10.1.2.3:5000".parse().unwrap(), "srt"));
+        assert!(!acl.admit("192.0.2.1:5000".parse().unwrap(), "srt"));
+        // Compteurs globaux partagés avec les autres tests: au moins ce refus
+        assert!(m.pkt_rcv_drop_total.load(std::sync::atomic::Ordering::Relaxed) > drops);
+    }
+}
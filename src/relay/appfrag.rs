@@ -0,0 +1,372 @@
+// Application-layer fragmentation (?appfrag=1), for a link whose path MTU is smaller than the datagrams
+// the input delivers. On an output, every buffer is cut into chunks that fit the output mtu (?mtu=, or
+// DEFAULT_MTU) and each chunk is prefixed with a small header; on an input, the matching receiver
+// reassembles the chunks into the original datagram. Both ends must use appfrag=1: the header is not
+// understood by anything else.
+//
+// Header (HEADER_LEN bytes): magic 0xAF, version 1, message id (u16, big-endian, wraps), fragment index,
+// fragment count. A datagram that fits in one fragment still carries the header (count = 1).
+// The receiver keeps up to MAX_PARTIALS messages in progress, keyed by message id: fragments of
+// different messages may interleave and arrive out of order. A message still incomplete after
+// PARTIAL_MAX_AGE, or pushed out by a newer one when the table is full, is abandoned (counted as
+// "incomplete").
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tracing::warn;
+
+use crate::common::logging::events;
+use crate::common::uri::query_param;
+use crate::relay::mtu::mtu_from_uri;
+use crate::relay::transport::{BufferOccupancy, EffectiveOptions, RxEndpoint, TransportMeta, TransportRx, TransportTx, TxEndpoint};
+use crate::structures::{Metrics, TResult, TransportError};
+
+const MAGIC: u8 = 0xAF;
+const VERSION: u8 = 1;
+pub const HEADER_LEN: usize = 6;
+const MAX_FRAGMENTS: usize = u8::MAX as usize;
+// Charge utile UDP d'un lien Ethernet à 1500 octets (IPv4)
+pub const DEFAULT_MTU: usize = 1472;
+const MAX_DATAGRAM: usize = 65_535;
+// Messages en cours de réassemblage et durée de vie d'un message incomplet: au-delà, un fragment
+// manquant est considéré perdu plutôt qu'en retard
+const MAX_PARTIALS: usize = 8;
+const PARTIAL_MAX_AGE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    msg_id: u16,
+    index: u8,
+    count: u8,
+}
+
+impl Header {
+    fn write(self, out: &mut Vec<u8>) {
+        out.push(MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&self.msg_id.to_be_bytes());
+        out.push(self.index);
+        out.push(self.count);
+    }
+
+    fn parse(datagram: &[u8]) -> Option<(Self, &[u8])> {
+        let (head, payload) = datagram.split_at_checked(HEADER_LEN)?;
+        if head[0] != MAGIC || head[1] != VERSION || head[5] == 0 || head[4] >= head[5] {
+            return None;
+        }
+        Some((Header { msg_id: u16::from_be_bytes([head[2], head[3]]), index: head[4], count: head[5] }, payload))
+    }
+}
+
+fn appfrag_from_uri(uri: &str) -> TResult<bool> {
+    match query_param(uri, "appfrag").map(|v| v.to_ascii_lowercase()).as_deref() {
+        None | Some("0") | Some("false") => Ok(false),
+        Some("1") | Some("true") => Ok(true),
+        Some(other) => Err(TransportError::InvalidUri(format!("appfrag must be 0/1 or true/false, got {}", other))),
+    }
+}
+
+fn count_failure(reason: &str) {
+    if let Some(m) = Metrics::global() {
+        m.appfrag_reassembly_failures_total.with_label_values(&[reason]).inc();
+        m.inc_pkt_drop();
+    }
+}
+
+pub struct FragmentingTx {
+    inner: Box<dyn TxEndpoint>,
+    mtu: usize,
+    next_id: u16,
+    frame: Vec<u8>,
+    warned: bool,
+}
+
+impl FragmentingTx {
+    pub fn new(inner: Box<dyn TxEndpoint>, mtu: usize) -> Self {
+        Self { inner, mtu, next_id: 0, frame: Vec::with_capacity(mtu), warned: false }
+    }
+}
+
+#[async_trait]
+impl TransportTx for FragmentingTx {
+    async fn send(&mut self, buf: &[u8]) -> TResult<usize> {
+        let chunk = self.mtu - HEADER_LEN;
+        let count = buf.len().div_ceil(chunk).max(1);
+        if count > MAX_FRAGMENTS {
+            if !self.warned {
+                self.warned = true;
+                warn!(event = events::DATAGRAM_OVERSIZED, subsystem = "net", len = buf.len(), mtu = self.mtu, action = "rejected", output = %self.inner.describe(), msg = "Datagram needs more application-layer fragments than the header allows (first occurrence)");
+            }
+            if let Some(m) = Metrics::global() {
+                m.oversized_datagrams_total.with_label_values(&["rejected"]).inc();
+            }
+            return Ok(0);
+        }
+        let msg_id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        // Un fragment écarté en aval (0 octet) perd tout le message: l'appel rapporte alors 0, sinon la
+        // taille du datagramme d'origine, en-têtes non comptés
+        let mut complete = true;
+        for index in 0..count {
+            let part = &buf[(index * chunk).min(buf.len())..((index + 1) * chunk).min(buf.len())];
+            self.frame.clear();
+            Header { msg_id, index: index as u8, count: count as u8 }.write(&mut self.frame);
+            self.frame.extend_from_slice(part);
+            complete &= self.inner.send(&self.frame).await? > 0;
+        }
+        if let Some(m) = Metrics::global() {
+            m.appfrag_fragments_sent_total.inc_by(count as u64);
+        }
+        Ok(if complete { buf.len() } else { 0 })
+    }
+}
+
+impl TransportMeta for FragmentingTx {
+    fn open(&mut self) -> TResult<()> {
+        self.inner.open()
+    }
+    fn close(&mut self) {
+        self.inner.close()
+    }
+    fn describe(&self) -> String {
+        format!("{} appfrag", self.inner.describe())
+    }
+    fn effective_options(&self) -> EffectiveOptions {
+        self.inner.effective_options()
+    }
+    fn buffer_occupancy(&self) -> Option<BufferOccupancy> {
+        self.inner.buffer_occupancy()
+    }
+    fn take_rtt_sample(&mut self) -> Option<Duration> {
+        self.inner.take_rtt_sample()
+    }
+    fn check_connectivity(&mut self, wait: Duration) -> TResult<()> {
+        self.inner.check_connectivity(wait)
+    }
+}
+
+// Message en cours de réassemblage
+struct Partial {
+    fragments: Vec<Option<Vec<u8>>>,
+    received: usize,
+    started: Instant,
+}
+
+// Réassemblage sans E/S; `failure` porte la cause d'un message perdu à cette étape et `abandoned` le
+// nombre de messages incomplets abandonnés (expirés ou évincés)
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Pushed {
+    message: Option<Vec<u8>>,
+    failure: Option<&'static str>,
+    abandoned: u64,
+}
+
+#[derive(Default)]
+struct Reassembler {
+    partials: HashMap<u16, Partial>,
+}
+
+impl Reassembler {
+    fn push(&mut self, datagram: &[u8], now: Instant) -> Pushed {
+        let Some((header, payload)) = Header::parse(datagram) else {
+            return Pushed { failure: Some("malformed"), ..Pushed::default() };
+        };
+        let before = self.partials.len();
+        self.partials.retain(|_, p| now.duration_since(p.started) < PARTIAL_MAX_AGE);
+        let mut abandoned = (before - self.partials.len()) as u64;
+        // Même identifiant avec un autre nombre de fragments: l'identifiant a bouclé sur un message perdu
+        if self.partials.get(&header.msg_id).is_some_and(|p| p.fragments.len() != header.count as usize) {
+            self.partials.remove(&header.msg_id);
+            abandoned += 1;
+        }
+        if !self.partials.contains_key(&header.msg_id) && self.partials.len() >= MAX_PARTIALS {
+            let oldest = self.partials.iter().min_by_key(|(_, p)| p.started).map(|(id, _)| *id);
+            if let Some(id) = oldest {
+                self.partials.remove(&id);
+                abandoned += 1;
+            }
+        }
+        let partial = self.partials.entry(header.msg_id).or_insert_with(|| Partial { fragments: vec![None; header.count as usize], received: 0, started: now });
+        let slot = &mut partial.fragments[header.index as usize];
+        // Doublon: le premier exemplaire est gardé
+        if slot.is_none() {
+            *slot = Some(payload.to_vec());
+            partial.received += 1;
+        }
+        if partial.received < partial.fragments.len() {
+            return Pushed { abandoned, ..Pushed::default() };
+        }
+        let message: Vec<u8> = self.partials.remove(&header.msg_id).into_iter().flat_map(|p| p.fragments).flatten().flatten().collect();
+        if message.len() > MAX_DATAGRAM {
+            return Pushed { failure: Some("oversized"), abandoned, ..Pushed::default() };
+        }
+        Pushed { message: Some(message), failure: None, abandoned }
+    }
+}
+
+pub struct ReassemblingRx {
+    inner: Box<dyn RxEndpoint>,
+    scratch: Vec<u8>,
+    reassembler: Reassembler,
+}
+
+impl ReassemblingRx {
+    pub fn new(inner: Box<dyn RxEndpoint>) -> Self {
+        Self { inner, scratch: vec![0u8; MAX_DATAGRAM], reassembler: Reassembler::default() }
+    }
+}
+
+#[async_trait]
+impl TransportRx for ReassemblingRx {
+    // Rend la main sur un message complet, ou sur toute erreur (timeout compris) du transport; un
+    // message plus long que `buf` est tronqué à sa taille, ce que le pipe traite comme un buffer trop petit
+    async fn recv(&mut self, buf: &mut [u8]) -> TResult<usize> {
+        loop {
+            let n = self.inner.recv(&mut self.scratch).await?;
+            if n == 0 {
+                return Ok(0);
+            }
+            let pushed = self.reassembler.push(&self.scratch[..n], Instant::now());
+            if let Some(reason) = pushed.failure {
+                count_failure(reason);
+            }
+            for _ in 0..pushed.abandoned {
+                count_failure("incomplete");
+            }
+            if let Some(message) = pushed.message {
+                let len = message.len().min(buf.len());
+                buf[..len].copy_from_slice(&message[..len]);
+                return Ok(len);
+            }
+        }
+    }
+}
+
+impl TransportMeta for ReassemblingRx {
+    fn open(&mut self) -> TResult<()> {
+        self.inner.open()
+    }
+    fn close(&mut self) {
+        self.reassembler = Reassembler::default();
+        self.inner.close()
+    }
+    fn describe(&self) -> String {
+        format!("{} appfrag", self.inner.describe())
+    }
+    fn effective_options(&self) -> EffectiveOptions {
+        self.inner.effective_options()
+    }
+    fn buffer_occupancy(&self) -> Option<BufferOccupancy> {
+        self.inner.buffer_occupancy()
+    }
+    fn take_rtt_sample(&mut self) -> Option<Duration> {
+        self.inner.take_rtt_sample()
+    }
+}
+
+// Wraps the sender when ?appfrag=1 is set on its URI (chunks sized to ?mtu, DEFAULT_MTU without it)
+pub fn wrap_tx(tx: Box<dyn TxEndpoint>, uri: &str) -> TResult<Box<dyn TxEndpoint>> {
+    if !appfrag_from_uri(uri)? {
+        return Ok(tx);
+    }
+    let mtu = mtu_from_uri(uri)?.map_or(DEFAULT_MTU, |(mtu, _)| mtu);
+    Ok(Box::new(FragmentingTx::new(tx, mtu)))
+}
+
+// Wraps the receiver when ?appfrag=1 is set on its URI
+pub fn wrap_rx(rx: Box<dyn RxEndpoint>, uri: &str) -> TResult<Box<dyn RxEndpoint>> {
+    Ok(if appfrag_from_uri(uri)? { Box::new(ReassemblingRx::new(rx)) } else { rx })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragments(msg_id: u16, payload: &[u8], chunk: usize) -> Vec<Vec<u8>> {
+        let parts: Vec<&[u8]> = payload.chunks(chunk).collect();
+        parts.iter().enumerate().map(|(i, part)| {
+            let mut frame = Vec::new();
+            Header { msg_id, index: i as u8, count: parts.len() as u8 }.write(&mut frame);
+            frame.extend_from_slice(part);
+            frame
+        }).collect()
+    }
+
+    #[test]
+    fn parses_the_flag() {
+        assert!(!appfrag_from_uri("srt://h:1").unwrap());
+        assert!(appfrag_from_uri("srt://h:1?appfrag=1").unwrap());
+        assert!(appfrag_from_uri("srt://h:1?appfrag=yes").is_err());
+    }
+
+    #[test]
+    fn reassembles_out_of_order_fragments() {
+        let payload: Vec<u8> = (0..4000u32).map(|i| i as u8).collect();
+        let mut frags = fragments(7, &payload, 1000);
+        frags.swap(0, 2);
+        let mut r = Reassembler::default();
+        let now = Instant::now();
+        for frag in &frags[..3] {
+            assert_eq!(r.push(frag, now), Pushed::default());
+        }
+        // Doublon ignoré
+        assert_eq!(r.push(&frags[1], now), Pushed::default());
+        assert_eq!(r.push(&frags[3], now).message, Some(payload));
+    }
+
+    #[test]
+    fn reassembles_interleaved_messages() {
+        let mut r = Reassembler::default();
+        let now = Instant::now();
+        let first = fragments(1, &[1u8; 300], 100);
+        let second = fragments(2, &[2u8; 200], 100);
+        assert_eq!(r.push(&first[0], now), Pushed::default());
+        assert_eq!(r.push(&second[1], now), Pushed::default());
+        assert_eq!(r.push(&first[2], now), Pushed::default());
+        assert_eq!(r.push(&second[0], now).message, Some(vec![2u8; 200]));
+        assert_eq!(r.push(&first[1], now).message, Some(vec![1u8; 300]));
+        assert!(r.partials.is_empty());
+    }
+
+    #[test]
+    fn counts_abandoned_and_malformed_messages() {
+        let mut r = Reassembler::default();
+        let start = Instant::now();
+        let first = fragments(1, &[1u8; 300], 100);
+        r.push(&first[0], start);
+        // Expiré: abandonné au fragment suivant, quel que soit son message
+        let second = fragments(2, &[2u8; 50], 100);
+        assert_eq!(r.push(&second[0], start + PARTIAL_MAX_AGE), Pushed { message: Some(vec![2u8; 50]), failure: None, abandoned: 1 });
+        // Table pleine: le plus ancien message incomplet laisse sa place
+        for id in 0..MAX_PARTIALS as u16 {
+            assert_eq!(r.push(&fragments(10 + id, &[0u8; 200], 100)[0], start + Duration::from_millis(id as u64)).abandoned, 0);
+        }
+        assert_eq!(r.push(&fragments(99, &[0u8; 200], 100)[0], start + Duration::from_millis(50)).abandoned, 1);
+        assert!(!r.partials.contains_key(&10));
+        assert_eq!(r.push(b"plain datagram", start).failure, Some("malformed"));
+        assert_eq!(r.push(&[MAGIC, VERSION, 0, 1, 3, 3], start).failure, Some("malformed"));
+    }
+
+    #[tokio::test]
+    async fn round_trips_over_udp() {
+        use crate::relay::registry::{TransportParams, TransportRegistry};
+        let registry = TransportRegistry::global();
+        // Port attribué par le système au socket d'écoute, sans fenêtre où un autre processus le prendrait
+        let input = "rist://@:0?appfrag=1";
+        let mut rx = wrap_rx(registry.build_rx(input, &TransportParams::default()).unwrap(), input).unwrap();
+        rx.open().unwrap();
+        let port = rx.effective_options().local_port.unwrap();
+        let output = format!("rist://127.0.0.1:{}?appfrag=1&mtu=500", port);
+        let mut tx = wrap_tx(registry.build_tx(&output, &TransportParams::default()).unwrap(), &output).unwrap();
+        tx.open().unwrap();
+        let payload: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+        assert_eq!(tx.send(&payload).await.unwrap(), payload.len());
+        let mut buf = vec![0u8; 8192];
+        let n = tokio::time::timeout(Duration::from_secs(2), rx.recv(&mut buf)).await.unwrap().unwrap();
+        assert_eq!(&buf[..n], &payload[..]);
+        tx.close();
+        rx.close();
+    }
+}
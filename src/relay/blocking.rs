@@ -0,0 +1,46 @@
+// Offloading of blocking setup calls to Tokio's blocking pool.
+//
+// Threading model: async worker threads never run a call that may block. The data path (recv / send)
+// uses non-blocking Tokio sockets; the blocking steps around it (output construction with its DNS
+// resolution, endpoint open, reachability probe) go through `run`. The pool is shared by the process
+// and bounded with --max-blocking-threads.
+
+use crate::structures::TResult;
+
+// One-off blocking step outside the data path: runs on the blocking pool so that a slow lookup or
+// open does not stall the other relays sharing the worker
+pub async fn run<T: Send + 'static>(call: impl FnOnce() -> TResult<T> + Send + 'static) -> TResult<T> {
+    tokio::task::spawn_blocking(call)
+        .await
+        .map_err(|e| std::io::Error::other(format!("blocking transport call failed: {}", e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn blocking_call_does_not_stall_the_runtime() {
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let counter = ticks.clone();
+        let ticker = tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        let value = run(|| {
+            std::thread::sleep(Duration::from_millis(50));
+            Ok(7)
+        })
+        .await
+        .unwrap();
+        assert_eq!(value, 7);
+        // The single worker thread kept running other tasks while the call blocked
+        assert!(ticks.load(Ordering::Relaxed) > 0);
+        ticker.abort();
+    }
+}
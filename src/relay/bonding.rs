@@ -0,0 +1,130 @@
+// Bonding RIST (?bonding=1) : une sortie listant plusieurs pairs (rist://10.0.0.1:1000,10.0.0.2:1000?bonding=1)
+// envoie sur chacun d'eux par son propre lien. Sans ?weights, chaque datagramme est dupliqué sur tous
+// les liens (redondance, poids 0 de librist); avec ?weights=5,1 les datagrammes sont répartis entre
+// les liens au prorata des poids (agrégation de débit), en basculant sur les autres si l'envoi échoue.
+// Ce n'est pas un fan-out (rist://a,rist://b): les liens forment une seule sortie, avec une seule
+// description et un seul échec (aucun lien n'a pu porter le datagramme); il faut au moins deux pairs.
+
+use crate::common::uri::query_param;
+use crate::structures::{TResult, TransportError};
+
+// Répartition pondérée lissée (smooth weighted round-robin): avec 5,1 la séquence est a a a b a a,
+// sans rafales sur un même lien
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeightedScheduler {
+    weights: Vec<u32>,
+    current: Vec<i64>,
+}
+
+impl WeightedScheduler {
+    pub fn new(weights: Vec<u32>) -> Self {
+        let current = vec![0; weights.len()];
+        Self { weights, current }
+    }
+
+    // Index du lien qui porte le prochain datagramme
+    pub fn next(&mut self) -> usize {
+        let total: i64 = self.weights.iter().map(|w| *w as i64).sum();
+        for (current, weight) in self.current.iter_mut().zip(&self.weights) {
+            *current += *weight as i64;
+        }
+        let best = (0..self.current.len()).max_by_key(|i| (self.current[*i], std::cmp::Reverse(*i))).unwrap_or(0);
+        self.current[best] -= total;
+        best
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bonding {
+    Duplicate,
+    Weighted(WeightedScheduler),
+}
+
+impl Bonding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Bonding::Duplicate => "duplicate",
+            Bonding::Weighted(_) => "weighted",
+        }
+    }
+
+    // Ordre d'essai des liens pour le prochain datagramme en mode pondéré: le lien choisi puis les suivants
+    pub fn failover_order(scheduler: &mut WeightedScheduler) -> impl Iterator<Item = usize> {
+        let links = scheduler.weights.len();
+        let first = scheduler.next();
+        (0..links).map(move |i| (first + i) % links)
+    }
+
+    pub fn describe(&self, links: usize) -> String {
+        let weights = match self {
+            Bonding::Duplicate => String::new(),
+            Bonding::Weighted(s) => format!(" weights={}", s.weights.iter().map(u32::to_string).collect::<Vec<_>>().join(",")),
+        };
+        format!(" bonding={} links={}{}", self.as_str(), links, weights)
+    }
+}
+
+// ?bonding=1 [&weights=W1,W2,...] pour une sortie à `peers` pairs; None sans bonding (un seul pair)
+pub fn bonding_from_uri(uri: &str, peers: usize) -> TResult<Option<Bonding>> {
+    let enabled = match query_param(uri, "bonding").map(|v| v.to_ascii_lowercase()).as_deref() {
+        None | Some("0") | Some("false") => false,
+        Some("1") | Some("true") => true,
+        Some(other) => return Err(TransportError::InvalidUri(format!("bonding must be 0 or 1, got {}", other))),
+    };
+    let weights = query_param(uri, "weights");
+    if !enabled {
+        if peers > 1 {
+            return Err(TransportError::InvalidUri(format!("{} peers listed in the output; add bonding=1 to bond them", peers)));
+        }
+        if weights.is_some() {
+            return Err(TransportError::InvalidUri("weights requires bonding=1".into()));
+        }
+        return Ok(None);
+    }
+    if peers < 2 {
+        return Err(TransportError::InvalidUri(format!("bonding=1 needs at least two peers, got {}", peers)));
+    }
+    let Some(raw) = weights else { return Ok(Some(Bonding::Duplicate)) };
+    let weights = raw
+        .split(',')
+        .map(|w| w.trim().parse::<u32>().ok().filter(|w| *w > 0))
+        .collect::<Option<Vec<u32>>>()
+        .ok_or_else(|| TransportError::InvalidUri(format!("weights must be positive integers, got {}", raw)))?;
+    if weights.len() != peers {
+        return Err(TransportError::InvalidUri(format!("weights lists {} values for {} peers", weights.len(), peers)));
+    }
+    Ok(Some(Bonding::Weighted(WeightedScheduler::new(weights))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bonding_params() {
+        assert_eq!(bonding_from_uri("rist://10.0.0.1:1000", 1).unwrap(), None);
+        assert_eq!(bonding_from_uri("rist://a:1,b:1?bonding=1", 2).unwrap(), Some(Bonding::Duplicate));
+        let Some(Bonding::Weighted(s)) = bonding_from_uri("rist://a:1,b:1?bonding=1&weights=5,1", 2).unwrap() else { panic!("weighted") };
+        assert_eq!(s.weights, vec![5, 1]);
+        assert!(bonding_from_uri("rist://a:1,b:1", 2).is_err());
+        assert!(bonding_from_uri("rist://a:1?weights=5", 1).is_err());
+        assert!(bonding_from_uri("rist://a:1,b:1?bonding=1&weights=5", 2).is_err());
+        assert!(bonding_from_uri("rist://a:1,b:1?bonding=1&weights=5,0", 2).is_err());
+        assert!(bonding_from_uri("rist://a:1?bonding=yes", 1).is_err());
+        assert!(bonding_from_uri("rist://a:1?bonding=1", 1).is_err());
+        assert!(bonding_from_uri("rist://a:1?bonding=1&weights=5", 1).is_err());
+    }
+
+    #[test]
+    fn spreads_datagrams_by_weight() {
+        let mut s = WeightedScheduler::new(vec![5, 1]);
+        let picks: Vec<usize> = (0..12).map(|_| s.next()).collect();
+        assert_eq!(picks.iter().filter(|i| **i == 1).count(), 2);
+        // Pas plus de trois datagrammes consécutifs sur le lien le plus lourd
+        assert_eq!(&picks[..6], &[0, 0, 0, 1, 0, 0]);
+        let mut s = WeightedScheduler::new(vec![1, 1, 1]);
+        assert_eq!((0..3).map(|_| s.next()).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(Bonding::failover_order(&mut s).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(Bonding::failover_order(&mut s).collect::<Vec<_>>(), vec![1, 2, 0]);
+    }
+}
@@ -0,0 +1,154 @@
+// Capture à la demande des datagrammes reçus par un relais (GET /relays/<id>/capture), au format pcap.
+// La capture est tenue en mémoire et bornée (durée, nombre de paquets, MAX_CAPTURE_BYTES): rien
+// n'est écrit sur disque. Le pipe ne voit que les charges utiles: chaque datagramme est enveloppé
+// d'en-têtes IPv4/UDP synthétiques (source 0.0.0.0:0, destination 127.0.0.1:<port d'entrée>) pour
+// que Wireshark le décode comme de l'UDP ("Decode As" MPEG-TS / RTP).
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::oneshot;
+
+use crate::structures::{TResult, TransportError};
+
+pub const DEFAULT_CAPTURE_SECS: u64 = 5;
+pub const MAX_CAPTURE_SECS: u64 = 60;
+pub const MAX_CAPTURE_PACKETS: u64 = 1_000_000;
+pub const MAX_CAPTURE_BYTES: usize = 32 * 1024 * 1024;
+
+// LINKTYPE_RAW: chaque enregistrement commence directement par l'en-tête IP
+const LINKTYPE_RAW: u32 = 101;
+const IPV4_UDP_HEADERS: usize = 20 + 8;
+
+pub type CaptureReply = oneshot::Sender<TResult<Vec<u8>>>;
+
+// Tap armé dans run_pipe: renvoie le fichier quand une borne est atteinte ou quand il est abandonné
+// (fin du pipe, reconnexion), avec ce qui a été capturé jusque-là
+pub struct CaptureTap {
+    out: Vec<u8>,
+    until: Instant,
+    max_packets: u64,
+    packets: u64,
+    dst_port: u16,
+    reply: Option<CaptureReply>,
+}
+
+impl CaptureTap {
+    pub fn new(duration: Duration, max_packets: Option<u64>, dst_port: u16, reply: CaptureReply) -> Self {
+        let mut out = Vec::with_capacity(64 * 1024);
+        out.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+        out.extend_from_slice(&2u16.to_le_bytes());
+        out.extend_from_slice(&4u16.to_le_bytes());
+        out.extend_from_slice(&[0; 8]);
+        out.extend_from_slice(&65_535u32.to_le_bytes());
+        out.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+        Self {
+            out,
+            until: Instant::now() + duration,
+            max_packets: max_packets.unwrap_or(MAX_CAPTURE_PACKETS),
+            packets: 0,
+            dst_port,
+            reply: Some(reply),
+        }
+    }
+
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.out.len()
+    }
+
+    pub fn expired(&self, now: Instant) -> bool {
+        now >= self.until
+    }
+
+    // Ajoute un datagramme; true quand la capture est complète (nombre de paquets ou taille atteints)
+    pub fn record(&mut self, payload: &[u8]) -> bool {
+        let len = IPV4_UDP_HEADERS + payload.len();
+        if len > u16::MAX as usize || self.out.len() + 16 + len > MAX_CAPTURE_BYTES {
+            return true;
+        }
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.out.extend_from_slice(&(ts.as_secs() as u32).to_le_bytes());
+        self.out.extend_from_slice(&ts.subsec_micros().to_le_bytes());
+        self.out.extend_from_slice(&(len as u32).to_le_bytes());
+        self.out.extend_from_slice(&(len as u32).to_le_bytes());
+        self.out.extend_from_slice(&ipv4_header(len as u16, self.packets as u16));
+        self.out.extend_from_slice(&0u16.to_be_bytes());
+        self.out.extend_from_slice(&self.dst_port.to_be_bytes());
+        self.out.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+        // Somme de contrôle UDP absente (autorisé en IPv4)
+        self.out.extend_from_slice(&0u16.to_be_bytes());
+        self.out.extend_from_slice(payload);
+        self.packets += 1;
+        self.packets >= self.max_packets
+    }
+
+    // Une seule capture à la fois par relais
+    pub fn reject(mut self, error: TransportError) {
+        if let Some(reply) = self.reply.take() {
+            let _ = reply.send(Err(error));
+        }
+    }
+}
+
+impl Drop for CaptureTap {
+    fn drop(&mut self) {
+        if let Some(reply) = self.reply.take() {
+            let _ = reply.send(Ok(std::mem::take(&mut self.out)));
+        }
+    }
+}
+
+fn ipv4_header(total_len: u16, id: u16) -> [u8; 20] {
+    let mut h = [0u8; 20];
+    h[0] = 0x45;
+    h[2..4].copy_from_slice(&total_len.to_be_bytes());
+    h[4..6].copy_from_slice(&id.to_be_bytes());
+    // DF, TTL 64, UDP
+    h[6] = 0x40;
+    h[8] = 64;
+    h[9] = 17;
+    h[16..20].copy_from_slice(&[127, 0, 0, 1]);
+    let sum = h.chunks(2).map(|w| u16::from_be_bytes([w[0], w[1]]) as u32).sum::<u32>();
+    let folded = (sum & 0xffff) + (sum >> 16);
+    h[10..12].copy_from_slice(&(!((folded & 0xffff) + (folded >> 16)) as u16).to_be_bytes());
+    h
+}
+
+// Port local de l'URI d'entrée (srt://@:9000, rist://0.0.0.0:9000?...), 0 s'il n'y en a pas
+pub fn input_port(uri: &str) -> u16 {
+    let rest = uri.split_once("://").map_or(uri, |(_, r)| r);
+    let authority = rest.split(['?', '/']).next().unwrap_or("");
+    authority.rsplit_once(':').and_then(|(_, p)| p.parse().ok()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_pcap_records_until_the_packet_bound() {
+        let (reply, mut done) = oneshot::channel();
+        let mut tap = CaptureTap::new(Duration::from_secs(5), Some(2), 9000, reply);
+        assert!(!tap.record(&[0x47; 188]));
+        assert!(tap.record(&[0x47; 188]));
+        drop(tap);
+        let pcap = done.try_recv().unwrap().unwrap();
+        assert_eq!(&pcap[..4], &[0xd4, 0xc3, 0xb2, 0xa1]);
+        assert_eq!(pcap.len(), 24 + 2 * (16 + IPV4_UDP_HEADERS + 188));
+        let ip = &pcap[24 + 16..24 + 16 + 20];
+        // Somme de contrôle IPv4 valide: le complément de la somme de l'en-tête vaut 0
+        let sum = ip.chunks(2).map(|w| u16::from_be_bytes([w[0], w[1]]) as u32).sum::<u32>();
+        assert_eq!((sum & 0xffff) + (sum >> 16), 0xffff);
+        assert_eq!(u16::from_be_bytes([pcap[24 + 16 + 22], pcap[24 + 16 + 23]]), 9000);
+    }
+
+    #[test]
+    fn parses_the_input_port() {
+        assert_eq!(input_port("srt://@:9000?mode=listener"), 9000);
+        assert_eq!(input_port("rist://0.0.0.0:10000"), 10000);
+        assert_eq!(input_port("srt://host"), 0);
+    }
+}
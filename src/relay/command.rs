@@ -0,0 +1,208 @@
+// Commandes adressées à la boucle d'un relais en cours (relais pilotés par le RelayManager).
+// run_pipe les traite entre deux lectures; pendant une reconnexion, run_relay les applique à la
+// configuration que la prochaine tentative ouvrira. Chaque commande porte sa réponse (sauf Stop).
+
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{sleep_until, Duration, Instant};
+
+use crate::common::uri::{redact_uri_secrets, split_uris};
+use crate::relay::capture::CaptureTap;
+use crate::relay::fanout::FanOutTx;
+use crate::relay::ratelimit::RateLimitConfig;
+use crate::relay::transport::{TransportMeta, TxEndpoint};
+use crate::structures::{TResult, TransportError};
+
+// Recouvrement par défaut d'un basculement de sortie (HTTP et socket de contrôle)
+pub const DEFAULT_SWITCH_OVERLAP_MS: u64 = 500;
+
+pub type CommandReply = oneshot::Sender<TResult<()>>;
+
+pub enum PipeCommand {
+    // Arrêt propre: les extrémités sont fermées et le relais se termine sans erreur
+    Stop,
+    // Nouveau plafond de débit sortant (bits/s), None = illimité; max_pps est conservé
+    SetRateLimit { max_bitrate: Option<u64>, reply: CommandReply },
+    // `tx`, déjà ouvert, rejoint le fan-out; `output` est son URI telle qu'elle figurera dans la liste
+    AddOutput { output: String, tx: Box<dyn TxEndpoint>, reply: CommandReply },
+    // Retire la cible dont l'URI figure dans la liste des sorties; la dernière sortie ne peut pas l'être
+    RemoveOutput { output: String, reply: CommandReply },
+    // Make-before-break: `tx`, déjà ouvert, devient la sortie; l'ancienne reçoit encore une copie de
+    // chaque datagramme pendant `overlap` puis est fermée. Le côté réception n'est pas interrompu.
+    SwitchOutput { output: String, tx: FanOutTx, overlap: Duration, reply: CommandReply },
+    // Arme une capture des datagrammes reçus; le tap porte ses bornes et sa réponse (le fichier pcap)
+    Capture { tap: CaptureTap },
+    // Suspend la transmission sans fermer les sockets; l'état survit aux reconnexions
+    Pause { mode: PauseMode, reply: CommandReply },
+    Resume { reply: CommandReply },
+}
+
+// Relais en pause: Drain continue de lire et jette les datagrammes (le buffer de réception ne se
+// remplit pas en amont), Hold cesse de lire (le noyau écarte ce qui dépasse le buffer)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseMode {
+    Drain,
+    Hold,
+}
+
+// Ce que la boucle de reconnexion doit faire après l'attente
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownAction {
+    Retry,
+    Stop,
+}
+
+// Canal de commandes d'un relais, conservé d'un pipe à l'autre au fil des reconnexions
+pub struct PipeControl {
+    pub commands: mpsc::Receiver<PipeCommand>,
+    // Liste de sorties courante (séparées par des virgules): une reconnexion rouvre celle-ci
+    pub output: Arc<Mutex<String>>,
+    // Plafond modifié en cours de vie, repris par les pipes suivants
+    pub rate_limit: Option<RateLimitConfig>,
+    // Pause en cours (partagée avec le RelayManager pour /relays), reprise par les pipes suivants
+    pub paused: Arc<Mutex<Option<PauseMode>>>,
+}
+
+impl PipeControl {
+    pub fn new(commands: mpsc::Receiver<PipeCommand>, output: Arc<Mutex<String>>) -> Self {
+        Self { commands, output, rate_limit: None, paused: Arc::new(Mutex::new(None)) }
+    }
+
+    pub fn pause_mode(&self) -> Option<PauseMode> {
+        *self.paused.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn set_pause_mode(&self, mode: Option<PauseMode>) {
+        *self.paused.lock().unwrap_or_else(|e| e.into_inner()) = mode;
+    }
+
+    pub fn current_output(&self) -> String {
+        self.output.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub(crate) fn set_output(&self, output: String) {
+        *self.output.lock().unwrap_or_else(|e| e.into_inner()) = output;
+    }
+
+    pub(crate) fn push_output(&self, output: &str) {
+        let mut current = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        current.push(',');
+        current.push_str(output);
+    }
+
+    // Position de `output` dans la liste des sorties, après vérification qu'une autre sortie reste
+    pub(crate) fn remove_output(&self, output: &str) -> TResult<usize> {
+        let mut current = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        let mut uris = split_uris(&current);
+        let index = uris.iter().position(|u| *u == output.trim())
+            .ok_or_else(|| TransportError::InvalidUri(format!("not an output of this relay: {}", redact_uri_secrets(output))))?;
+        if uris.len() == 1 {
+            return Err(TransportError::Other("cannot remove the last output of a relay".into()));
+        }
+        uris.remove(index);
+        *current = uris.join(",");
+        Ok(index)
+    }
+
+    // Attente entre deux tentatives de reconnexion, sans pipe en cours: les commandes reçues modifient
+    // ce que la prochaine tentative ouvrira (les émetteurs déjà ouverts sont refermés), Stop l'interrompt
+    pub async fn wait_down(&mut self, backoff: Duration, rate_limit: RateLimitConfig) -> DownAction {
+        let until = Instant::now() + backoff;
+        loop {
+            let cmd = tokio::select! {
+                _ = sleep_until(until) => return DownAction::Retry,
+                cmd = self.commands.recv() => cmd,
+            };
+            match cmd {
+                None => {
+                    sleep_until(until).await;
+                    return DownAction::Retry;
+                }
+                Some(PipeCommand::Stop) => return DownAction::Stop,
+                Some(PipeCommand::SetRateLimit { max_bitrate, reply }) => {
+                    let base = self.rate_limit.unwrap_or(rate_limit);
+                    self.rate_limit = Some(RateLimitConfig { max_bitrate, ..base });
+                    let _ = reply.send(Ok(()));
+                }
+                Some(PipeCommand::AddOutput { output, mut tx, reply }) => {
+                    tx.close();
+                    self.push_output(&output);
+                    let _ = reply.send(Ok(()));
+                }
+                Some(PipeCommand::RemoveOutput { output, reply }) => {
+                    let _ = reply.send(self.remove_output(&output).map(drop));
+                }
+                Some(PipeCommand::SwitchOutput { output, mut tx, reply, .. }) => {
+                    tx.close();
+                    self.set_output(output);
+                    let _ = reply.send(Ok(()));
+                }
+                Some(PipeCommand::Capture { tap }) => {
+                    tap.reject(TransportError::Other("relay is reconnecting, nothing to capture".into()));
+                }
+                Some(PipeCommand::Pause { mode, reply }) => {
+                    self.set_pause_mode(Some(mode));
+                    let _ = reply.send(Ok(()));
+                }
+                Some(PipeCommand::Resume { reply }) => {
+                    self.set_pause_mode(None);
+                    let _ = reply.send(Ok(()));
+                }
+            }
+        }
+    }
+}
+
+// Prochaine commande, ou jamais pour un pipe sans canal de commandes (CLI, selftest)
+pub async fn next_command(control: &mut Option<&mut PipeControl>) -> Option<PipeCommand> {
+    match control {
+        Some(c) => c.commands.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control(output: &str) -> PipeControl {
+        let (_tx, rx) = mpsc::channel(1);
+        PipeControl::new(rx, Arc::new(Mutex::new(output.into())))
+    }
+
+    #[test]
+    fn edits_the_output_list() {
+        let c = control("srt://a:1");
+        assert!(c.remove_output("srt://a:1").is_err());
+        c.push_output("srt://b:2");
+        assert_eq!(c.current_output(), "srt://a:1,srt://b:2");
+        assert!(c.remove_output("srt://c:3").is_err());
+        assert_eq!(c.remove_output("srt://b:2").unwrap(), 1);
+        assert_eq!(c.current_output(), "srt://a:1");
+    }
+
+    #[tokio::test]
+    async fn stop_interrupts_the_reconnect_wait() {
+        let (tx, rx) = mpsc::channel(4);
+        let mut c = PipeControl::new(rx, Arc::new(Mutex::new("srt://a:1".into())));
+        let (reply, done) = oneshot::channel();
+        tx.send(PipeCommand::SetRateLimit { max_bitrate: Some(1_000_000), reply }).await.unwrap();
+        tx.send(PipeCommand::Stop).await.unwrap();
+        let base = RateLimitConfig { max_bitrate: None, max_pps: Some(100) };
+        assert_eq!(c.wait_down(Duration::from_secs(60), base).await, DownAction::Stop);
+        assert!(done.await.unwrap().is_ok());
+        assert_eq!(c.rate_limit, Some(RateLimitConfig { max_bitrate: Some(1_000_000), max_pps: Some(100) }));
+    }
+
+    #[tokio::test]
+    async fn pause_survives_the_reconnect_wait() {
+        let (tx, rx) = mpsc::channel(4);
+        let mut c = PipeControl::new(rx, Arc::new(Mutex::new("srt://a:1".into())));
+        let (reply, done) = oneshot::channel();
+        tx.send(PipeCommand::Pause { mode: PauseMode::Hold, reply }).await.unwrap();
+        assert_eq!(c.wait_down(Duration::from_millis(20), RateLimitConfig::default()).await, DownAction::Retry);
+        assert!(done.await.unwrap().is_ok());
+        assert_eq!(c.pause_mode(), Some(PauseMode::Hold));
+    }
+}
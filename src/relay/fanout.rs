@@ -0,0 +1,140 @@
+// Fan-out sender: one datagram in, one copy per output. Built from a comma-separated output list
+// (--output "srt://a:1,srt://b:2"); each target keeps its own URI parameters and impairments.
+// A failing target does not stop the others: send() only errors when every target failed.
+// Relays always send through a FanOutTx, even with a single target, so that outputs can be added
+// and removed while the relay runs (relay::command).
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tracing::warn;
+
+use crate::common::logging::{events, LogThrottle};
+use crate::relay::transport::{BufferOccupancy, EffectiveOptions, TransportMeta, TransportTx, TxEndpoint};
+use crate::structures::{TResult, TransportError};
+
+pub struct FanOutTx {
+    targets: Vec<Box<dyn TxEndpoint>>,
+    failure_log: LogThrottle,
+}
+
+impl FanOutTx {
+    pub fn new(targets: Vec<Box<dyn TxEndpoint>>) -> Self {
+        Self { targets, failure_log: LogThrottle::new(Duration::from_secs(10)) }
+    }
+
+    // La cible doit être déjà ouverte
+    pub fn push(&mut self, target: Box<dyn TxEndpoint>) {
+        self.targets.push(target);
+    }
+
+    // Index dans l'ordre de la liste de sorties; la cible retirée n'est pas fermée
+    pub fn remove(&mut self, index: usize) -> Box<dyn TxEndpoint> {
+        self.targets.remove(index)
+    }
+}
+
+#[async_trait]
+impl TransportTx for FanOutTx {
+    async fn send(&mut self, buf: &[u8]) -> TResult<usize> {
+        let mut sent = None;
+        let mut last_err = None;
+        let targets_len = self.targets.len();
+        for (index, target) in self.targets.iter_mut().enumerate() {
+            match target.send(buf).await {
+                Ok(n) => sent = Some(sent.unwrap_or(0).max(n)),
+                Err(e) => {
+                    // Avec une seule cible l'erreur remonte au pipe, qui la journalise lui-même
+                    if targets_len > 1
+                        && let Some(suppressed) = self.failure_log.allow()
+                    {
+                        warn!(event = events::RELAY_ERROR, subsystem = "relay", output_index = index, output = %target.describe(), error = %e, suppressed = suppressed, msg = "Fan-out target send failed");
+                    }
+                    last_err = Some(e);
+                }
+            }
+        }
+        match (sent, last_err) {
+            (Some(n), _) => Ok(n),
+            (None, Some(e)) => Err(e),
+            (None, None) => Err(TransportError::Closed),
+        }
+    }
+}
+
+impl TransportMeta for FanOutTx {
+    // Tout ou rien: si une cible ne s'ouvre pas, celles déjà ouvertes sont refermées
+    fn open(&mut self) -> TResult<()> {
+        for i in 0..self.targets.len() {
+            if let Err(e) = self.targets[i].open() {
+                self.targets[..i].iter_mut().for_each(|t| t.close());
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+    fn close(&mut self) {
+        self.targets.iter_mut().for_each(|t| t.close());
+    }
+    fn describe(&self) -> String {
+        self.targets.iter().map(|t| t.describe()).collect::<Vec<_>>().join(" | ")
+    }
+    // Options de la première cible, représentative tant que les cibles partagent le même schéma
+    fn effective_options(&self) -> EffectiveOptions {
+        self.targets.first().map(|t| t.effective_options()).unwrap_or_default()
+    }
+    fn buffer_occupancy(&self) -> Option<BufferOccupancy> {
+        self.targets.iter().filter_map(|t| t.buffer_occupancy()).reduce(|a, b| BufferOccupancy {
+            recv_bytes: a.recv_bytes + b.recv_bytes,
+            send_bytes: a.send_bytes + b.send_bytes,
+        })
+    }
+    // Pire RTT parmi les cibles ayant une mesure nouvelle
+    fn take_rtt_sample(&mut self) -> Option<Duration> {
+        self.targets.iter_mut().filter_map(|t| t.take_rtt_sample()).max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Sink {
+        fail: bool,
+        sent: usize,
+    }
+
+    impl TransportMeta for Sink {
+        fn open(&mut self) -> TResult<()> {
+            Ok(())
+        }
+        fn close(&mut self) {}
+        fn describe(&self) -> String {
+            "sink".into()
+        }
+    }
+
+    #[async_trait]
+    impl TransportTx for Sink {
+        async fn send(&mut self, buf: &[u8]) -> TResult<usize> {
+            if self.fail {
+                return Err(TransportError::Closed);
+            }
+            self.sent += 1;
+            Ok(buf.len())
+        }
+    }
+
+    #[tokio::test]
+    async fn one_failing_target_does_not_stop_the_others() {
+        let mut tx = FanOutTx::new(vec![Box::new(Sink { fail: true, sent: 0 }), Box::new(Sink { fail: false, sent: 0 })]);
+        assert_eq!(tx.send(b"abc").await.unwrap(), 3);
+        let mut all_down = FanOutTx::new(vec![Box::new(Sink { fail: true, sent: 0 })]);
+        assert!(all_down.send(b"abc").await.is_err());
+        assert_eq!(tx.describe(), "sink | sink");
+        tx.push(Box::new(Sink { fail: false, sent: 0 }));
+        tx.remove(0);
+        assert_eq!(tx.describe(), "sink | sink");
+        assert_eq!(tx.send(b"abc").await.unwrap(), 3);
+    }
+}
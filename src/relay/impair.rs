@@ -0,0 +1,331 @@
+// Network impairment for lab / staging tests, applied on the send path and disabled by default.
+// ?drop_pct=2 drops 2 % of outgoing datagrams using a seeded PRNG (?drop_seed=N to replay a run).
+// Injected drops are counted in injected_drops_total, never as real loss.
+// ?delay_ms=50&jitter_ms=10 holds each datagram 50 ms ± 10 ms before sending it (?jitter_seed=N),
+// without reordering; the delay applied is summed in injected_delay_seconds_total.
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::info;
+
+use crate::common::logging::events;
+use crate::common::uri::query_param;
+use crate::relay::transport::{BufferOccupancy, EffectiveOptions, TransportMeta, TransportTx, TxEndpoint};
+use crate::structures::{Metrics, TResult, TransportError};
+
+// SplitMix64: tiny, seedable and good enough to pick which datagrams to drop
+#[derive(Debug, Clone)]
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+pub struct DropSim {
+    inner: Box<dyn TxEndpoint>,
+    ratio: f64,
+    seed: u64,
+    rng: SplitMix64,
+}
+
+impl DropSim {
+    pub fn new(inner: Box<dyn TxEndpoint>, drop_pct: f64, seed: u64) -> Self {
+        Self { inner, ratio: drop_pct / 100.0, seed, rng: SplitMix64::new(seed) }
+    }
+
+    fn should_drop(&mut self) -> bool {
+        self.rng.next_f64() < self.ratio
+    }
+}
+
+#[async_trait]
+impl TransportTx for DropSim {
+    // A dropped datagram reports 0 bytes sent so that bytes_out only counts what left the host
+    async fn send(&mut self, buf: &[u8]) -> TResult<usize> {
+        if self.should_drop() {
+            if let Some(m) = Metrics::global() {
+                m.injected_drops_total.inc();
+            }
+            return Ok(0);
+        }
+        self.inner.send(buf).await
+    }
+}
+
+impl TransportMeta for DropSim {
+    fn open(&mut self) -> TResult<()> {
+        self.inner.open()
+    }
+    fn close(&mut self) {
+        self.inner.close()
+    }
+    fn describe(&self) -> String {
+        format!("{} drop_pct={} drop_seed={}", self.inner.describe(), self.ratio * 100.0, self.seed)
+    }
+    fn effective_options(&self) -> EffectiveOptions {
+        self.inner.effective_options()
+    }
+    fn buffer_occupancy(&self) -> Option<BufferOccupancy> {
+        self.inner.buffer_occupancy()
+    }
+    fn take_rtt_sample(&mut self) -> Option<Duration> {
+        self.inner.take_rtt_sample()
+    }
+}
+
+// Datagrams waiting in the delay queue before send() waits for room
+const DELAY_QUEUE_CAPACITY: usize = 4096;
+
+// Each datagram is due at now + delay ± jitter, but never before the previous one: order is kept
+struct DelaySchedule {
+    delay: Duration,
+    jitter: Duration,
+    rng: SplitMix64,
+    last_due: Option<Instant>,
+}
+
+impl DelaySchedule {
+    fn new(delay: Duration, jitter: Duration, seed: u64) -> Self {
+        Self { delay, jitter, rng: SplitMix64::new(seed), last_due: None }
+    }
+
+    fn next_due(&mut self, now: Instant) -> Instant {
+        let offset = (self.rng.next_f64() * 2.0 - 1.0) * self.jitter.as_secs_f64();
+        let wait = Duration::from_secs_f64((self.delay.as_secs_f64() + offset).max(0.0));
+        let due = self.last_due.map_or(now + wait, |last| (now + wait).max(last));
+        self.last_due = Some(due);
+        due
+    }
+}
+
+// The inner sender is moved into a worker task at open(): send() only enqueues, the worker sends
+// each datagram when it is due. A send error is reported by the next send() to trigger a reconnect.
+pub struct DelaySim {
+    inner: Option<Box<dyn TxEndpoint>>,
+    schedule: DelaySchedule,
+    seed: u64,
+    description: String,
+    options: EffectiveOptions,
+    queue: Option<mpsc::Sender<(Instant, Vec<u8>)>>,
+    queued_bytes: Arc<AtomicU64>,
+    failure: Arc<Mutex<Option<TransportError>>>,
+}
+
+impl DelaySim {
+    pub fn new(inner: Box<dyn TxEndpoint>, delay: Duration, jitter: Duration, seed: u64) -> Self {
+        Self {
+            description: inner.describe(),
+            options: inner.effective_options(),
+            inner: Some(inner),
+            schedule: DelaySchedule::new(delay, jitter, seed),
+            seed,
+            queue: None,
+            queued_bytes: Arc::new(AtomicU64::new(0)),
+            failure: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+async fn delay_worker(mut inner: Box<dyn TxEndpoint>, mut queue: mpsc::Receiver<(Instant, Vec<u8>)>, queued_bytes: Arc<AtomicU64>, failure: Arc<Mutex<Option<TransportError>>>) {
+    while let Some((due, buf)) = queue.recv().await {
+        tokio::time::sleep_until(due).await;
+        queued_bytes.fetch_sub(buf.len() as u64, Ordering::Relaxed);
+        if let Err(e) = inner.send(&buf).await {
+            *failure.lock().unwrap_or_else(|e| e.into_inner()) = Some(e);
+            break;
+        }
+    }
+    // Après un échec, les datagrammes encore en file sont perdus: ils sortent du compte
+    queue.close();
+    while let Ok((_, buf)) = queue.try_recv() {
+        queued_bytes.fetch_sub(buf.len() as u64, Ordering::Relaxed);
+    }
+    inner.close();
+}
+
+#[async_trait]
+impl TransportTx for DelaySim {
+    async fn send(&mut self, buf: &[u8]) -> TResult<usize> {
+        if let Some(e) = self.failure.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            return Err(e);
+        }
+        let queue = self.queue.as_ref().ok_or(TransportError::Closed)?;
+        let now = Instant::now();
+        let due = self.schedule.next_due(now);
+        self.queued_bytes.fetch_add(buf.len() as u64, Ordering::Relaxed);
+        if queue.send((due, buf.to_vec())).await.is_err() {
+            self.queued_bytes.fetch_sub(buf.len() as u64, Ordering::Relaxed);
+            return Err(TransportError::Closed);
+        }
+        if let Some(m) = Metrics::global() {
+            m.injected_delay_seconds_total.inc_by(due.saturating_duration_since(now).as_secs_f64());
+        }
+        Ok(buf.len())
+    }
+}
+
+impl TransportMeta for DelaySim {
+    fn open(&mut self) -> TResult<()> {
+        let mut inner = self.inner.take().ok_or(TransportError::Closed)?;
+        inner.open()?;
+        self.options = inner.effective_options();
+        let (tx, rx) = mpsc::channel(DELAY_QUEUE_CAPACITY);
+        tokio::spawn(delay_worker(inner, rx, self.queued_bytes.clone(), self.failure.clone()));
+        self.queue = Some(tx);
+        Ok(())
+    }
+    // Le worker envoie encore les datagrammes en file puis ferme l'émetteur sous-jacent
+    fn close(&mut self) {
+        self.queue = None;
+        if let Some(inner) = self.inner.as_mut() {
+            inner.close();
+        }
+    }
+    fn describe(&self) -> String {
+        format!("{} delay_ms={} jitter_ms={} jitter_seed={}", self.description, self.schedule.delay.as_millis(), self.schedule.jitter.as_millis(), self.seed)
+    }
+    fn effective_options(&self) -> EffectiveOptions {
+        self.options.clone()
+    }
+    // Octets retenus dans la file de délai, vus comme du buffer d'émission
+    fn buffer_occupancy(&self) -> Option<BufferOccupancy> {
+        Some(BufferOccupancy { recv_bytes: 0, send_bytes: self.queued_bytes.load(Ordering::Relaxed) })
+    }
+}
+
+fn millis_from_uri(uri: &str, key: &str) -> TResult<Option<Duration>> {
+    let Some(raw) = query_param(uri, key) else { return Ok(None) };
+    raw.parse::<u64>()
+        .map(|ms| Some(Duration::from_millis(ms)))
+        .map_err(|_| TransportError::InvalidUri(format!("{} must be a number of milliseconds, got {}", key, raw)))
+}
+
+fn drop_pct_from_uri(uri: &str) -> TResult<Option<f64>> {
+    let Some(raw) = query_param(uri, "drop_pct") else { return Ok(None) };
+    match raw.parse::<f64>() {
+        Ok(pct) if (0.0..=100.0).contains(&pct) => Ok((pct > 0.0).then_some(pct)),
+        _ => Err(TransportError::InvalidUri(format!("drop_pct must be a percentage between 0 and 100, got {}", raw))),
+    }
+}
+
+fn seed_from_uri(uri: &str, key: &str) -> TResult<u64> {
+    match query_param(uri, key) {
+        Some(raw) => raw.parse().map_err(|_| TransportError::InvalidUri(format!("{} must be an unsigned integer, got {}", key, raw))),
+        // Sans graine imposée, l'horloge en fournit une; elle est journalisée pour rejouer le tirage
+        None => Ok(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0)),
+    }
+}
+
+// Wraps the sender with the impairments requested on its URI; returns it unchanged otherwise
+// (the drop decision comes first: a dropped datagram never enters the delay queue)
+pub fn wrap_tx(tx: Box<dyn TxEndpoint>, uri: &str) -> TResult<Box<dyn TxEndpoint>> {
+    let delay = millis_from_uri(uri, "delay_ms")?.unwrap_or_default();
+    let jitter = millis_from_uri(uri, "jitter_ms")?.unwrap_or_default();
+    let tx: Box<dyn TxEndpoint> = if delay.is_zero() && jitter.is_zero() {
+        tx
+    } else {
+        let seed = seed_from_uri(uri, "jitter_seed")?;
+        info!(event = events::SOCKET_OPTION, subsystem = "net", option = "delay_ms", value = delay.as_millis() as u64, jitter_ms = jitter.as_millis() as u64, seed = seed, msg = "Latency injection enabled on output");
+        Box::new(DelaySim::new(tx, delay, jitter, seed))
+    };
+    let Some(pct) = drop_pct_from_uri(uri)? else { return Ok(tx) };
+    let seed = seed_from_uri(uri, "drop_seed")?;
+    info!(event = events::SOCKET_OPTION, subsystem = "net", option = "drop_pct", value = pct, seed = seed, msg = "Packet drop simulation enabled on output");
+    Ok(Box::new(DropSim::new(tx, pct, seed)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FailingTx;
+
+    #[async_trait]
+    impl TransportTx for FailingTx {
+        async fn send(&mut self, _buf: &[u8]) -> TResult<usize> {
+            Err(TransportError::Closed)
+        }
+    }
+
+    impl TransportMeta for FailingTx {
+        fn open(&mut self) -> TResult<()> {
+            Ok(())
+        }
+        fn close(&mut self) {}
+        fn describe(&self) -> String {
+            "output=failing".into()
+        }
+    }
+
+    #[tokio::test]
+    async fn queued_bytes_are_released_when_the_worker_fails() {
+        let mut sim = DelaySim::new(Box::new(FailingTx), Duration::from_millis(20), Duration::ZERO, 1);
+        sim.open().unwrap();
+        for _ in 0..3 {
+            sim.send(&[0u8; 100]).await.unwrap();
+        }
+        assert_eq!(sim.buffer_occupancy().unwrap().send_bytes, 300);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(sim.buffer_occupancy().unwrap().send_bytes, 0);
+        assert!(sim.send(&[0u8; 100]).await.is_err());
+        assert!(sim.send(&[0u8; 100]).await.is_err());
+        assert_eq!(sim.buffer_occupancy().unwrap().send_bytes, 0);
+    }
+
+    #[test]
+    fn prng_is_reproducible_and_uniform() {
+        let mut a = SplitMix64::new(42);
+        let mut b = SplitMix64::new(42);
+        assert!((0..100).all(|_| a.next_u64() == b.next_u64()));
+        let mut r = SplitMix64::new(7);
+        let hits = (0..100_000).filter(|_| r.next_f64() < 0.02).count();
+        assert!((1_700..2_300).contains(&hits), "{}", hits);
+    }
+
+    #[test]
+    fn parses_drop_params() {
+        assert_eq!(drop_pct_from_uri("srt://h:1").unwrap(), None);
+        assert_eq!(drop_pct_from_uri("srt://h:1?drop_pct=0").unwrap(), None);
+        assert_eq!(drop_pct_from_uri("srt://h:1?drop_pct=2.5").unwrap(), Some(2.5));
+        assert!(drop_pct_from_uri("srt://h:1?drop_pct=150").is_err());
+        assert_eq!(seed_from_uri("srt://h:1?drop_seed=99", "drop_seed").unwrap(), 99);
+        assert!(seed_from_uri("srt://h:1?drop_seed=x", "drop_seed").is_err());
+        assert_eq!(millis_from_uri("srt://h:1?delay_ms=50", "delay_ms").unwrap(), Some(Duration::from_millis(50)));
+        assert!(millis_from_uri("srt://h:1?jitter_ms=-1", "jitter_ms").is_err());
+    }
+
+    #[test]
+    fn delay_schedule_stays_in_order_and_bounds() {
+        let mut s = DelaySchedule::new(Duration::from_millis(50), Duration::from_millis(10), 3);
+        let start = Instant::now();
+        let mut last = start;
+        for i in 0..1000u64 {
+            let now = start + Duration::from_micros(i * 100);
+            let due = s.next_due(now);
+            assert!(due >= last);
+            assert!(due >= now + Duration::from_millis(40));
+            assert!(due <= now + Duration::from_millis(60) || due == last);
+            last = due;
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+// Inter-arrival jitter with the RFC 3550 smoothing (J += (|D| - J) / 16).
+// Without a sender timestamp, D is the difference between two consecutive arrival
+// intervals, so a source paced at a steady rate reports ~0 whatever its bitrate.
+
+#[derive(Debug, Default)]
+pub struct JitterEstimator {
+    last_arrival: Option<Instant>,
+    last_interval: Option<Duration>,
+    jitter_secs: f64,
+}
+
+impl JitterEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Records one arrival and returns the smoothed jitter in milliseconds
+    pub fn observe(&mut self, arrival: Instant) -> f64 {
+        if let Some(prev) = self.last_arrival {
+            let interval = arrival.saturating_duration_since(prev);
+            if let Some(prev_interval) = self.last_interval {
+                let d = (interval.as_secs_f64() - prev_interval.as_secs_f64()).abs();
+                self.jitter_secs += (d - self.jitter_secs) / 16.0;
+            }
+            self.last_interval = Some(interval);
+        }
+        self.last_arrival = Some(arrival);
+        self.jitter_ms()
+    }
+
+    pub fn jitter_ms(&self) -> f64 {
+        self.jitter_secs * 1000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regular_arrivals_have_no_jitter() {
+        let mut j = JitterEstimator::new();
+        let t0 = Instant::now();
+        for i in 0..50 {
+            j.observe(t0 + Duration::from_millis(10 * i));
+        }
+        assert!(j.jitter_ms() < 0.001);
+    }
+
+    #[test]
+    fn irregular_arrivals_raise_jitter() {
+        let mut j = JitterEstimator::new();
+        let t0 = Instant::now();
+        let mut t = t0;
+        for i in 0..200 {
+            t += Duration::from_millis(if i % 2 == 0 { 5 } else { 15 });
+            j.observe(t);
+        }
+        // |D| = 10 ms on every packet: the estimate converges to 10 ms
+        assert!((j.jitter_ms() - 10.0).abs() < 0.5);
+    }
+}
@@ -0,0 +1,88 @@
+// Keep-alive on the output (?keepalive_ms=2000): when nothing has been forwarded for that long, the
+// pipe sends a small filler datagram so that a downstream listener with an idle timeout keeps the
+// path open while the input is briefly silent. The default filler is one MPEG-TS null packet
+// (PID 0x1FFF), which demuxers discard; ?keepalive_payload=HEX sends other bytes instead.
+// Keep-alives are counted in keepalives_sent_total, never in the forwarded bytes/packets.
+
+use std::time::{Duration, Instant};
+
+use crate::common::uri::query_param;
+use crate::structures::{TResult, TransportError};
+
+const MIN_KEEPALIVE_MS: u64 = 10;
+const MAX_KEEPALIVE_PAYLOAD: usize = 1316;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeepAlive {
+    pub interval: Duration,
+    pub payload: Vec<u8>,
+}
+
+impl KeepAlive {
+    // Keep-alive à envoyer si rien n'est parti depuis `interval`
+    pub fn due(&self, last_sent: Instant, now: Instant) -> bool {
+        now.duration_since(last_sent) >= self.interval
+    }
+}
+
+// 0x47, PID 0x1FFF, payload only, continuity counter 0, padding 0xFF
+pub fn ts_null_packet() -> Vec<u8> {
+    let mut packet = vec![0xFF; 188];
+    packet[..4].copy_from_slice(&[0x47, 0x1F, 0xFF, 0x10]);
+    packet
+}
+
+fn parse_hex(raw: &str) -> Option<Vec<u8>> {
+    let raw = raw.trim_start_matches("0x");
+    if raw.is_empty() || !raw.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..raw.len()).step_by(2).map(|i| u8::from_str_radix(raw.get(i..i + 2)?, 16).ok()).collect()
+}
+
+// ?keepalive_ms=MS [&keepalive_payload=HEX] on an output URI; None when keep-alives are off (absent or 0)
+pub fn keepalive_from_uri(uri: &str) -> TResult<Option<KeepAlive>> {
+    let interval = match query_param(uri, "keepalive_ms") {
+        None => return Ok(None),
+        Some(raw) => match raw.parse::<u64>() {
+            Ok(0) => return Ok(None),
+            Ok(ms) if ms >= MIN_KEEPALIVE_MS => Duration::from_millis(ms),
+            _ => return Err(TransportError::InvalidUri(format!("keepalive_ms must be 0 (off) or at least {} milliseconds, got {}", MIN_KEEPALIVE_MS, raw))),
+        },
+    };
+    let payload = match query_param(uri, "keepalive_payload") {
+        None => ts_null_packet(),
+        Some(raw) => parse_hex(&raw)
+            .filter(|p| p.len() <= MAX_KEEPALIVE_PAYLOAD)
+            .ok_or_else(|| TransportError::InvalidUri(format!("keepalive_payload must be 1 to {} bytes of hex, got {}", MAX_KEEPALIVE_PAYLOAD, raw)))?,
+    };
+    Ok(Some(KeepAlive { interval, payload }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keepalive_params() {
+        assert_eq!(keepalive_from_uri("srt://h:1").unwrap(), None);
+        assert_eq!(keepalive_from_uri("srt://h:1?keepalive_ms=0").unwrap(), None);
+        let ka = keepalive_from_uri("srt://h:1?keepalive_ms=2000").unwrap().unwrap();
+        assert_eq!(ka.interval, Duration::from_secs(2));
+        assert_eq!(ka.payload.len(), 188);
+        assert_eq!(&ka.payload[..4], &[0x47, 0x1F, 0xFF, 0x10]);
+        let ka = keepalive_from_uri("srt://h:1?keepalive_ms=500&keepalive_payload=00ff").unwrap().unwrap();
+        assert_eq!(ka.payload, vec![0x00, 0xFF]);
+        assert!(keepalive_from_uri("srt://h:1?keepalive_ms=5").is_err());
+        assert!(keepalive_from_uri("srt://h:1?keepalive_ms=500&keepalive_payload=abc").is_err());
+        assert!(keepalive_from_uri("srt://h:1?keepalive_ms=500&keepalive_payload=zz").is_err());
+    }
+
+    #[test]
+    fn due_after_the_interval() {
+        let ka = KeepAlive { interval: Duration::from_millis(100), payload: ts_null_packet() };
+        let t0 = Instant::now();
+        assert!(!ka.due(t0, t0 + Duration::from_millis(99)));
+        assert!(ka.due(t0, t0 + Duration::from_millis(100)));
+    }
+}
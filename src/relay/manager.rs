@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::{mpsc, oneshot};
+use tokio::sync::mpsc::error::SendTimeoutError;
+use tokio::task::{AbortHandle, JoinHandle};
+use tracing::{error, info};
+
+use crate::common::config::RelayConfig;
+use crate::common::logging::{events, short_uuid};
+use crate::common::uri::{pin_assigned_port, redact_uri_list, redact_uri_secrets, split_uris};
+use crate::relay::capture::{self, CaptureTap};
+use crate::relay::command::{CommandReply, PauseMode, PipeCommand, PipeControl};
+use crate::relay::pipe::PipeOptions;
+use crate::relay::transport::TransportMeta;
+use crate::relay::registry::{TransportParams, TransportRegistry};
+use crate::structures::{BitrateThresholds, Metrics, RelayCounts, RelayFailure, TResult, TransportError};
+
+// Dernières erreurs fatales conservées pour /health
+const MAX_FAILURES: usize = 16;
+// Commandes en attente par relais avant que l'envoi ne bloque
+const COMMAND_QUEUE_LEN: usize = 8;
+// Délai laissé au relais pour accepter puis prendre en compte une commande (traitée entre deux lectures)
+const COMMAND_REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+// Chauffe par défaut, y compris pour les relais hors RelayManager (ligne de commande)
+pub const DEFAULT_WARMUP: Duration = Duration::from_secs(5);
+
+// Dépôt borné d'une commande: un pipe bloqué (reconnexion, attente du limiteur) laisse sa file pleine,
+// l'appelant (socket de contrôle, sans délai HTTP) reçoit alors un Timeout au lieu d'attendre sans fin
+async fn enqueue(commands: &mpsc::Sender<PipeCommand>, command: PipeCommand, limit: Duration) -> TResult<()> {
+    commands.send_timeout(command, limit).await.map_err(|e| match e {
+        SendTimeoutError::Timeout(_) => TransportError::Timeout { during: Some(format!("while queuing a relay command after {} ms", limit.as_millis())) },
+        SendTimeoutError::Closed(_) => TransportError::Closed,
+    })
+}
+
+// Relais lancés en tâche de fond (fichier de configuration, socket de contrôle), pilotables par relay_id
+struct ManagedRelay {
+    input: String,
+    // URI de sortie courante, non masquée (partagée avec la tâche, mise à jour au basculement)
+    output: Arc<Mutex<String>>,
+    params: TransportParams,
+    commands: mpsc::Sender<PipeCommand>,
+    labels: HashMap<String, String>,
+    bitrate_thresholds: BitrateThresholds,
+    warmup: Duration,
+    handle: JoinHandle<()>,
+    // Pause en cours, tenue par le PipeControl du relais
+    paused: Arc<Mutex<Option<PauseMode>>>,
+    // Tâche terminée en erreur (distinct d'un arrêt propre sur max_runtime / idle_timeout)
+    failed: Arc<AtomicBool>,
+}
+
+// Le protocole de sortie fait partie de l'identité du relais (étiquettes de métriques)
+fn same_output_protocol(registry: &TransportRegistry, current: &str, output: &str) -> TResult<()> {
+    let protocol = registry.resolve(split_uris(current)[0])?;
+    for uri in split_uris(output) {
+        if registry.resolve(uri)? != protocol {
+            return Err(TransportError::InvalidUri(format!("outputs must keep the {} protocol: {}", protocol, redact_uri_secrets(uri))));
+        }
+    }
+    Ok(())
+}
+
+impl ManagedRelay {
+    fn status(&self, relay_id: &str) -> &'static str {
+        if self.handle.is_finished() {
+            return if self.failed.load(Ordering::Relaxed) { "failed" } else { "stopped" };
+        }
+        // Boucle du pipe figée: prime sur l'état de pause, qu'elle ne peut plus appliquer
+        if Metrics::global().is_some_and(|m| m.relay_stalled(relay_id)) {
+            return "stalled";
+        }
+        match *self.paused.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some(_) => "paused",
+            None if Metrics::global().is_some_and(|m| m.relay_overrun(relay_id)) => "overrun",
+            None => "running",
+        }
+    }
+
+    fn info(&self, relay_id: &str) -> ManagedRelayInfo {
+        let (restart_count, total_reconnects) = Metrics::global().and_then(|m| m.relay_restarts(relay_id)).unwrap_or_default();
+        ManagedRelayInfo {
+            relay_id: relay_id.to_string(),
+            input: self.input.clone(),
+            output: redact_uri_list(&self.output.lock().unwrap_or_else(|e| e.into_inner())),
+            labels: self.labels.clone(),
+            running: !self.handle.is_finished(),
+            status: self.status(relay_id),
+            restart_count,
+            total_reconnects,
+        }
+    }
+}
+
+// Probes automatiques (SRTRIST_AUTO_SRT / SRTRIST_AUTO_RIST): hors RelayManager, suivies pour /health
+struct ProbeTask {
+    handle: AbortHandle,
+    failed: Arc<AtomicBool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManagedRelayInfo {
+    pub relay_id: String,
+    pub input: String,
+    pub output: String,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
+    pub running: bool,
+    // running | paused | overrun (débit entrant au-dessus de overrun_bitrate) | stalled (boucle du pipe
+    // figée au-delà de --stall-threshold-secs) | stopped (arrêt propre) | failed
+    pub status: &'static str,
+    // Pipes rouverts après une erreur et tentatives de reconnexion depuis le lancement
+    pub restart_count: u64,
+    pub total_reconnects: u64,
+}
+
+#[derive(Default)]
+pub struct RelayManager {
+    relays: Mutex<HashMap<String, ManagedRelay>>,
+    failures: Mutex<Vec<RelayFailure>>,
+    probes: Mutex<Vec<ProbeTask>>,
+    // Relais de configuration refusés au démarrage: déclarés, jamais lancés
+    start_failures: AtomicUsize,
+}
+
+static GLOBAL_MANAGER: Lazy<RelayManager> = Lazy::new(RelayManager::default);
+
+impl RelayManager {
+    pub fn global() -> &'static RelayManager {
+        &GLOBAL_MANAGER
+    }
+
+    // Valide les URIs (schémas, options) et ouvre les extrémités avant de lancer la tâche, pour
+    // qu'une erreur évidente (bind refusé, port occupé) soit renvoyée à l'appelant et pas seulement journalisée
+    pub async fn start(&self, mut cfg: RelayConfig) -> TResult<String> {
+        let registry = TransportRegistry::global();
+        registry.resolve(&cfg.input)?;
+        split_uris(&cfg.output).into_iter().try_for_each(|uri| registry.resolve(uri).map(drop))?;
+        PipeOptions::from_uris(&cfg.input, &cfg.output)?;
+        let bitrate_thresholds = BitrateThresholds { min_bps: cfg.alert_min_bitrate, max_bps: cfg.alert_max_bitrate };
+        if let (Some(min), Some(max)) = (bitrate_thresholds.min_bps, bitrate_thresholds.max_bps)
+            && min > max
+        {
+            return Err(TransportError::Other(format!("alert_min_bitrate ({}) is above alert_max_bitrate ({})", min, max)));
+        }
+        let params = TransportParams { latency_ms: cfg.latency_ms, reachability_check: cfg.reachability_check };
+        if cfg.overrun_bitrate == Some(0) {
+            return Err(TransportError::Other("overrun_bitrate must be a positive bitrate".into()));
+        }
+        if cfg.max_recv_timeouts == Some(0) {
+            return Err(TransportError::Other("max_recv_timeouts must be at least 1".into()));
+        }
+        let endpoints = super::open_endpoints(registry, &cfg.input, &cfg.output, &params).await?;
+        // Listener sur :0: le port attribué par l'OS figure dans /relays et sert aux reconnexions
+        if let Some(port) = endpoints.0.effective_options().local_port {
+            cfg.input = pin_assigned_port(&cfg.input, port);
+        }
+
+        let warmup = cfg.warmup_secs.map_or(DEFAULT_WARMUP, Duration::from_secs);
+        let relay_id = short_uuid();
+        let input = redact_uri_secrets(&cfg.input);
+        let output = Arc::new(Mutex::new(cfg.output.clone()));
+        let (commands, rx_commands) = mpsc::channel(COMMAND_QUEUE_LEN);
+        let control = PipeControl::new(rx_commands, output.clone());
+        let paused = control.paused.clone();
+        let id = relay_id.clone();
+        let red_input = input.clone();
+        let labels = cfg.labels.clone();
+        if let Some(m) = Metrics::global() {
+            m.set_relay_labels(&relay_id, labels.clone());
+        }
+        let failed = Arc::new(AtomicBool::new(false));
+        let flag = failed.clone();
+        let handle = tokio::spawn(async move {
+            if let Err(e) = super::run_configured_relay(id.clone(), cfg, Some(endpoints), Some(control)).await {
+                error!(event = events::RELAY_ERROR, subsystem = "relay", relay_id = %id, error = %e, msg = "Managed relay failed");
+                flag.store(true, Ordering::Relaxed);
+                RelayManager::global().record_failure(Some(id), red_input, e.to_string());
+            }
+        });
+        let relay = ManagedRelay { input, output, params, commands, labels, bitrate_thresholds, warmup, handle, paused, failed };
+        self.relays.lock().unwrap_or_else(|e| e.into_inner()).insert(relay_id.clone(), relay);
+        Ok(relay_id)
+    }
+
+    // Arrêt propre via PipeCommand::Stop: le pipe ferme ses extrémités et se termine entre deux lectures.
+    // Si la commande ne peut pas être déposée (file pleine, tâche terminée), la tâche est annulée.
+    pub fn stop(&self, relay_id: &str) -> bool {
+        let Some(relay) = self.relays.lock().unwrap_or_else(|e| e.into_inner()).remove(relay_id) else {
+            return false;
+        };
+        // Le pipe journalise lui-même son arrêt (relay_stop, reason=control)
+        if relay.commands.try_send(PipeCommand::Stop).is_err() {
+            relay.handle.abort();
+            info!(event = events::RELAY_STOP, subsystem = "relay", relay_id = %relay_id, reason = "control", msg = "Relay task cancelled on request");
+        }
+        if let Some(m) = Metrics::global() {
+            m.set_relay_labels(relay_id, HashMap::new());
+        }
+        self.failures.lock().unwrap_or_else(|e| e.into_inner()).retain(|f| f.relay_id.as_deref() != Some(relay_id));
+        true
+    }
+
+    // Nouveau plafond de débit sortant, appliqué sans redémarrer le relais. Ok(None) si le relais est inconnu.
+    pub async fn set_rate_limit(&self, relay_id: &str, max_bitrate: Option<u64>) -> TResult<Option<ManagedRelayInfo>> {
+        let Some((commands, _, _)) = self.command_target(relay_id) else {
+            return Ok(None);
+        };
+        self.send_command(relay_id, &commands, |reply| PipeCommand::SetRateLimit { max_bitrate, reply }).await
+    }
+
+    // Ajoute une cible (une seule URI, même protocole) au fan-out d'un relais en cours
+    pub async fn add_output(&self, relay_id: &str, output: String) -> TResult<Option<ManagedRelayInfo>> {
+        let Some((commands, params, current)) = self.command_target(relay_id) else {
+            return Ok(None);
+        };
+        if split_uris(&output).len() != 1 {
+            return Err(TransportError::InvalidUri("add_output takes a single output URI".into()));
+        }
+        let registry = TransportRegistry::global();
+        same_output_protocol(registry, &current, &output)?;
+        let tx = super::open_tx(super::build_target(registry, output.trim(), &params).await?).await?;
+        let output = output.trim().to_string();
+        self.send_command(relay_id, &commands, |reply| PipeCommand::AddOutput { output, tx, reply }).await
+    }
+
+    // Retire une cible désignée par son URI telle qu'elle figure dans la liste des sorties
+    pub async fn remove_output(&self, relay_id: &str, output: String) -> TResult<Option<ManagedRelayInfo>> {
+        let Some((commands, _, _)) = self.command_target(relay_id) else {
+            return Ok(None);
+        };
+        self.send_command(relay_id, &commands, |reply| PipeCommand::RemoveOutput { output, reply }).await
+    }
+
+    // Make-before-break: la nouvelle sortie est ouverte ici (une erreur est renvoyée à l'appelant sans
+    // toucher au relais), puis confiée au pipe qui alimente les deux sorties pendant `overlap`.
+    // Ok(None) si le relais est inconnu.
+    pub async fn switch_output(&self, relay_id: &str, output: String, overlap: Duration) -> TResult<Option<ManagedRelayInfo>> {
+        let Some((commands, params, current)) = self.command_target(relay_id) else {
+            return Ok(None);
+        };
+        let registry = TransportRegistry::global();
+        same_output_protocol(registry, &current, &output)?;
+        let tx = super::open_tx(super::build_tx(registry, &output, &params).await?).await?;
+        self.send_command(relay_id, &commands, |reply| PipeCommand::SwitchOutput { output, tx, overlap, reply }).await
+    }
+
+    // Suspend la transmission d'un relais sans le détruire: sockets, configuration et entrée du registre
+    // sont conservés, la reprise est immédiate. Ok(None) si le relais est inconnu.
+    pub async fn pause(&self, relay_id: &str, mode: PauseMode) -> TResult<Option<ManagedRelayInfo>> {
+        let Some((commands, _, _)) = self.command_target(relay_id) else {
+            return Ok(None);
+        };
+        self.send_command(relay_id, &commands, |reply| PipeCommand::Pause { mode, reply }).await
+    }
+
+    pub async fn resume(&self, relay_id: &str) -> TResult<Option<ManagedRelayInfo>> {
+        let Some((commands, _, _)) = self.command_target(relay_id) else {
+            return Ok(None);
+        };
+        self.send_command(relay_id, &commands, |reply| PipeCommand::Resume { reply }).await
+    }
+
+    // Capture pcap des datagrammes reçus pendant `duration` (ou jusqu'à `max_packets`); None si le relais est inconnu
+    pub async fn capture(&self, relay_id: &str, duration: Duration, max_packets: Option<u64>) -> TResult<Option<Vec<u8>>> {
+        let target = {
+            let relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+            relays.get(relay_id).map(|r| (r.commands.clone(), capture::input_port(&r.input)))
+        };
+        let Some((commands, port)) = target else {
+            return Ok(None);
+        };
+        let (reply, done) = oneshot::channel();
+        enqueue(&commands, PipeCommand::Capture { tap: CaptureTap::new(duration, max_packets, port, reply) }, COMMAND_REPLY_TIMEOUT).await?;
+        match tokio::time::timeout(duration + COMMAND_REPLY_TIMEOUT, done).await {
+            Ok(Ok(result)) => result.map(Some),
+            Ok(Err(_)) => Err(TransportError::Closed),
+            Err(_) => Err(TransportError::Timeout { during: None }),
+        }
+    }
+
+    // Canal de commandes, paramètres de transport et sorties courantes d'un relais
+    fn command_target(&self, relay_id: &str) -> Option<(mpsc::Sender<PipeCommand>, TransportParams, String)> {
+        let relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        let relay = relays.get(relay_id)?;
+        let current = relay.output.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        Some((relay.commands.clone(), relay.params.clone(), current))
+    }
+
+    async fn send_command<F>(&self, relay_id: &str, commands: &mpsc::Sender<PipeCommand>, command: F) -> TResult<Option<ManagedRelayInfo>>
+    where
+        F: FnOnce(CommandReply) -> PipeCommand,
+    {
+        let (reply, done) = oneshot::channel();
+        enqueue(commands, command(reply), COMMAND_REPLY_TIMEOUT).await?;
+        match tokio::time::timeout(COMMAND_REPLY_TIMEOUT, done).await {
+            Ok(Ok(result)) => result.map(|()| self.info(relay_id)),
+            Ok(Err(_)) => Err(TransportError::Closed),
+            Err(_) => Err(TransportError::Timeout { during: None }),
+        }
+    }
+
+    // Tâche de relais terminée en erreur (relais pilotés ou probes automatiques); `input` déjà masqué
+    pub fn record_failure(&self, relay_id: Option<String>, input: String, error: String) {
+        let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        if failures.len() == MAX_FAILURES {
+            failures.remove(0);
+        }
+        failures.push(RelayFailure { relay_id, input, error });
+    }
+
+    // Relais déclaré dont le démarrage a échoué (URI invalide, bind refusé): compte comme un échec
+    pub fn record_start_failure(&self, input: String, error: String) {
+        self.start_failures.fetch_add(1, Ordering::Relaxed);
+        self.record_failure(None, input, error);
+    }
+
+    // Lance une probe automatique; l'erreur éventuelle est journalisée par l'appelant puis retenue pour /health
+    pub fn spawn_probe<F>(&self, input: String, probe: F) -> JoinHandle<()>
+    where
+        F: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let failed = Arc::new(AtomicBool::new(false));
+        let flag = failed.clone();
+        let handle = tokio::spawn(async move {
+            if let Err(e) = probe.await {
+                flag.store(true, Ordering::Relaxed);
+                RelayManager::global().record_failure(None, input, e.to_string());
+            }
+        });
+        self.probes.lock().unwrap_or_else(|e| e.into_inner()).push(ProbeTask { handle: handle.abort_handle(), failed });
+        handle
+    }
+
+    // Relais déclarés, encore actifs et terminés en erreur, pour l'état de /health
+    pub fn counts(&self) -> RelayCounts {
+        let start_failures = self.start_failures.load(Ordering::Relaxed);
+        let mut counts = RelayCounts { configured: start_failures, active: 0, failed: start_failures };
+        let relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        let probes = self.probes.lock().unwrap_or_else(|e| e.into_inner());
+        let tasks = relays.values().map(|r| (r.handle.is_finished(), &r.failed))
+            .chain(probes.iter().map(|p| (p.handle.is_finished(), &p.failed)));
+        for (finished, failed) in tasks {
+            counts.configured += 1;
+            if !finished {
+                counts.active += 1;
+            }
+            if failed.load(Ordering::Relaxed) {
+                counts.failed += 1;
+            }
+        }
+        counts
+    }
+
+    pub fn failures(&self) -> Vec<RelayFailure> {
+        self.failures.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn bitrate_thresholds(&self, relay_id: &str) -> BitrateThresholds {
+        let relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        relays.get(relay_id).map(|r| r.bitrate_thresholds).unwrap_or_default()
+    }
+
+    pub fn warmup(&self, relay_id: &str) -> Duration {
+        let relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        relays.get(relay_id).map_or(DEFAULT_WARMUP, |r| r.warmup)
+    }
+
+    pub fn info(&self, relay_id: &str) -> Option<ManagedRelayInfo> {
+        let relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        relays.get(relay_id).map(|r| r.info(relay_id))
+    }
+
+    pub fn list(&self) -> Vec<ManagedRelayInfo> {
+        let relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out: Vec<ManagedRelayInfo> = relays.iter().map(|(id, r)| r.info(id)).collect();
+        out.sort_by(|a, b| a.relay_id.cmp(&b.relay_id));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn a_full_command_queue_times_out() {
+        let (commands, mut queue) = mpsc::channel(1);
+        enqueue(&commands, PipeCommand::Stop, Duration::from_millis(10)).await.unwrap();
+        let e = enqueue(&commands, PipeCommand::Stop, Duration::from_millis(10)).await.unwrap_err();
+        assert!(matches!(e, TransportError::Timeout { during: Some(_) }), "{}", e);
+        queue.close();
+        while queue.try_recv().is_ok() {}
+        assert!(matches!(enqueue(&commands, PipeCommand::Stop, Duration::from_millis(10)).await, Err(TransportError::Closed)));
+    }
+}
@@ -1,70 +1,317 @@
 pub mod transport;
+pub mod acl;
+pub mod capture;
+pub mod command;
+pub mod peers;
 pub mod pipe;
 pub mod srt;
 pub mod rist;
+pub mod bonding;
+pub mod rtp;
+pub mod socket;
+pub mod resolve;
+pub mod ts;
+pub mod registry;
+pub mod reconnect;
+pub mod jitter;
+pub mod keepalive;
+pub mod ratelimit;
+pub mod overrun;
+pub mod watchdog;
+pub mod selftest;
+pub mod probe;
+pub mod manager;
+pub mod impair;
+pub mod fanout;
+pub mod mtu;
+pub mod appfrag;
+pub mod sendqueue;
+pub mod recvbuf;
+pub mod recvbatch;
+// Étapes bloquantes (résolution, ouverture) déportées sur le pool bloquant de Tokio
+pub mod blocking;
 
+use std::time::Instant;
 use anyhow::Result;
 use tokio::task::JoinHandle;
-use tracing::{info, error};
+use tracing::{info, warn, error, instrument};
 
-use crate::relay::pipe::run_pipe;
-use crate::relay::srt::{SrtReceiver, SrtSender};
-use crate::relay::rist::{RistReceiver, RistSender};
+use crate::common::features;
+use crate::relay::command::{DownAction, PipeControl};
+use crate::relay::fanout::FanOutTx;
+use crate::relay::pipe::{run_pipe, PipeOptions, StopReason};
+use crate::relay::reconnect::ReconnectPolicy;
+use crate::relay::registry::{TransportParams, TransportRegistry};
+use crate::relay::transport::{RxEndpoint, TransportMeta, TxEndpoint};
+use crate::structures::{Metrics, RelayProtocols, TResult, TransportError};
+use crate::common::config::{format_labels, RelayConfig};
 use crate::common::logging::{events, short_uuid};
-use crate::common::uri::redact_uri_secrets;
+use crate::common::uri::{pin_assigned_port, redact_uri_list, redact_uri_secrets, split_uris};
 
-pub async fn run_srt_probe(input: String, output: String, latency_ms: u64) -> Result<()> {
-    let relay_id = short_uuid();
-    let red_in = redact_uri_secrets(&input);
-    let red_out = redact_uri_secrets(&output);
-    info!(event = events::RELAY_START, subsystem = "srt", protocol = "srt", relay_id = %relay_id, input = %red_in, output = %red_out, latency_ms = latency_ms, msg = "SRT probe start");
-    let rx = SrtReceiver::from_input_uri(&input, latency_ms)?;
-    let tx = SrtSender::from_output_uri(&output, latency_ms)?;
-    // Boucle de pipe jusqu'à Ctrl+C
-    if let Err(e) = run_pipe(rx, tx, "srt", &relay_id).await {
-        error!(event = events::RELAY_ERROR, subsystem = "srt", protocol = "srt", relay_id = %relay_id, error = %e, msg = "SRT pipe error");
+// Construit et ouvre les deux extrémités; en cas d'échec rien ne reste ouvert.
+// `output` peut lister plusieurs cibles séparées par des virgules: un émetteur par cible, en fan-out.
+async fn open_endpoints(registry: &'static TransportRegistry, input: &str, output: &str, params: &TransportParams) -> TResult<Endpoints> {
+    let mut rx = appfrag::wrap_rx(registry.build_rx(input, params)?, input)?;
+    let mut tx = build_tx(registry, output, params).await?;
+    // open() peut bloquer (sonde d'accessibilité ?reachability_check, liaison FFI): sur le pool bloquant
+    blocking::run(move || {
+        rx.open()?;
+        if let Err(e) = tx.open() {
+            rx.close();
+            return Err(e);
+        }
+        Ok((rx, tx))
+    })
+    .await
+}
+
+// Ouverture d'une sortie construite à part (ajout ou bascule sur un relais en cours), hors des workers
+async fn open_tx<T: TransportMeta + Send + 'static>(mut tx: T) -> TResult<T> {
+    blocking::run(move || tx.open().map(|()| tx)).await
+}
+
+// Côté émission seul, non ouvert: utilisé aussi pour modifier les sorties d'un relais en cours.
+// Toujours un FanOutTx (une cible par URI, dans l'ordre de la liste) pour pouvoir en ajouter ou en retirer.
+// La construction résout les noms d'hôte des cibles (getaddrinfo, bloquant): sur le pool bloquant.
+async fn build_tx(registry: &'static TransportRegistry, output: &str, params: &TransportParams) -> TResult<FanOutTx> {
+    let (output, params) = (output.to_string(), params.clone());
+    blocking::run(move || {
+        let targets = split_uris(&output)
+            .into_iter()
+            .map(|uri| build_target_blocking(registry, uri, &params))
+            .collect::<TResult<Vec<_>>>()?;
+        Ok(FanOutTx::new(targets))
+    })
+    .await
+}
+
+async fn build_target(registry: &'static TransportRegistry, uri: &str, params: &TransportParams) -> TResult<Box<dyn TxEndpoint>> {
+    let (uri, params) = (uri.to_string(), params.clone());
+    blocking::run(move || build_target_blocking(registry, &uri, &params)).await
+}
+
+// Une cible de sortie avec ses enveloppes (garde MTU, fragmentation applicative au-dessus d'elle pour
+// que les fragments la passent, impairments, puis la file d'émission qui découple le tout de la boucle
+// de réception)
+fn build_target_blocking(registry: &TransportRegistry, uri: &str, params: &TransportParams) -> TResult<Box<dyn TxEndpoint>> {
+    let tx = appfrag::wrap_tx(mtu::wrap_tx(registry.build_tx(uri, params)?, uri)?, uri)?;
+    sendqueue::wrap_tx(impair::wrap_tx(tx, uri)?, uri)
+}
+
+// Relais générique: récepteur et émetteur construits via le registre selon le schéma des URIs.
+// Une erreur au premier démarrage est renvoyée telle quelle; une erreur en cours de relais déclenche
+// des reconnexions selon `policy`.
+// `opts.max_runtime` couvre toute la vie du relais: le temps passé en reconnexion est décompté.
+pub async fn run_relay(input: String, output: String, params: TransportParams, policy: ReconnectPolicy, opts: PipeOptions) -> Result<()> {
+    run_relay_as(short_uuid(), RelaySetup { input, output, params, policy, opts, endpoints: None, control: None }).await
+}
+
+type Endpoints = (Box<dyn RxEndpoint>, FanOutTx);
+
+// Les stats du relais survivent à ses pipes tant que run_relay_as tourne, quel que soit le chemin de sortie
+struct TrackedRelay<'a>(&'a str);
+
+impl<'a> TrackedRelay<'a> {
+    fn new(relay_id: &'a str) -> Self {
+        if let Some(m) = Metrics::global() { m.track_relay(relay_id); }
+        Self(relay_id)
     }
-    Ok(())
 }
 
-pub async fn run_rist_probe(input: String, output: String) -> Result<()> {
-    let relay_id = short_uuid();
+impl Drop for TrackedRelay<'_> {
+    fn drop(&mut self) {
+        if let Some(m) = Metrics::global() { m.untrack_relay(self.0); }
+    }
+}
+
+// Ce qu'il faut pour faire tourner un relais: URIs, paramètres de transport et de reconnexion, options
+// du pipe, et pour les relais pilotés, des extrémités déjà ouvertes et un canal de commandes
+struct RelaySetup {
+    input: String,
+    output: String,
+    params: TransportParams,
+    policy: ReconnectPolicy,
+    opts: PipeOptions,
+    endpoints: Option<Endpoints>,
+    control: Option<PipeControl>,
+}
+
+// Variante à relay_id imposé (relais pilotés par le RelayManager), éventuellement avec des extrémités
+// déjà ouvertes par l'appelant et un canal de commandes (basculement de sortie)
+async fn run_relay_as(relay_id: String, setup: RelaySetup) -> Result<()> {
+    let RelaySetup { input, output, params, policy, mut opts, endpoints, mut control } = setup;
+    let registry = TransportRegistry::global();
+    let protocols = RelayProtocols { input: registry.resolve(&input)?, output: registry.resolve(split_uris(&output)[0])? };
+    let protocol = protocols.input;
     let red_in = redact_uri_secrets(&input);
-    let red_out = redact_uri_secrets(&output);
-    info!(event = events::RELAY_START, subsystem = "rist", protocol = "rist", relay_id = %relay_id, input = %red_in, output = %red_out, msg = "RIST probe start");
-    let rx = RistReceiver::from_input_uri(&input)?;
-    let tx = RistSender::from_output_uri(&output)?;
-    if let Err(e) = run_pipe(rx, tx, "rist", &relay_id).await {
-        error!(event = events::RELAY_ERROR, subsystem = "rist", protocol = "rist", relay_id = %relay_id, error = %e, msg = "RIST pipe error");
+    let red_out = redact_uri_list(&output);
+    info!(event = events::RELAY_START, subsystem = protocol, protocol = protocol, output_protocol = protocols.output, relay_id = %relay_id, input = %red_in, output = %red_out, latency_ms = params.latency_ms, msg = "Relay start");
+    let started = Instant::now();
+    let (mut rx, mut tx) = match endpoints {
+        Some(endpoints) => endpoints,
+        None => open_endpoints(registry, &input, &output, &params).await?,
+    };
+    // Un listener sur :0 garde le port attribué par l'OS d'une reconnexion à l'autre
+    let input = match rx.effective_options().local_port {
+        Some(port) => pin_assigned_port(&input, port),
+        None => input,
+    };
+    let _tracked = TrackedRelay::new(&relay_id);
+    let mut attempt: u32 = 0;
+    let mut down_since: Option<Instant> = None;
+    // Boucle de pipe jusqu'à Ctrl+C, avec reconnexion sur erreur
+    loop {
+        if let Some(since) = down_since.take() {
+            let down = since.elapsed();
+            info!(event = events::RECONNECT_SUCCESS, subsystem = protocol, protocol = protocol, relay_id = %relay_id, attempt = attempt, down_ms = down.as_millis() as u64, outcome = "recovered", msg = "Relay reconnected");
+            if let Some(m) = Metrics::global() {
+                m.reconnect_duration_seconds.with_label_values(&["recovered"]).observe(down.as_secs_f64());
+            }
+            attempt = 0;
+        }
+        if let Some(rate_limit) = control.as_ref().and_then(|c| c.rate_limit) {
+            opts.rate_limit = rate_limit;
+        }
+        let remaining = opts.max_runtime.map(|max| max.saturating_sub(started.elapsed()));
+        // Motif repris par chaque reconnect_attempt de la coupure: watchdog ou erreur de transport
+        let reason = match run_pipe(rx, tx, protocols, &relay_id, PipeOptions { max_runtime: remaining, ..opts.clone() }, control.as_mut()).await {
+            Ok(_) => return Ok(()),
+            Err(TransportError::Stalled { .. }) => "watchdog",
+            Err(e) => {
+                error!(event = events::RELAY_ERROR, subsystem = protocol, protocol = protocol, relay_id = %relay_id, error = %e, msg = "Relay pipe error");
+                "error"
+            }
+        };
+        let since = Instant::now();
+        down_since = Some(since);
+        (rx, tx) = loop {
+            attempt += 1;
+            if policy.exhausted(attempt) {
+                let down = since.elapsed();
+                error!(event = events::RECONNECT_GIVEUP, subsystem = protocol, protocol = protocol, relay_id = %relay_id, attempt = attempt - 1, down_ms = down.as_millis() as u64, outcome = "giveup", msg = "Relay reconnect attempts exhausted");
+                if let Some(m) = Metrics::global() {
+                    m.reconnect_duration_seconds.with_label_values(&["giveup"]).observe(down.as_secs_f64());
+                }
+                return Err(TransportError::Closed.into());
+            }
+            let backoff = policy.backoff(attempt);
+            if let Some(max) = opts.max_runtime
+                && started.elapsed() + backoff >= max
+            {
+                info!(event = events::RELAY_STOP, subsystem = protocol, protocol = protocol, relay_id = %relay_id, reason = StopReason::MaxRuntime.as_str(), msg = "Relay reached its maximum runtime while reconnecting");
+                return Ok(());
+            }
+            info!(event = events::RECONNECT_SCHEDULED, subsystem = protocol, protocol = protocol, relay_id = %relay_id, attempt = attempt, backoff_ms = backoff.as_millis() as u64, msg = "Relay reconnect scheduled");
+            let action = match control.as_mut() {
+                Some(c) => c.wait_down(backoff, opts.rate_limit).await,
+                None => {
+                    tokio::time::sleep(backoff).await;
+                    DownAction::Retry
+                }
+            };
+            if action == DownAction::Stop {
+                info!(event = events::RELAY_STOP, subsystem = protocol, protocol = protocol, relay_id = %relay_id, reason = StopReason::Control.as_str(), msg = "Relay stopped on request while reconnecting");
+                return Ok(());
+            }
+            if let Some(rate_limit) = control.as_ref().and_then(|c| c.rate_limit) {
+                opts.rate_limit = rate_limit;
+            }
+            info!(event = events::RECONNECT_ATTEMPT, subsystem = protocol, protocol = protocol, relay_id = %relay_id, attempt = attempt, reason = reason, msg = "Relay reconnect attempt");
+            if let Some(m) = Metrics::global() { m.record_reconnect_attempt(&relay_id); }
+            // Sorties modifiées avant ou pendant la coupure: la liste courante remplace celle de départ
+            let output = control.as_ref().map_or_else(|| output.clone(), PipeControl::current_output);
+            match open_endpoints(registry, &input, &output, &params).await {
+                Ok(endpoints) => break endpoints,
+                Err(e) => warn!(event = events::RECONNECT_ATTEMPT, subsystem = protocol, protocol = protocol, relay_id = %relay_id, attempt = attempt, reason = reason, error = %e, outcome = "failed", msg = "Relay reconnect attempt failed"),
+            }
+        };
+    }
+}
+
+// Démarre en tâche de fond, via le RelayManager, un relais décrit dans le fichier de configuration
+pub async fn start_relay(cfg: RelayConfig) {
+    let input = redact_uri_secrets(&cfg.input);
+    let manager = manager::RelayManager::global();
+    if let Err(e) = manager.start(cfg).await {
+        error!(event = events::RELAY_ERROR, subsystem = "relay", error = %e, msg = "Configured relay failed");
+        manager.record_start_failure(input, e.to_string());
+    }
+}
+
+// Le span porte les étiquettes utilisateur sur les logs de reconnexion; run_pipe les reprend dans le sien
+#[instrument(name = "relay", skip_all, fields(relay_id = %relay_id, labels = %format_labels(&cfg.labels)))]
+async fn run_configured_relay(relay_id: String, cfg: RelayConfig, endpoints: Option<Endpoints>, control: Option<PipeControl>) -> Result<()> {
+    let policy = ReconnectPolicy { max_attempts: cfg.max_reconnects, ..ReconnectPolicy::default() };
+    let mut opts = PipeOptions::from_uris(&cfg.input, &cfg.output)?;
+    // Les champs du fichier de configuration l'emportent sur les paramètres d'URI équivalents
+    if let Some(secs) = cfg.max_runtime_secs {
+        opts.max_runtime = Some(std::time::Duration::from_secs(secs));
+    }
+    if let Some(secs) = cfg.idle_timeout_secs {
+        opts.idle_timeout = Some(std::time::Duration::from_secs(secs));
+    }
+    opts.max_recv_timeouts = cfg.max_recv_timeouts.or(opts.max_recv_timeouts);
+    opts.rate_limit.max_bitrate = cfg.max_bitrate.or(opts.rate_limit.max_bitrate);
+    opts.rate_limit.max_pps = cfg.max_pps.or(opts.rate_limit.max_pps);
+    if let Some(ceiling_bps) = cfg.overrun_bitrate.or(opts.overrun.map(|o| o.ceiling_bps)) {
+        let action = cfg.overrun_action.or(opts.overrun.map(|o| o.action)).unwrap_or_default();
+        opts.overrun = Some(overrun::OverrunConfig { ceiling_bps, action });
+    }
+    // watchdog_secs = 0 désactive un ?watchdog présent sur l'URI
+    match cfg.watchdog_secs {
+        Some(0) => opts.watchdog = None,
+        Some(secs) => {
+            let action = opts.watchdog.map(|w| w.action).unwrap_or_default();
+            opts.watchdog = Some(watchdog::WatchdogConfig { silence: std::time::Duration::from_secs(secs), action });
+        }
+        None => {}
     }
-    Ok(())
+    if let (Some(wd), Some(action)) = (opts.watchdog.as_mut(), cfg.watchdog_action) {
+        wd.action = action;
+    }
+    let params = TransportParams { latency_ms: cfg.latency_ms, reachability_check: cfg.reachability_check };
+    run_relay_as(relay_id, RelaySetup { input: cfg.input, output: cfg.output, params, policy, opts, endpoints, control }).await
+}
+
+// Les sous-commandes historiques restent des enveloppes fixant le protocole attendu
+fn require_scheme(uri: &str, scheme: &str) -> Result<(), TransportError> {
+    match registry::scheme_of(uri) {
+        Some(s) if s.eq_ignore_ascii_case(scheme) => Ok(()),
+        _ => Err(TransportError::InvalidUri(format!("expected a {}:// URI: {}", scheme, redact_uri_secrets(uri)))),
+    }
+}
+
+pub async fn run_srt_probe(input: String, output: String, latency_ms: u64) -> Result<()> {
+    features::require_transport("srt")?;
+    require_scheme(&input, "srt")?;
+    split_uris(&output).into_iter().try_for_each(|uri| require_scheme(uri, "srt"))?;
+    let opts = PipeOptions::from_uris(&input, &output)?;
+    run_relay(input, output, TransportParams { latency_ms, ..TransportParams::default() }, ReconnectPolicy::default(), opts).await
+}
+
+pub async fn run_rist_probe(input: String, output: String) -> Result<()> {
+    features::require_transport("rist")?;
+    require_scheme(&input, "rist")?;
+    split_uris(&output).into_iter().try_for_each(|uri| require_scheme(uri, "rist"))?;
+    let opts = PipeOptions::from_uris(&input, &output)?;
+    run_relay(input, output, TransportParams::default(), ReconnectPolicy::default(), opts).await
 }
 
 // Auto-run background tasks that keep endpoints open and run the pipe in background
 pub fn start_srt_auto(input: String, output: String, latency_ms: u64) -> JoinHandle<()> {
-    tokio::spawn(async move {
-        let relay_id = short_uuid();
-        let red_in = redact_uri_secrets(&input);
-        let red_out = redact_uri_secrets(&output);
-        info!(event = events::RELAY_START, subsystem = "srt", protocol = "srt", relay_id = %relay_id, input = %red_in, output = %red_out, latency_ms = latency_ms, msg = "SRT auto start");
-        let rx = match SrtReceiver::from_input_uri(&input, latency_ms) { Ok(v) => v, Err(e) => { error!(event = events::RELAY_ERROR, subsystem = "srt", protocol = "srt", relay_id = %relay_id, error = %e, msg = "SRT init rx failed"); return; } };
-        let tx = match SrtSender::from_output_uri(&output, latency_ms) { Ok(v) => v, Err(e) => { error!(event = events::RELAY_ERROR, subsystem = "srt", protocol = "srt", relay_id = %relay_id, error = %e, msg = "SRT init tx failed"); return; } };
-        if let Err(e) = run_pipe(rx, tx, "srt", &relay_id).await {
-            error!(event = events::RELAY_ERROR, subsystem = "srt", protocol = "srt", relay_id = %relay_id, error = %e, msg = "SRT pipe error");
-        }
+    let red_input = redact_uri_secrets(&input);
+    manager::RelayManager::global().spawn_probe(red_input, async move {
+        run_srt_probe(input, output, latency_ms).await.inspect_err(|e| {
+            error!(event = events::RELAY_ERROR, subsystem = "srt", protocol = "srt", error = %e, msg = "SRT auto start failed");
+        })
     })
 }
 
 pub fn start_rist_auto(input: String, output: String) -> JoinHandle<()> {
-    tokio::spawn(async move {
-        let relay_id = short_uuid();
-        let red_in = redact_uri_secrets(&input);
-        let red_out = redact_uri_secrets(&output);
-        info!(event = events::RELAY_START, subsystem = "rist", protocol = "rist", relay_id = %relay_id, input = %red_in, output = %red_out, msg = "RIST auto start");
-        let rx = match RistReceiver::from_input_uri(&input) { Ok(v) => v, Err(e) => { error!(event = events::RELAY_ERROR, subsystem = "rist", protocol = "rist", relay_id = %relay_id, error = %e, msg = "RIST init rx failed"); return; } };
-        let tx = match RistSender::from_output_uri(&output) { Ok(v) => v, Err(e) => { error!(event = events::RELAY_ERROR, subsystem = "rist", protocol = "rist", relay_id = %relay_id, error = %e, msg = "RIST init tx failed"); return; } };
-        if let Err(e) = run_pipe(rx, tx, "rist", &relay_id).await {
-            error!(event = events::RELAY_ERROR, subsystem = "rist", protocol = "rist", relay_id = %relay_id, error = %e, msg = "RIST pipe error");
-        }
+    let red_input = redact_uri_secrets(&input);
+    manager::RelayManager::global().spawn_probe(red_input, async move {
+        run_rist_probe(input, output).await.inspect_err(|e| {
+            error!(event = events::RELAY_ERROR, subsystem = "rist", protocol = "rist", error = %e, msg = "RIST auto start failed");
+        })
     })
 }
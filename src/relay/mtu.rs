@@ -0,0 +1,131 @@
+// Egress payload size enforcement (?mtu=1316 on an output): a buffer larger than the limit is never
+// sent as one oversized datagram, which the network would fragment at the IP layer. It is rejected
+// and counted, or with ?mtu_split=1 cut into smaller datagrams when it carries whole MPEG-TS packets.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tracing::warn;
+
+use crate::common::logging::events;
+use crate::common::uri::query_param;
+use crate::relay::transport::{BufferOccupancy, EffectiveOptions, TransportMeta, TransportTx, TxEndpoint};
+use crate::relay::ts::TS_PACKET_LEN;
+use crate::structures::{Metrics, TResult, TransportError};
+
+// Smallest useful limit: one TS packet (and well under any real MTU)
+const MIN_MTU: usize = TS_PACKET_LEN;
+
+pub struct MtuGuard {
+    inner: Box<dyn TxEndpoint>,
+    mtu: usize,
+    split: bool,
+    warned: bool,
+}
+
+// Chunk size for a TS-aligned buffer: as many whole TS packets as fit, None if the buffer is not aligned
+fn ts_chunk_len(len: usize, mtu: usize) -> Option<usize> {
+    (len.is_multiple_of(TS_PACKET_LEN) && mtu >= TS_PACKET_LEN).then(|| mtu / TS_PACKET_LEN * TS_PACKET_LEN)
+}
+
+impl MtuGuard {
+    pub fn new(inner: Box<dyn TxEndpoint>, mtu: usize, split: bool) -> Self {
+        Self { inner, mtu, split, warned: false }
+    }
+
+    fn count(&self, action: &str) {
+        if let Some(m) = Metrics::global() {
+            m.oversized_datagrams_total.with_label_values(&[action]).inc();
+        }
+    }
+}
+
+#[async_trait]
+impl TransportTx for MtuGuard {
+    async fn send(&mut self, buf: &[u8]) -> TResult<usize> {
+        if buf.len() <= self.mtu {
+            return self.inner.send(buf).await;
+        }
+        let chunk = if self.split { ts_chunk_len(buf.len(), self.mtu) } else { None };
+        if !self.warned {
+            self.warned = true;
+            let action = if chunk.is_some() { "split" } else { "rejected" };
+            warn!(event = events::DATAGRAM_OVERSIZED, subsystem = "net", len = buf.len(), mtu = self.mtu, action = action, output = %self.inner.describe(), msg = "Datagram larger than the configured mtu (first occurrence); check the upstream payload size");
+        }
+        let Some(chunk) = chunk else {
+            self.count("rejected");
+            return Ok(0);
+        };
+        self.count("split");
+        let mut sent = 0;
+        for part in buf.chunks(chunk) {
+            sent += self.inner.send(part).await?;
+        }
+        Ok(sent)
+    }
+}
+
+impl TransportMeta for MtuGuard {
+    fn open(&mut self) -> TResult<()> {
+        self.inner.open()
+    }
+    fn close(&mut self) {
+        self.inner.close()
+    }
+    fn describe(&self) -> String {
+        format!("{} mtu={}{}", self.inner.describe(), self.mtu, if self.split { " mtu_split" } else { "" })
+    }
+    fn effective_options(&self) -> EffectiveOptions {
+        self.inner.effective_options()
+    }
+    fn buffer_occupancy(&self) -> Option<BufferOccupancy> {
+        self.inner.buffer_occupancy()
+    }
+    fn take_rtt_sample(&mut self) -> Option<Duration> {
+        self.inner.take_rtt_sample()
+    }
+}
+
+pub fn mtu_from_uri(uri: &str) -> TResult<Option<(usize, bool)>> {
+    let split = match query_param(uri, "mtu_split").map(|v| v.to_ascii_lowercase()).as_deref() {
+        None | Some("0") | Some("false") => false,
+        Some("1") | Some("true") => true,
+        Some(other) => return Err(TransportError::InvalidUri(format!("mtu_split must be 0/1 or true/false, got {}", other))),
+    };
+    let Some(raw) = query_param(uri, "mtu") else {
+        return if split { Err(TransportError::InvalidUri("mtu_split requires mtu".into())) } else { Ok(None) };
+    };
+    match raw.parse::<usize>() {
+        Ok(mtu) if (MIN_MTU..=65507).contains(&mtu) => Ok(Some((mtu, split))),
+        _ => Err(TransportError::InvalidUri(format!("mtu must be a payload size between {} and 65507 bytes, got {}", MIN_MTU, raw))),
+    }
+}
+
+// Wraps the sender when ?mtu is set on its URI; returns it unchanged otherwise
+pub fn wrap_tx(tx: Box<dyn TxEndpoint>, uri: &str) -> TResult<Box<dyn TxEndpoint>> {
+    Ok(match mtu_from_uri(uri)? {
+        Some((mtu, split)) => Box::new(MtuGuard::new(tx, mtu, split)),
+        None => tx,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mtu_params() {
+        assert_eq!(mtu_from_uri("srt://h:1").unwrap(), None);
+        assert_eq!(mtu_from_uri("srt://h:1?mtu=1316").unwrap(), Some((1316, false)));
+        assert_eq!(mtu_from_uri("srt://h:1?mtu=1316&mtu_split=1").unwrap(), Some((1316, true)));
+        assert!(mtu_from_uri("srt://h:1?mtu=10").is_err());
+        assert!(mtu_from_uri("srt://h:1?mtu_split=1").is_err());
+    }
+
+    #[test]
+    fn splits_only_ts_aligned_buffers() {
+        assert_eq!(ts_chunk_len(14 * TS_PACKET_LEN, 1316), Some(7 * TS_PACKET_LEN));
+        assert_eq!(ts_chunk_len(14 * TS_PACKET_LEN, 1400), Some(7 * TS_PACKET_LEN));
+        assert_eq!(ts_chunk_len(2000, 1316), None);
+    }
+}
@@ -0,0 +1,151 @@
+// Overrun protection (?overrun_bitrate=BPS on the input, or overrun_bitrate in the config file): when
+// the smoothed input rate goes above the ceiling (a misconfigured encoder sending 200 Mbit/s), the relay
+// enters a distinct "overrun" state, logged once and shown in /stats, /relays and relay_overrun.
+// With ?overrun_action=cap the egress is also held at the ceiling until the state clears, so a single
+// bad feed cannot saturate a shared uplink; the default (alarm) keeps forwarding everything.
+// The state clears once the rate falls back under CLEAR_RATIO of the ceiling: a capped feed hovering
+// right at the ceiling stays in overrun instead of flapping.
+
+use serde::Deserialize;
+
+use crate::common::uri::query_param;
+use crate::relay::ratelimit::RateLimitConfig;
+use crate::structures::{TResult, TransportError};
+
+const CLEAR_RATIO: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverrunAction {
+    // Alarme seule, tout est transmis
+    #[default]
+    Alarm,
+    // Alarme et plafond d'émission au niveau du seuil tant que l'alarme dure
+    Cap,
+}
+
+impl OverrunAction {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.to_ascii_lowercase().as_str() {
+            "alarm" => Some(OverrunAction::Alarm),
+            "cap" => Some(OverrunAction::Cap),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OverrunAction::Alarm => "alarm",
+            OverrunAction::Cap => "cap",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverrunConfig {
+    pub ceiling_bps: u64,
+    pub action: OverrunAction,
+}
+
+// ?overrun_bitrate=BPS [&overrun_action=alarm|cap] on an input URI; None when absent
+pub fn overrun_from_uri(uri: &str) -> TResult<Option<OverrunConfig>> {
+    let Some(raw) = query_param(uri, "overrun_bitrate") else { return Ok(None) };
+    let ceiling_bps = raw.parse::<u64>().ok().filter(|&bps| bps > 0).ok_or_else(|| {
+        TransportError::InvalidUri(format!("overrun_bitrate must be a positive bitrate in bits/s, got {}", raw))
+    })?;
+    let action = match query_param(uri, "overrun_action") {
+        None => OverrunAction::default(),
+        Some(raw) => OverrunAction::parse(&raw)
+            .ok_or_else(|| TransportError::InvalidUri(format!("overrun_action must be alarm or cap, got {}", raw)))?,
+    };
+    Ok(Some(OverrunConfig { ceiling_bps, action }))
+}
+
+// Changement d'état renvoyé par OverrunDetector::observe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverrunChange {
+    Raised,
+    Cleared,
+}
+
+#[derive(Debug)]
+pub struct OverrunDetector {
+    pub cfg: OverrunConfig,
+    active: bool,
+}
+
+impl OverrunDetector {
+    pub fn new(cfg: OverrunConfig) -> Self {
+        Self { cfg, active: false }
+    }
+
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    // Compare le débit entrant lissé (bits/s) au seuil; Some seulement quand l'état change
+    pub fn observe(&mut self, bps: f64) -> Option<OverrunChange> {
+        let ceiling = self.cfg.ceiling_bps as f64;
+        if !self.active && bps > ceiling {
+            self.active = true;
+            Some(OverrunChange::Raised)
+        } else if self.active && bps < ceiling * CLEAR_RATIO {
+            self.active = false;
+            Some(OverrunChange::Cleared)
+        } else {
+            None
+        }
+    }
+
+    // Plafond d'émission effectif: celui du relais, abaissé au seuil pendant un dépassement en mode cap
+    pub fn cap(&self, cfg: RateLimitConfig) -> RateLimitConfig {
+        if !self.active || self.cfg.action != OverrunAction::Cap {
+            return cfg;
+        }
+        let ceiling = self.cfg.ceiling_bps;
+        RateLimitConfig { max_bitrate: Some(cfg.max_bitrate.map_or(ceiling, |bps| bps.min(ceiling))), ..cfg }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_overrun_params() {
+        assert_eq!(overrun_from_uri("srt://@:9000").unwrap(), None);
+        let cfg = overrun_from_uri("srt://@:9000?overrun_bitrate=20000000").unwrap().unwrap();
+        assert_eq!(cfg, OverrunConfig { ceiling_bps: 20_000_000, action: OverrunAction::Alarm });
+        let cfg = overrun_from_uri("srt://@:9000?overrun_bitrate=20000000&overrun_action=CAP").unwrap().unwrap();
+        assert_eq!(cfg.action, OverrunAction::Cap);
+        assert!(overrun_from_uri("srt://@:9000?overrun_bitrate=0").is_err());
+        assert!(overrun_from_uri("srt://@:9000?overrun_bitrate=20M").is_err());
+        assert!(overrun_from_uri("srt://@:9000?overrun_bitrate=1000&overrun_action=drop").is_err());
+    }
+
+    #[test]
+    fn raises_once_and_clears_with_hysteresis() {
+        let mut d = OverrunDetector::new(OverrunConfig { ceiling_bps: 10_000_000, action: OverrunAction::Cap });
+        assert_eq!(d.observe(9_000_000.0), None);
+        assert_eq!(d.observe(200_000_000.0), Some(OverrunChange::Raised));
+        assert_eq!(d.observe(150_000_000.0), None);
+        // Flux plafonné autour du seuil: l'alarme tient
+        assert_eq!(d.observe(9_950_000.0), None);
+        assert!(d.active());
+        assert_eq!(d.observe(8_000_000.0), Some(OverrunChange::Cleared));
+        assert!(!d.active());
+    }
+
+    #[test]
+    fn cap_lowers_egress_only_while_active() {
+        let limit = RateLimitConfig { max_bitrate: Some(50_000_000), max_pps: Some(1000) };
+        let mut d = OverrunDetector::new(OverrunConfig { ceiling_bps: 10_000_000, action: OverrunAction::Cap });
+        assert_eq!(d.cap(limit), limit);
+        d.observe(20_000_000.0);
+        assert_eq!(d.cap(limit), RateLimitConfig { max_bitrate: Some(10_000_000), max_pps: Some(1000) });
+        assert_eq!(d.cap(RateLimitConfig::default()).max_bitrate, Some(10_000_000));
+        let mut alarm = OverrunDetector::new(OverrunConfig { ceiling_bps: 10_000_000, action: OverrunAction::Alarm });
+        alarm.observe(20_000_000.0);
+        assert_eq!(alarm.cap(limit), limit);
+    }
+}
@@ -0,0 +1,134 @@
+// Sources distinctes vues par un listener UDP (adresse source de recv_from): peer_connected au premier
+// datagramme d'une nouvelle source, peer_disconnected après PEER_IDLE_TIMEOUT de silence ou à la
+// fermeture. Plusieurs émetteurs peuvent pousser en même temps; chacun a sa propre session. Partagé
+// par les listeners SRT et RIST (protocole en étiquette des journaux).
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::common::logging::events;
+use crate::common::uri::redact_addr;
+
+// Silence après lequel une source est considérée partie (SRTO_PEERIDLETIMEO par défaut de libsrt)
+pub const PEER_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Au-delà, les nouvelles sources ne sont plus suivies (adresses usurpées, scan): le flux passe, sans journal
+pub const MAX_TRACKED_PEERS: usize = 64;
+
+// Intervalle minimal entre deux recherches de sources inactives pendant un flux continu
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+struct PeerActivity {
+    started: Instant,
+    last_seen: Instant,
+    bytes: u64,
+    packets: u64,
+}
+
+pub struct PeerTracker {
+    protocol: &'static str,
+    peers: HashMap<SocketAddr, PeerActivity>,
+    last_sweep: Instant,
+    overflow_logged: bool,
+}
+
+impl PeerTracker {
+    pub fn new(protocol: &'static str) -> Self {
+        Self { protocol, peers: HashMap::new(), last_sweep: Instant::now(), overflow_logged: false }
+    }
+
+    pub fn seen(&mut self, peer: SocketAddr, len: usize) {
+        self.seen_at(peer, len, Instant::now());
+    }
+
+    fn seen_at(&mut self, peer: SocketAddr, len: usize, now: Instant) {
+        if now.duration_since(self.last_sweep) >= SWEEP_INTERVAL {
+            self.expire_at(now);
+        }
+        if !self.peers.contains_key(&peer) {
+            if self.peers.len() >= MAX_TRACKED_PEERS {
+                if !self.overflow_logged {
+                    self.overflow_logged = true;
+                    warn!(event = events::PEER_LIMIT, subsystem = self.protocol, protocol = self.protocol, peer = %redact_addr(&peer), tracked = self.peers.len(), msg = "Too many distinct sources on this listener; new sources are no longer logged");
+                }
+                return;
+            }
+            info!(event = events::PEER_CONNECTED, subsystem = self.protocol, protocol = self.protocol, peer = %redact_addr(&peer), peers = self.peers.len() + 1, msg = "New source sending to the listener");
+            self.peers.insert(peer, PeerActivity { started: now, last_seen: now, bytes: 0, packets: 0 });
+        }
+        if let Some(p) = self.peers.get_mut(&peer) {
+            p.last_seen = now;
+            p.bytes += len as u64;
+            p.packets += 1;
+        }
+    }
+
+    // Appelé sur le timeout de réception et périodiquement pendant le flux
+    pub fn expire(&mut self) {
+        self.expire_at(Instant::now());
+    }
+
+    fn expire_at(&mut self, now: Instant) {
+        self.last_sweep = now;
+        let idle: Vec<SocketAddr> = self.peers.iter().filter(|(_, p)| now.duration_since(p.last_seen) >= PEER_IDLE_TIMEOUT).map(|(a, _)| *a).collect();
+        for peer in idle {
+            self.end(peer, "idle");
+        }
+    }
+
+    pub fn close_all(&mut self) {
+        let all: Vec<SocketAddr> = self.peers.keys().copied().collect();
+        for peer in all {
+            self.end(peer, "closed");
+        }
+        self.overflow_logged = false;
+    }
+
+    fn end(&mut self, peer: SocketAddr, reason: &'static str) {
+        if let Some(p) = self.peers.remove(&peer) {
+            info!(event = events::PEER_DISCONNECTED, subsystem = self.protocol, protocol = self.protocol, peer = %redact_addr(&peer), bytes = p.bytes, packets = p.packets, duration_ms = p.last_seen.duration_since(p.started).as_millis() as u64, reason = reason, msg = "Source stopped sending to the listener");
+        }
+    }
+}
+
+impl Drop for PeerTracker {
+    fn drop(&mut self) {
+        self.close_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_distinct_sources_and_expires_idle_ones() {
+        let a: SocketAddr = "192.0.2.1:5000".parse().unwrap();
+        let b: SocketAddr = "192.0.2.2:5000".parse().unwrap();
+        let t0 = Instant::now();
+        let mut peers = PeerTracker::new("rist");
+        peers.seen_at(a, 188, t0);
+        peers.seen_at(b, 188, t0);
+        peers.seen_at(a, 188, t0 + Duration::from_millis(10));
+        assert_eq!(peers.peers.len(), 2);
+        assert_eq!(peers.peers[&a].packets, 2);
+        // b se tait, a continue: b expire au balayage suivant
+        peers.seen_at(a, 188, t0 + PEER_IDLE_TIMEOUT + Duration::from_millis(10));
+        assert_eq!(peers.peers.len(), 1);
+        assert!(peers.peers.contains_key(&a));
+        peers.close_all();
+        assert_eq!(peers.peers.len(), 0);
+    }
+
+    #[test]
+    fn bounds_the_number_of_tracked_sources() {
+        let mut peers = PeerTracker::new("rist");
+        for port in 0..(MAX_TRACKED_PEERS as u16 + 10) {
+            peers.seen(SocketAddr::from(([192, 0, 2, 1], 1000 + port)), 1);
+        }
+        assert_eq!(peers.peers.len(), MAX_TRACKED_PEERS);
+    }
+}
@@ -1,49 +1,655 @@
-use crate::structures::{TResult, TransportError, Metrics};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use crate::structures::{TResult, TransportError, Metrics, RelayProtocols, RelayStats, RttSide};
+use crate::relay::capture::CaptureTap;
+use crate::relay::command::{next_command, PauseMode, PipeCommand, PipeControl};
+use crate::relay::fanout::FanOutTx;
 use crate::relay::transport::{TransportMeta, TransportRx, TransportTx};
-use tokio::time::{sleep, Duration};
-use tracing::{info, error};
-use crate::common::logging::events;
+use tokio::time::{sleep, sleep_until, Duration, Instant};
+use tracing::{info, debug, warn, error, instrument};
+use crate::common::config::format_labels;
+use crate::common::logging::{events, LogThrottle};
+use crate::common::uri::{query_param, split_uris};
+use crate::relay::jitter::JitterEstimator;
+use crate::relay::keepalive::{keepalive_from_uri, KeepAlive};
+use crate::relay::overrun::{overrun_from_uri, OverrunChange, OverrunConfig, OverrunDetector};
+use crate::relay::ratelimit::{RateLimitConfig, RateLimiter};
+use crate::relay::recvbuf::{AdaptiveRecvBuffer, Resize};
+use crate::relay::rtp::RtpLossDetector;
+use crate::relay::ts::TsContinuityChecker;
+use crate::relay::watchdog::{watchdog_from_uri, WatchdogAction, WatchdogConfig};
 
-pub async fn run_pipe<Rx, Tx>(mut rx: Rx, mut tx: Tx, protocol: &'static str, relay_id: &str) -> TResult<()>
+// Rythme de la boucle d'un relais en pause sans lecture (PauseMode::Hold), comme le timeout de lecture
+const PAUSED_TICK: Duration = Duration::from_millis(20);
+
+// Nature of the payload carried by the input, used to enable payload-aware analysis
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PayloadKind {
+    #[default]
+    Raw,
+    Rtp,
+    Ts,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PipeOptions {
+    pub payload: PayloadKind,
+    // Durée maximale du pipe; run_relay la décompte sur toute la vie du relais, reconnexions comprises
+    pub max_runtime: Option<Duration>,
+    // Arrêt si aucune donnée n'est reçue pendant cette durée (distinct du timeout de lecture du transport)
+    pub idle_timeout: Option<Duration>,
+    // Timeouts de lecture consécutifs tolérés avant de considérer l'entrée perdue et de reconnecter
+    pub max_recv_timeouts: Option<u32>,
+    // Plafond d'émission propre au relais (lu sur l'URI de sortie)
+    pub rate_limit: RateLimitConfig,
+    // Datagramme de maintien envoyé quand rien n'est parti depuis l'intervalle (?keepalive_ms sur la sortie)
+    pub keepalive: Option<KeepAlive>,
+    // Plafond de débit entrant lissé déclenchant l'état overrun (?overrun_bitrate sur l'entrée)
+    pub overrun: Option<OverrunConfig>,
+    // Silence au-delà duquel le relais est jugé bloqué: reconnexion complète ou arrêt (?watchdog sur l'entrée)
+    pub watchdog: Option<WatchdogConfig>,
+}
+
+impl PipeOptions {
+    // Reads pipe options from the input URI (e.g. ?payload=rtp, ?max_runtime=7200, ?idle_timeout=30,
+    // ?max_recv_timeouts=200, ?overrun_bitrate=20000000&overrun_action=cap, ?watchdog=15&watchdog_action=stop)
+    // and the output URI (?max_bitrate=8000000, ?max_pps=1000, ?keepalive_ms=2000)
+    // Avec plusieurs sorties (liste séparée par des virgules), le plafond d'émission et le keep-alive
+    // viennent de la première
+    pub fn from_uris(input: &str, output: &str) -> TResult<Self> {
+        let output = split_uris(output)[0];
+        let payload = match query_param(input, "payload").map(|v| v.to_ascii_lowercase()).as_deref() {
+            Some("rtp") => PayloadKind::Rtp,
+            Some("ts") => PayloadKind::Ts,
+            _ => PayloadKind::Raw,
+        };
+        Ok(Self {
+            payload,
+            max_runtime: uint_param(input, "max_runtime")?.map(Duration::from_secs),
+            idle_timeout: uint_param(input, "idle_timeout")?.map(Duration::from_secs),
+            max_recv_timeouts: match uint_param(input, "max_recv_timeouts")? {
+                Some(0) => return Err(TransportError::InvalidUri("max_recv_timeouts must be at least 1".into())),
+                Some(n) => Some(u32::try_from(n).map_err(|_| TransportError::InvalidUri(format!("max_recv_timeouts={} is too large", n)))?),
+                None => None,
+            },
+            rate_limit: RateLimitConfig {
+                max_bitrate: uint_param(output, "max_bitrate")?,
+                max_pps: uint_param(output, "max_pps")?,
+            },
+            keepalive: keepalive_from_uri(output)?,
+            overrun: overrun_from_uri(input)?,
+            watchdog: watchdog_from_uri(input)?,
+        })
+    }
+}
+
+fn uint_param(uri: &str, key: &str) -> TResult<Option<u64>> {
+    let Some(value) = query_param(uri, key) else { return Ok(None) };
+    value.parse::<u64>().map(Some).map_err(|_| {
+        TransportError::InvalidUri(format!("{}={} is not a non-negative integer", key, value))
+    })
+}
+
+// Motif d'arrêt volontaire du pipe (par opposition à une erreur de transport)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    MaxRuntime,
+    IdleTimeout,
+    // PipeCommand::Stop (arrêt demandé via le RelayManager)
+    Control,
+    // Silence au-delà du seuil du watchdog avec watchdog_action=stop
+    Watchdog,
+}
+
+impl StopReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StopReason::MaxRuntime => "max_runtime",
+            StopReason::IdleTimeout => "idle_timeout",
+            StopReason::Control => "control",
+            StopReason::Watchdog => "watchdog",
+        }
+    }
+}
+
+// Se termine à l'échéance, ou jamais en l'absence d'échéance
+async fn deadline_reached(deadline: Option<Instant>) {
+    match deadline {
+        Some(at) => sleep_until(at).await,
+        None => std::future::pending().await,
+    }
+}
+
+// Échéance du watchdog comptée depuis le dernier datagramme reçu; jamais pour un relais en pause
+async fn watchdog_due(watchdog: Option<WatchdogConfig>, last_data: Instant, paused: bool) {
+    match watchdog {
+        Some(cfg) if !paused => sleep_until(last_data + cfg.silence).await,
+        _ => std::future::pending().await,
+    }
+}
+
+// Borne atteinte: le fichier part au demandeur quand le tap est relâché
+fn finish_capture(tap: CaptureTap, protocol: &str, relay_id: &str) {
+    info!(event = events::CAPTURE_FINISHED, subsystem = protocol, protocol = protocol, relay_id = %relay_id, packets = tap.packets(), bytes = tap.bytes() as u64, msg = "Capture of received datagrams finished");
+}
+
+// Enregistre le relais dans Metrics pour la durée du pipe; le Drop le retire quel que soit le chemin de sortie
+struct RelayRegistration {
+    stats: Option<Arc<RelayStats>>,
+}
+
+impl RelayRegistration {
+    fn new(relay_id: &str, protocols: RelayProtocols) -> Self {
+        let stats = Metrics::global().map(|m| {
+            m.inc_active_relays();
+            m.register_relay(relay_id, protocols)
+        });
+        Self { stats }
+    }
+}
+
+impl Drop for RelayRegistration {
+    fn drop(&mut self) {
+        if let (Some(m), Some(stats)) = (Metrics::global(), self.stats.as_ref()) {
+            m.unregister_relay(&stats.relay_id);
+            m.dec_active_relays();
+        }
+    }
+}
+
+// Les extrémités doivent être ouvertes par l'appelant (voir relay::open_endpoints), ce qui permet
+// de distinguer un échec d'ouverture d'une erreur en cours de relais.
+// Renvoie Ok(motif) sur un arrêt volontaire (max_runtime, idle_timeout, commande Stop), Err sur une erreur de transport.
+// `control` porte les commandes de reconfiguration à chaud (voir relay::command), traitées entre deux lectures.
+// Le span "relay" étiquette tous les logs émis pendant le pipe (y compris depuis les transports)
+#[instrument(name = "relay", skip_all, fields(relay_id = %relay_id, protocol = protocols.input, output_protocol = protocols.output, labels = tracing::field::Empty))]
+pub async fn run_pipe<Rx>(mut rx: Rx, mut tx: FanOutTx, protocols: RelayProtocols, relay_id: &str, opts: PipeOptions, mut control: Option<&mut PipeControl>) -> TResult<StopReason>
 where
     Rx: TransportRx + TransportMeta,
-    Tx: TransportTx + TransportMeta,
 {
-    rx.open()?;
-    tx.open()?;
-
-    if let Some(m) = Metrics::global() {
-        m.inc_active_relays();
+    let registration = RelayRegistration::new(relay_id, protocols);
+    if let Some(labels) = Metrics::global().and_then(|m| m.relay_labels(relay_id)) {
+        tracing::Span::current().record("labels", format_labels(&labels));
+    }
+    if let (Some(m), Some(stats)) = (Metrics::global(), registration.stats.as_ref()) {
+        stats.set_options(rx.effective_options(), tx.effective_options());
+        m.record_rate_limit(stats, opts.rate_limit);
+        m.record_overrun(stats, opts.overrun.map(|o| o.ceiling_bps), false);
     }
+    // Les logs du pipe sont rattachés au protocole d'entrée; output_protocol précise le sens du pont
+    let protocol = protocols.input;
+
+    info!(event = events::RELAY_START, subsystem = protocol, protocol = protocol, output_protocol = protocols.output, relay_id = %relay_id, input = %rx.describe(), output = %tx.describe(), msg = "Pipe start");
 
-    info!(event = events::RELAY_START, subsystem = protocol, protocol = protocol, relay_id = %relay_id, input = %rx.describe(), output = %tx.describe(), msg = "Pipe start");
+    let mut rtp = (opts.payload == PayloadKind::Rtp).then(RtpLossDetector::new);
+    let mut ts = (opts.payload == PayloadKind::Ts).then(TsContinuityChecker::new);
 
-    let mut buf = vec![0u8; 64 * 1024];
+    let mut truncation_log = LogThrottle::new(Duration::from_secs(10));
+
+    let mut jitter = JitterEstimator::new();
+    let mut rate_limit = opts.rate_limit;
+    let mut limiter = (!rate_limit.is_unlimited()).then(|| RateLimiter::new(rate_limit, Instant::now().into_std()));
+    let mut overrun = opts.overrun.map(OverrunDetector::new);
+    // Ancienne sortie après un basculement, alimentée en parallèle jusqu'à l'échéance
+    let mut retiring: Option<(FanOutTx, Instant)> = None;
+    // Dernier passage du sampler partagé (Metrics::spawn_sampler) pris en compte par ce pipe
+    let mut sample_epoch = 0;
+    let deadline = opts.max_runtime.map(|d| Instant::now() + d);
+    // Le délai d'inactivité court depuis l'ouverture, puis depuis le dernier datagramme reçu
+    let mut last_data = Instant::now();
+    // Dernier envoi sur la sortie (données ou keep-alive), pour ?keepalive_ms
+    let mut last_sent = Instant::now();
+    // Remis à zéro par toute lecture réussie
+    let mut consecutive_timeouts: u32 = 0;
+    // Capture en cours (GET /relays/<id>/capture): la relâcher renvoie le fichier au demandeur
+    let mut capture: Option<CaptureTap> = None;
+    // Pause demandée (POST /relays/<id>/pause), conservée d'un pipe à l'autre par PipeControl
+    let mut paused = control.as_deref().and_then(PipeControl::pause_mode);
+
+    let mut buf = AdaptiveRecvBuffer::new(Instant::now().into_std());
+    if let (Some(m), Some(stats)) = (Metrics::global(), registration.stats.as_ref()) {
+        m.record_recv_buffer_len(stats, buf.len());
+    }
     loop {
-        match rx.recv(&mut buf).await {
+        if let Some(stats) = registration.stats.as_ref() {
+            stats.timings.iterations.fetch_add(1, Ordering::Relaxed);
+            stats.beat();
+        }
+        if let (Some(m), Some(stats)) = (Metrics::global(), registration.stats.as_ref())
+            && m.sample_epoch() != sample_epoch
+        {
+            sample_epoch = m.sample_epoch();
+            m.record_buffer_occupancy(stats, rx.buffer_occupancy(), tx.buffer_occupancy());
+            if let Some(rtt) = rx.take_rtt_sample() {
+                m.record_rtt(stats, RttSide::Input, rtt);
+            }
+            if let Some(rtt) = tx.take_rtt_sample() {
+                m.record_rtt(stats, RttSide::Output, rtt);
+            }
+            // Débit lissé tout juste avancé par le sampler: l'alarme est journalisée une fois par dépassement
+            if let Some(detector) = overrun.as_mut()
+                && let Some(change) = detector.observe(stats.smoothed_bps())
+            {
+                let bps = stats.smoothed_bps() as u64;
+                let ceiling_bps = detector.cfg.ceiling_bps;
+                let action = detector.cfg.action.as_str();
+                match change {
+                    OverrunChange::Raised => warn!(event = events::RELAY_OVERRUN, subsystem = protocol, protocol = protocol, relay_id = %relay_id, bitrate_bps = bps, ceiling_bps = ceiling_bps, action = action, msg = "Input bitrate above the overrun ceiling"),
+                    OverrunChange::Cleared => info!(event = events::RELAY_OVERRUN_CLEARED, subsystem = protocol, protocol = protocol, relay_id = %relay_id, bitrate_bps = bps, ceiling_bps = ceiling_bps, msg = "Input bitrate back under the overrun ceiling"),
+                }
+                m.record_overrun(stats, Some(ceiling_bps), detector.active());
+                let effective = detector.cap(rate_limit);
+                limiter = (!effective.is_unlimited()).then(|| RateLimiter::new(effective, Instant::now().into_std()));
+            }
+        }
+        if let Some(tap) = capture.take_if(|tap| tap.expired(Instant::now().into_std())) {
+            finish_capture(tap, protocol, relay_id);
+        }
+        if let Some((mut old, _)) = retiring.take_if(|(_, until)| Instant::now() >= *until) {
+            info!(event = events::OUTPUT_RETIRED, subsystem = protocol, protocol = protocol, relay_id = %relay_id, output = %old.describe(), msg = "Previous output closed after the switch overlap");
+            old.close();
+        }
+        // Rien de transmis depuis l'intervalle (entrée silencieuse, relais en pause): datagramme de maintien
+        if let Some(keepalive) = opts.keepalive.as_ref()
+            && keepalive.due(last_sent.into_std(), Instant::now().into_std())
+        {
+            if let Err(e) = tx.send(&keepalive.payload).await {
+                if let Some(m) = Metrics::global() {
+                    m.count_send_failure(protocols.output, keepalive.payload.len());
+                }
+                error!(event = events::RELAY_ERROR, subsystem = protocol, protocol = protocol, relay_id = %relay_id, error = %e, msg = "Keep-alive send failed");
+                rx.close();
+                tx.close();
+                break Err(e);
+            }
+            last_sent = Instant::now();
+            if let Some(m) = Metrics::global() {
+                m.keepalives_sent_total.inc();
+            }
+            if let Some(stats) = registration.stats.as_ref() {
+                stats.mark_keepalive();
+            }
+        }
+        let recv_started = Instant::now();
+        let received = tokio::select! {
+            r = rx.recv(buf.as_mut_slice()), if paused != Some(PauseMode::Hold) => r,
+            // En pause sans lecture, la boucle tourne au rythme du timeout de lecture pour les commandes
+            _ = sleep(PAUSED_TICK), if paused == Some(PauseMode::Hold) => Ok(0),
+            Some(cmd) = next_command(&mut control) => {
+                match cmd {
+                    PipeCommand::Stop => {
+                        info!(event = events::RELAY_STOP, subsystem = protocol, protocol = protocol, relay_id = %relay_id, reason = StopReason::Control.as_str(), msg = "Relay stopped on request");
+                        rx.close();
+                        tx.close();
+                        if let Some((mut old, _)) = retiring.take() {
+                            old.close();
+                        }
+                        break Ok(StopReason::Control);
+                    }
+                    PipeCommand::SetRateLimit { max_bitrate, reply } => {
+                        rate_limit.max_bitrate = max_bitrate;
+                        // Un dépassement en cours en mode cap continue d'abaisser le nouveau plafond
+                        let effective = overrun.as_ref().map_or(rate_limit, |d| d.cap(rate_limit));
+                        limiter = (!effective.is_unlimited()).then(|| RateLimiter::new(effective, Instant::now().into_std()));
+                        if let (Some(m), Some(stats)) = (Metrics::global(), registration.stats.as_ref()) {
+                            m.record_rate_limit(stats, rate_limit);
+                        }
+                        if let Some(c) = control.as_deref_mut() {
+                            c.rate_limit = Some(rate_limit);
+                        }
+                        info!(event = events::RELAY_RECONFIGURED, subsystem = protocol, protocol = protocol, relay_id = %relay_id, max_bitrate = ?max_bitrate, msg = "Egress rate limit changed");
+                        let _ = reply.send(Ok(()));
+                    }
+                    PipeCommand::AddOutput { output, tx: target, reply } => {
+                        info!(event = events::RELAY_RECONFIGURED, subsystem = protocol, protocol = protocol, relay_id = %relay_id, output = %target.describe(), msg = "Output added");
+                        tx.push(target);
+                        if let Some(c) = control.as_deref() {
+                            c.push_output(&output);
+                        }
+                        let _ = reply.send(Ok(()));
+                    }
+                    PipeCommand::RemoveOutput { output, reply } => {
+                        let removed = match control.as_deref() {
+                            Some(c) => c.remove_output(&output),
+                            None => Err(TransportError::Closed),
+                        };
+                        let _ = reply.send(removed.map(|index| {
+                            let mut target = tx.remove(index);
+                            info!(event = events::RELAY_RECONFIGURED, subsystem = protocol, protocol = protocol, relay_id = %relay_id, output = %target.describe(), msg = "Output removed");
+                            target.close();
+                        }));
+                    }
+                    PipeCommand::SwitchOutput { output, tx: next, overlap, reply } => {
+                        let previous = std::mem::replace(&mut tx, next);
+                        info!(event = events::OUTPUT_SWITCHED, subsystem = protocol, protocol = protocol, relay_id = %relay_id, output = %tx.describe(), overlap_ms = overlap.as_millis() as u64, msg = "Output switched, previous output kept for the overlap");
+                        if let Some((mut older, _)) = retiring.replace((previous, Instant::now() + overlap)) {
+                            older.close();
+                        }
+                        if let Some(c) = control.as_deref() {
+                            c.set_output(output);
+                        }
+                        let _ = reply.send(Ok(()));
+                    }
+                    PipeCommand::Capture { tap } if capture.is_some() => {
+                        tap.reject(TransportError::Other("a capture is already running on this relay".into()));
+                    }
+                    PipeCommand::Capture { tap } => {
+                        info!(event = events::CAPTURE_STARTED, subsystem = protocol, protocol = protocol, relay_id = %relay_id, msg = "Capture of received datagrams started");
+                        capture = Some(tap);
+                    }
+                    PipeCommand::Pause { mode, reply } => {
+                        info!(event = events::RELAY_PAUSED, subsystem = protocol, protocol = protocol, relay_id = %relay_id, drain = mode == PauseMode::Drain, msg = "Relay paused, sockets kept open");
+                        paused = Some(mode);
+                        if let Some(c) = control.as_deref() {
+                            c.set_pause_mode(paused);
+                        }
+                        let _ = reply.send(Ok(()));
+                    }
+                    PipeCommand::Resume { reply } => {
+                        if paused.take().is_some() {
+                            info!(event = events::RELAY_RESUMED, subsystem = protocol, protocol = protocol, relay_id = %relay_id, msg = "Relay resumed");
+                            // Le silence de la pause ne compte ni pour idle_timeout ni pour max_recv_timeouts
+                            last_data = Instant::now();
+                            consecutive_timeouts = 0;
+                        }
+                        if let Some(c) = control.as_deref() {
+                            c.set_pause_mode(None);
+                        }
+                        let _ = reply.send(Ok(()));
+                    }
+                }
+                if let Some(stats) = registration.stats.as_ref() {
+                    stats.set_options(rx.effective_options(), tx.effective_options());
+                }
+                continue;
+            }
+            _ = deadline_reached(deadline) => {
+                info!(event = events::RELAY_STOP, subsystem = protocol, protocol = protocol, relay_id = %relay_id, reason = StopReason::MaxRuntime.as_str(), msg = "Relay reached its maximum runtime");
+                rx.close();
+                tx.close();
+                break Ok(StopReason::MaxRuntime);
+            }
+            // Bras distinct de la lecture: un recv() qui ne rend jamais la main est rattrapé aussi
+            _ = watchdog_due(opts.watchdog, last_data, paused.is_some()) => {
+                let Some(cfg) = opts.watchdog else { continue };
+                let silent_ms = last_data.elapsed().as_millis() as u64;
+                warn!(event = events::RELAY_WATCHDOG, subsystem = protocol, protocol = protocol, relay_id = %relay_id, silent_ms = silent_ms, action = cfg.action.as_str(), msg = "No data received within the watchdog threshold; input considered wedged");
+                rx.close();
+                tx.close();
+                if let Some((mut old, _)) = retiring.take() {
+                    old.close();
+                }
+                match cfg.action {
+                    WatchdogAction::Stop => {
+                        info!(event = events::RELAY_STOP, subsystem = protocol, protocol = protocol, relay_id = %relay_id, reason = StopReason::Watchdog.as_str(), msg = "Relay stopped by the watchdog");
+                        break Ok(StopReason::Watchdog);
+                    }
+                    // Err plutôt qu'un arrêt: run_relay rouvre les extrémités et trace le motif de la reconnexion
+                    WatchdogAction::Reconnect => break Err(TransportError::Stalled { silent_ms }),
+                }
+            }
+        };
+        if let Some(stats) = registration.stats.as_ref() {
+            stats.timings.add(&stats.timings.recv_ns, recv_started.elapsed());
+        }
+        if received.is_ok() {
+            consecutive_timeouts = 0;
+        }
+        match received {
+            Ok(n) if buf.is_full(n) => {
+                if let Some(m) = Metrics::global() {
+                    m.datagrams_truncated_total.inc();
+                    m.inc_pkt_drop();
+                }
+                if let Some(stats) = registration.stats.as_ref() {
+                    stats.add_dropped(1);
+                }
+                // Le datagramme tronqué est perdu; les suivants de même taille passeront dans le buffer agrandi
+                if let Some(Resize::Grown(len)) = buf.observe(n, Instant::now().into_std()) {
+                    info!(event = events::RECV_BUFFER_RESIZED, subsystem = protocol, protocol = protocol, relay_id = %relay_id, len = len, msg = "Datagram larger than the receive buffer; buffer grown, datagram dropped");
+                    if let (Some(m), Some(stats)) = (Metrics::global(), registration.stats.as_ref()) {
+                        m.record_recv_buffer_len(stats, len);
+                    }
+                } else if let Some(suppressed) = truncation_log.allow() {
+                    warn!(event = events::DATAGRAM_TRUNCATED, subsystem = protocol, protocol = protocol, relay_id = %relay_id, len = n, suppressed = suppressed, msg = "Datagram larger than the receive buffer was truncated; dropped");
+                }
+            }
             Ok(n) if n > 0 => {
+                if let Some(Resize::Shrunk(len)) = buf.observe(n, Instant::now().into_std()) {
+                    debug!(event = events::RECV_BUFFER_RESIZED, subsystem = protocol, protocol = protocol, relay_id = %relay_id, len = len, msg = "Receive buffer shrunk to the datagram sizes seen");
+                    if let (Some(m), Some(stats)) = (Metrics::global(), registration.stats.as_ref()) {
+                        m.record_recv_buffer_len(stats, len);
+                    }
+                }
+                let buf = buf.data(n);
+                if let Some(tap) = capture.take_if(|tap| tap.record(buf)) {
+                    finish_capture(tap, protocol, relay_id);
+                }
+                last_data = Instant::now();
+                let jitter_ms = jitter.observe(last_data.into_std());
+                if let Some(stats) = registration.stats.as_ref() {
+                    stats.timings.packets.fetch_add(1, Ordering::Relaxed);
+                    stats.set_jitter_ms(jitter_ms);
+                    stats.mark_recv(n);
+                    stats.bytes_in.inc_by(n as u64);
+                }
                 if let Some(m) = Metrics::global() {
                     m.inc_pkt_in();
                     m.add_bytes_in(n as u64);
                 }
-                let sent = tx.send(&buf[..n]).await?;
-                if let Some(m) = Metrics::global() {
-                    m.inc_pkt_out();
-                    m.add_bytes_out(sent as u64);
+                if let Some(outcome) = rtp.as_mut().and_then(|d| d.observe(buf)) {
+                    if outcome.lost > 0 {
+                        debug!(event = events::RELAY_LOSS, subsystem = protocol, protocol = protocol, relay_id = %relay_id, lost = outcome.lost, msg = "RTP sequence gap");
+                    }
+                    if let Some(stats) = registration.stats.as_ref() {
+                        stats.add_lost(outcome.lost);
+                    }
+                    if let Some(m) = Metrics::global() {
+                        m.add_pkt_loss(outcome.lost);
+                        if outcome.reordered { m.inc_pkt_reordered(); }
+                    }
+                }
+                if let Some(outcome) = ts.as_mut().map(|c| c.observe(buf))
+                    && (outcome.cc_errors > 0 || outcome.sync_errors > 0)
+                {
+                    debug!(event = events::RELAY_LOSS, subsystem = protocol, protocol = protocol, relay_id = %relay_id, cc_errors = outcome.cc_errors, sync_errors = outcome.sync_errors, lost = outcome.lost, msg = "MPEG-TS discontinuity");
+                    if let Some(stats) = registration.stats.as_ref() {
+                        stats.add_lost(outcome.lost);
+                    }
+                    if let Some(m) = Metrics::global() {
+                        m.add_pkt_loss(outcome.lost);
+                        m.ts_cc_errors_total.inc_by(outcome.cc_errors);
+                        m.ts_sync_errors_total.inc_by(outcome.sync_errors);
+                    }
+                }
+                // En pause (drain), le datagramme est reçu et analysé mais pas transmis
+                if paused.is_some() {
+                    continue;
+                }
+                if let Some(wait) = limiter.as_mut().map(|l| l.delay(n, Instant::now().into_std()))
+                    && !wait.is_zero()
+                {
+                    if let Some(stats) = registration.stats.as_ref() {
+                        stats.beat_through(wait);
+                    }
+                    sleep(wait).await;
+                    if let (Some(m), Some(stats)) = (Metrics::global(), registration.stats.as_ref()) {
+                        m.record_throttled(stats, wait);
+                    }
+                }
+                let send_started = Instant::now();
+                let sent = match tx.send(buf).await {
+                    Ok(sent) => sent,
+                    Err(e) => {
+                        if let Some(m) = Metrics::global() {
+                            m.record_send_failure(registration.stats.as_deref(), protocols.output, n);
+                        }
+                        error!(event = events::RELAY_ERROR, subsystem = protocol, protocol = protocol, relay_id = %relay_id, error = %e, msg = "Send failed");
+                        rx.close();
+                        tx.close();
+                        break Err(e);
+                    }
+                };
+                if let Some(stats) = registration.stats.as_ref() {
+                    stats.timings.add(&stats.timings.send_ns, send_started.elapsed());
+                }
+                // La copie vers l'ancienne sortie est au mieux: son échec n'arrête pas le relais
+                if let Some((old, _)) = retiring.as_mut()
+                    && let Err(e) = old.send(buf).await
+                {
+                    debug!(event = events::RELAY_ERROR, subsystem = protocol, protocol = protocol, relay_id = %relay_id, error = %e, msg = "Send to the previous output failed during the switch overlap");
+                }
+                // Un datagramme reçu compte une fois, en sortie ou en drop: 0 octet envoyé = écarté par les
+                // filtres de sortie (drop_pct, mtu, file pleine) de toutes les cibles du fan-out; une cible
+                // seule qui l'écarte n'apparaît que dans le compteur de son filtre
+                if sent == 0 {
+                    if let Some(m) = Metrics::global() {
+                        m.inc_pkt_drop();
+                    }
+                    if let Some(stats) = registration.stats.as_ref() {
+                        stats.add_dropped(1);
+                    }
+                } else {
+                    if let Some(m) = Metrics::global() {
+                        m.inc_pkt_out();
+                        m.add_bytes_out(sent as u64);
+                    }
+                    if let Some(stats) = registration.stats.as_ref() {
+                        stats.mark_sent(sent);
+                    }
                 }
+                last_sent = Instant::now();
             }
             Ok(_) => {
                 // n == 0, ignore
             }
-            Err(TransportError::Timeout) => {
+            Err(TransportError::Timeout { during: None }) => {
                 if let Some(m) = Metrics::global() { m.inc_timeout(); }
+                if let Some(stats) = registration.stats.as_ref() {
+                    stats.timings.timeouts.fetch_add(1, Ordering::Relaxed);
+                }
+                if let Some(idle) = opts.idle_timeout
+                    && paused.is_none()
+                    && last_data.elapsed() >= idle
+                {
+                    info!(event = events::RELAY_STOP, subsystem = protocol, protocol = protocol, relay_id = %relay_id, reason = StopReason::IdleTimeout.as_str(), idle_secs = idle.as_secs(), msg = "No data received within the idle timeout");
+                    rx.close();
+                    tx.close();
+                    break Ok(StopReason::IdleTimeout);
+                }
+                if paused.is_none() {
+                    consecutive_timeouts = consecutive_timeouts.saturating_add(1);
+                }
+                if let Some(max) = opts.max_recv_timeouts
+                    && consecutive_timeouts >= max
+                {
+                    // Err plutôt qu'un arrêt: run_relay passe en reconnexion et rouvre les extrémités
+                    warn!(event = events::RELAY_ERROR, subsystem = protocol, protocol = protocol, relay_id = %relay_id, consecutive_timeouts = consecutive_timeouts, msg = "Too many consecutive receive timeouts; treating the input as down");
+                    rx.close();
+                    tx.close();
+                    break Err(TransportError::Timeout { during: None });
+                }
                 sleep(Duration::from_millis(5)).await;
             }
             Err(e) => {
                 error!(event = events::RELAY_ERROR, subsystem = protocol, protocol = protocol, relay_id = %relay_id, error = %e, msg = "Pipe error");
-                if let Some(m) = Metrics::global() { m.dec_active_relays(); }
+                rx.close();
+                tx.close();
                 break Err(e);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use async_trait::async_trait;
+
+    // Sortie dont chaque envoi échoue; `closed` indique si le pipe l'a refermée
+    struct FailingTx {
+        closed: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl TransportTx for FailingTx {
+        async fn send(&mut self, _buf: &[u8]) -> TResult<usize> {
+            Err(TransportError::Closed)
+        }
+    }
+
+    impl TransportMeta for FailingTx {
+        fn open(&mut self) -> TResult<()> {
+            Ok(())
+        }
+        fn close(&mut self) {
+            self.closed.store(true, Ordering::SeqCst);
+        }
+        fn describe(&self) -> String {
+            "failing".into()
+        }
+    }
+
+    #[test]
+    fn options_from_uris() {
+        let out = "srt://127.0.0.1:10000";
+        let opts = PipeOptions::from_uris("srt://@:9000?mode=listener&payload=ts&max_runtime=7200", out).unwrap();
+        assert_eq!(opts.payload, PayloadKind::Ts);
+        assert_eq!(opts.max_runtime, Some(Duration::from_secs(7200)));
+        assert_eq!(opts.idle_timeout, None);
+        assert_eq!(PipeOptions::from_uris("rist://@:1?idle_timeout=30", out).unwrap().idle_timeout, Some(Duration::from_secs(30)));
+        assert!(PipeOptions::from_uris("srt://@:9000?max_runtime=2h", out).is_err());
+        assert_eq!(PipeOptions::from_uris("srt://@:9000?max_recv_timeouts=200", out).unwrap().max_recv_timeouts, Some(200));
+        assert!(PipeOptions::from_uris("srt://@:9000?max_recv_timeouts=0", out).is_err());
+        let limited = PipeOptions::from_uris("srt://@:9000", "srt://h:1?max_bitrate=8000000&max_pps=1000").unwrap();
+        assert_eq!(limited.rate_limit, RateLimitConfig { max_bitrate: Some(8_000_000), max_pps: Some(1000) });
+        assert!(limited.keepalive.is_none());
+        let keepalive = PipeOptions::from_uris("srt://@:9000", "srt://h:1?keepalive_ms=2000,srt://h:2").unwrap().keepalive;
+        assert_eq!(keepalive.map(|k| k.interval), Some(Duration::from_secs(2)));
+        let watchdog = PipeOptions::from_uris("srt://@:9000?watchdog=15", out).unwrap().watchdog;
+        assert_eq!(watchdog.map(|w| (w.silence, w.action)), Some((Duration::from_secs(15), WatchdogAction::Reconnect)));
+    }
+
+    #[tokio::test]
+    async fn watchdog_reconnects_or_stops_a_silent_relay() {
+        let registry = crate::relay::registry::TransportRegistry::global();
+        let sink = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let output = format!("rist://127.0.0.1:{}", sink.local_addr().unwrap().port());
+        let protocols = RelayProtocols { input: "rist", output: "rist" };
+        for (action, stalled) in [(WatchdogAction::Reconnect, true), (WatchdogAction::Stop, false)] {
+            let (rx, tx) = crate::relay::open_endpoints(registry, "rist://@:0?mode=listener", &output, &Default::default()).await.unwrap();
+            let opts = PipeOptions { watchdog: Some(WatchdogConfig { silence: Duration::from_millis(100), action }), ..PipeOptions::default() };
+            let result = tokio::time::timeout(Duration::from_secs(5), run_pipe(rx, tx, protocols, "watchdog-test", opts, None)).await.unwrap();
+            if stalled {
+                assert!(matches!(result, Err(TransportError::Stalled { silent_ms }) if silent_ms >= 100), "{:?}", result);
+            } else {
+                assert_eq!(result.unwrap(), StopReason::Watchdog);
+            }
+        }
+    }
+
+    // Échec sur un datagramme transmis, puis sur un keep-alive d'une entrée silencieuse
+    #[tokio::test]
+    async fn a_send_failure_closes_both_ends() {
+        let registry = crate::relay::registry::TransportRegistry::global();
+        let sink = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let output = format!("rist://127.0.0.1:{}", sink.local_addr().unwrap().port());
+        let protocols = RelayProtocols { input: "rist", output: "rist" };
+        for keepalive in [false, true] {
+            let (rx, _) = crate::relay::open_endpoints(registry, "rist://@:0?mode=listener", &output, &Default::default()).await.unwrap();
+            let port = rx.effective_options().local_port.unwrap();
+            let closed = Arc::new(AtomicBool::new(false));
+            let tx = FanOutTx::new(vec![Box::new(FailingTx { closed: closed.clone() })]);
+            let keepalive = keepalive.then(|| KeepAlive { interval: Duration::from_millis(50), payload: vec![0x47; 188] });
+            let opts = PipeOptions { keepalive: keepalive.clone(), ..PipeOptions::default() };
+            let pipe = tokio::spawn(run_pipe(rx, tx, protocols, "send-failure-test", opts, None));
+            if keepalive.is_none() {
+                sink.send_to(&[0x47; 188], ("127.0.0.1", port)).unwrap();
+            }
+            let result = tokio::time::timeout(Duration::from_secs(5), pipe).await.unwrap().unwrap();
+            assert!(matches!(result, Err(TransportError::Closed)), "{:?}", result);
+            assert!(closed.load(Ordering::SeqCst));
+        }
+    }
+}
@@ -0,0 +1,87 @@
+// Test de connectivité d'une sortie sans lancer de relais (POST /probe-output): l'émetteur est construit
+// par le registre et ouvert comme pour un relais, vérifié (TransportMeta::check_connectivity, sonde
+// d'accessibilité pour SRT), puis fermé. Une URI invalide est une erreur; une cible injoignable est
+// un résultat (reachable=false) pour que l'interface puisse l'afficher.
+
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use tracing::info;
+
+use crate::common::logging::events;
+use crate::common::uri::{redact_uri_secrets, split_uris};
+use crate::relay::blocking;
+use crate::relay::registry::{TransportParams, TransportRegistry};
+use crate::relay::transport::TransportMeta;
+use crate::structures::{TResult, TransportError};
+
+pub const DEFAULT_PROBE_TIMEOUT_MS: u64 = 2000;
+pub const MAX_PROBE_TIMEOUT_MS: u64 = 10_000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputProbeResult {
+    // URI masquée (psk, passphrase)
+    pub output: String,
+    pub protocol: &'static str,
+    pub reachable: bool,
+    // Ouverture et vérification (handshake compris pour une liaison qui en fait un)
+    pub connect_ms: f64,
+    // RTT rapporté par le transport; null s'il ne le mesure pas (stub UDP)
+    pub rtt_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Une seule URI de sortie; `timeout` borne l'ouverture et la vérification ensemble
+pub async fn probe_output(output: &str, params: TransportParams, timeout: Duration) -> TResult<OutputProbeResult> {
+    let uris = split_uris(output);
+    if uris.len() != 1 {
+        return Err(TransportError::InvalidUri("probe-output takes a single output URI".into()));
+    }
+    let uri = uris[0].trim();
+    let registry = TransportRegistry::global();
+    let protocol = registry.resolve(uri)?;
+    let started = Instant::now();
+    // Construction (résolution DNS), open() et la sonde bloquent: hors des workers Tokio
+    let target = uri.to_string();
+    let mut tx = blocking::run(move || registry.build_tx(&target, &params)).await?;
+    let checked = tokio::task::spawn_blocking(move || {
+        let result = tx.open().and_then(|()| tx.check_connectivity(timeout));
+        let rtt = tx.take_rtt_sample();
+        tx.close();
+        result.map(|()| rtt)
+    });
+    let outcome = match tokio::time::timeout(timeout, checked).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(TransportError::Other(e.to_string())),
+        Err(_) => Err(TransportError::Timeout { during: Some(format!("while connecting to {} after {} ms", redact_uri_secrets(uri), timeout.as_millis())) }),
+    };
+    let result = OutputProbeResult {
+        output: redact_uri_secrets(uri),
+        protocol,
+        reachable: outcome.is_ok(),
+        connect_ms: started.elapsed().as_secs_f64() * 1000.0,
+        rtt_ms: outcome.as_ref().ok().copied().flatten().map(|rtt| rtt.as_secs_f64() * 1000.0),
+        error: outcome.err().map(|e| e.to_string()),
+    };
+    info!(event = events::OUTPUT_PROBED, subsystem = protocol, protocol = protocol, output = %result.output, reachable = result.reachable, connect_ms = result.connect_ms, error = result.error.as_deref().unwrap_or(""), msg = "Output connectivity probed");
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn probes_a_local_output_and_rejects_bad_uris() {
+        let sink = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let output = format!("rist://127.0.0.1:{}", sink.local_addr().unwrap().port());
+        let result = probe_output(&output, TransportParams::default(), Duration::from_secs(1)).await.unwrap();
+        assert!(result.reachable, "{:?}", result.error);
+        assert_eq!(result.protocol, "rist");
+        assert_eq!(result.rtt_ms, None);
+        assert!(matches!(probe_output("udp://127.0.0.1:1", TransportParams::default(), Duration::from_secs(1)).await, Err(TransportError::UnsupportedScheme(_))));
+        assert!(probe_output("rist://127.0.0.1:1,rist://127.0.0.1:2", TransportParams::default(), Duration::from_secs(1)).await.is_err());
+        // Une URI de listener n'est pas une sortie
+        assert!(probe_output("srt://@:9000", TransportParams::default(), Duration::from_secs(1)).await.is_err());
+    }
+}
@@ -0,0 +1,111 @@
+// Per-relay egress limiting: token buckets on bits per second and/or packets per second.
+// The bucket may go into debt; the caller sleeps for the returned delay before sending.
+
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+// Share of one second of traffic allowed as a burst above the configured rate
+const BURST_WINDOW_SECS: f64 = 0.1;
+// A burst must at least fit one full-size datagram, otherwise low rates could never send one
+const MIN_BURST_BYTES: f64 = 64.0 * 1024.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RateLimitConfig {
+    pub max_bitrate: Option<u64>,
+    pub max_pps: Option<u64>,
+}
+
+impl RateLimitConfig {
+    pub fn is_unlimited(&self) -> bool {
+        self.max_bitrate.is_none() && self.max_pps.is_none()
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    // Starts full so that a relay does not stall on its first burst
+    fn new(rate: f64, capacity: f64, now: Instant) -> Self {
+        Self { rate, capacity, tokens: capacity, last: now }
+    }
+
+    fn take(&mut self, amount: f64, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity) - amount;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes: Option<TokenBucket>,
+    packets: Option<TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn new(cfg: RateLimitConfig, now: Instant) -> Self {
+        let bytes = cfg.max_bitrate.filter(|&b| b > 0).map(|bps| {
+            let rate = bps as f64 / 8.0;
+            TokenBucket::new(rate, (rate * BURST_WINDOW_SECS).max(MIN_BURST_BYTES), now)
+        });
+        let packets = cfg.max_pps.filter(|&p| p > 0).map(|pps| {
+            let rate = pps as f64;
+            TokenBucket::new(rate, (rate * BURST_WINDOW_SECS).max(1.0), now)
+        });
+        Self { bytes, packets }
+    }
+
+    // Debits one datagram of `len` bytes and returns how long to wait before sending it
+    pub fn delay(&mut self, len: usize, now: Instant) -> Duration {
+        let by_bytes = self.bytes.as_mut().map_or(Duration::ZERO, |b| b.take(len as f64, now));
+        let by_packets = self.packets.as_mut().map_or(Duration::ZERO, |b| b.take(1.0, now));
+        by_bytes.max(by_packets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlimited_never_waits() {
+        let now = Instant::now();
+        let mut l = RateLimiter::new(RateLimitConfig::default(), now);
+        assert!(RateLimitConfig::default().is_unlimited());
+        assert_eq!(l.delay(1316, now), Duration::ZERO);
+    }
+
+    #[test]
+    fn packet_rate_is_enforced_after_burst() {
+        let now = Instant::now();
+        let mut l = RateLimiter::new(RateLimitConfig { max_bitrate: None, max_pps: Some(100) }, now);
+        // Burst of 10 packets (100 ms worth), then each extra packet costs 10 ms
+        for _ in 0..10 {
+            assert_eq!(l.delay(1316, now), Duration::ZERO);
+        }
+        let wait = l.delay(1316, now);
+        assert!((wait.as_secs_f64() - 0.01).abs() < 1e-6);
+    }
+
+    #[test]
+    fn bitrate_refills_over_time() {
+        let t0 = Instant::now();
+        // 8 Mbit/s = 1 MB/s, burst 100 KB
+        let mut l = RateLimiter::new(RateLimitConfig { max_bitrate: Some(8_000_000), max_pps: None }, t0);
+        assert_eq!(l.delay(100_000, t0), Duration::ZERO);
+        let wait = l.delay(50_000, t0);
+        assert!((wait.as_secs_f64() - 0.05).abs() < 1e-6);
+        assert_eq!(l.delay(0, t0 + Duration::from_millis(60)), Duration::ZERO);
+    }
+}
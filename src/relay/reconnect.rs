@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+// Politique de reconnexion d'un relais: backoff exponentiel plafonné, nombre d'essais optionnellement borné
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // None = réessayer indéfiniment
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    // Délai avant l'essai `attempt` (1-based): initial * 2^(attempt-1), plafonné à max_backoff
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+
+    pub fn exhausted(&self, attempt: u32) -> bool {
+        self.max_attempts.is_some_and(|max| attempt > max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_and_caps() {
+        let p = ReconnectPolicy::default();
+        assert_eq!(p.backoff(1), Duration::from_millis(500));
+        assert_eq!(p.backoff(3), Duration::from_secs(2));
+        assert_eq!(p.backoff(40), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn exhausted_only_when_bounded() {
+        let mut p = ReconnectPolicy::default();
+        assert!(!p.exhausted(1000));
+        p.max_attempts = Some(3);
+        assert!(!p.exhausted(3));
+        assert!(p.exhausted(4));
+    }
+}
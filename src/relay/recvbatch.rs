@@ -0,0 +1,224 @@
+// Batched reads on an input (?recv_batch=N): on Linux one recvmmsg(2) call pulls up to N queued
+// datagrams, which recv() then hands to the pipe one at a time, in arrival order. The pipe still sees
+// one datagram per recv(), so its counters, loss analysis and truncation check are unchanged; only the
+// number of syscalls drops at high packet rates. Elsewhere the batch is filled by a single recv_from,
+// which is the plain per-datagram path. A datagram the kernel truncated to its slot (MSG_TRUNC) is
+// handed out as a full read, exactly like a truncated plain recv, so the pipe drops and counts it even
+// when its buffer has grown since the slot was filled.
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+
+use tokio::io::Interest;
+use tokio::net::UdpSocket;
+
+use crate::common::uri::query_param;
+use crate::structures::{TResult, TransportError};
+
+pub const MAX_RECV_BATCH: usize = 64;
+
+pub struct RecvBatch {
+    size: usize,
+    // One buffer per datagram of the batch, sized like the pipe's receive buffer at the last fill
+    slots: Vec<Vec<u8>>,
+    // Datagrams read but not yet handed out, in arrival order
+    ready: VecDeque<Received>,
+    // En-têtes recvmmsg réutilisés d'un appel à l'autre
+    #[cfg(target_os = "linux")]
+    headers: MmsgHeaders,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Received {
+    slot: usize,
+    len: usize,
+    peer: SocketAddr,
+    // Le datagramme dépassait son slot: seul le début a été lu
+    truncated: bool,
+}
+
+impl RecvBatch {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            slots: Vec::new(),
+            ready: VecDeque::with_capacity(size),
+            #[cfg(target_os = "linux")]
+            headers: MmsgHeaders::new(size),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // Datagramme suivant du lot courant, copié dans buf. Tronqué, il est rendu comme une lecture qui
+    // remplit buf: le pipe le compte en datagrams_truncated_total au lieu de relayer un contenu coupé.
+    fn pop(&mut self, buf: &mut [u8]) -> Option<(usize, SocketAddr)> {
+        let Received { slot, len, peer, truncated } = self.ready.pop_front()?;
+        if truncated {
+            return Some((buf.len(), peer));
+        }
+        let n = len.min(buf.len());
+        buf[..n].copy_from_slice(&self.slots[slot][..n]);
+        Some((n, peer))
+    }
+
+    // Un appel système sans attente; WouldBlock quand rien n'est en file
+    fn fill(&mut self, sock: &UdpSocket, slot_len: usize) -> io::Result<()> {
+        if self.slots.first().is_none_or(|s| s.len() != slot_len) {
+            self.slots = vec![vec![0; slot_len]; self.size];
+        }
+        self.ready.clear();
+        self.fill_slots(sock)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn fill_slots(&mut self, sock: &UdpSocket) -> io::Result<()> {
+        use std::os::fd::AsRawFd;
+        let msgs = self.headers.prepare(&mut self.slots);
+        // SAFETY: fd valide pendant l'appel; chaque en-tête pointe vers une adresse et un buffer vivants
+        let rc = unsafe { libc::recvmmsg(sock.as_raw_fd(), msgs.as_mut_ptr(), self.size as libc::c_uint, libc::MSG_DONTWAIT, std::ptr::null_mut()) };
+        if rc < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        for (slot, msg) in self.headers.msgs.iter().take(rc as usize).enumerate() {
+            let mut storage = socket2::SockAddrStorage::zeroed();
+            // SAFETY: sockaddr_storage est le type natif de SockAddrStorage; namelen vient du noyau
+            let peer = unsafe {
+                *storage.view_as::<libc::sockaddr_storage>() = self.headers.addrs[slot];
+                socket2::SockAddr::new(storage, msg.msg_hdr.msg_namelen)
+            };
+            let peer = peer.as_socket().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "datagram source is not an IP address"))?;
+            let truncated = msg.msg_hdr.msg_flags & libc::MSG_TRUNC != 0;
+            self.ready.push_back(Received { slot, len: msg.msg_len as usize, peer, truncated });
+        }
+        Ok(())
+    }
+
+    // Sans MSG_TRUNC, une lecture qui remplit le slot est traitée comme tronquée, comme dans le pipe
+    #[cfg(not(target_os = "linux"))]
+    fn fill_slots(&mut self, sock: &UdpSocket) -> io::Result<()> {
+        let (len, peer) = sock.try_recv_from(&mut self.slots[0])?;
+        self.ready.push_back(Received { slot: 0, len, peer, truncated: len >= self.slots[0].len() });
+        Ok(())
+    }
+}
+
+// Tableaux passés à recvmmsg, alloués une fois par lot. Les pointeurs (adresse source, iovec, buffer du
+// slot) sont réécrits avant chaque appel, ainsi que les champs que le noyau modifie (namelen, flags).
+#[cfg(target_os = "linux")]
+struct MmsgHeaders {
+    addrs: Vec<libc::sockaddr_storage>,
+    iovecs: Vec<libc::iovec>,
+    msgs: Vec<libc::mmsghdr>,
+}
+
+// SAFETY: les pointeurs bruts ne visent que les tableaux de cette structure et les slots du même
+// RecvBatch; ils ne sont lus que pendant recvmmsg, après prepare(), sur le thread qui possède le lot
+#[cfg(target_os = "linux")]
+unsafe impl Send for MmsgHeaders {}
+
+#[cfg(target_os = "linux")]
+impl MmsgHeaders {
+    fn new(size: usize) -> Self {
+        // SAFETY: sockaddr_storage, iovec et mmsghdr sont des structures C pour lesquelles des zéros sont valides
+        unsafe {
+            Self {
+                addrs: vec![std::mem::zeroed(); size],
+                iovecs: vec![std::mem::zeroed(); size],
+                msgs: vec![std::mem::zeroed(); size],
+            }
+        }
+    }
+
+    fn prepare(&mut self, slots: &mut [Vec<u8>]) -> &mut [libc::mmsghdr] {
+        for (((msg, iov), addr), slot) in self.msgs.iter_mut().zip(self.iovecs.iter_mut()).zip(self.addrs.iter_mut()).zip(slots.iter_mut()) {
+            *iov = libc::iovec { iov_base: slot.as_mut_ptr().cast(), iov_len: slot.len() };
+            msg.msg_hdr.msg_name = (addr as *mut libc::sockaddr_storage).cast();
+            msg.msg_hdr.msg_namelen = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+            msg.msg_hdr.msg_iov = iov;
+            msg.msg_hdr.msg_iovlen = 1;
+            msg.msg_hdr.msg_flags = 0;
+            msg.msg_len = 0;
+        }
+        &mut self.msgs
+    }
+}
+
+// recv_from qui passe par le lot quand ?recv_batch est actif
+pub async fn recv_from(sock: &UdpSocket, batch: Option<&mut RecvBatch>, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+    let Some(batch) = batch else { return sock.recv_from(buf).await };
+    loop {
+        if let Some(received) = batch.pop(buf) {
+            return Ok(received);
+        }
+        sock.readable().await?;
+        match sock.try_io(Interest::READABLE, || batch.fill(sock, buf.len())) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            other => other?,
+        }
+    }
+}
+
+// ?recv_batch=N sur une URI d'entrée (1..=64); 0 ou absent = une lecture par datagramme
+pub fn recv_batch_from_uri(uri: &str) -> TResult<Option<RecvBatch>> {
+    let Some(raw) = query_param(uri, "recv_batch") else { return Ok(None) };
+    match raw.parse::<usize>() {
+        Ok(0) => Ok(None),
+        Ok(size) if size <= MAX_RECV_BATCH => Ok(Some(RecvBatch::new(size))),
+        _ => Err(TransportError::InvalidUri(format!("recv_batch must be 0 (off) to {} datagrams, got {}", MAX_RECV_BATCH, raw))),
+    }
+}
+
+pub fn describe_recv_batch(batch: Option<&RecvBatch>) -> String {
+    batch.map(|b| format!(" recv_batch={}", b.size())).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_recv_batch_param() {
+        assert!(recv_batch_from_uri("rist://@:9000").unwrap().is_none());
+        assert!(recv_batch_from_uri("rist://@:9000?recv_batch=0").unwrap().is_none());
+        assert_eq!(recv_batch_from_uri("rist://@:9000?recv_batch=32").unwrap().unwrap().size(), 32);
+        assert!(recv_batch_from_uri("rist://@:9000?recv_batch=65").is_err());
+        assert!(recv_batch_from_uri("rist://@:9000?recv_batch=x").is_err());
+    }
+
+    #[tokio::test]
+    async fn hands_out_a_batch_in_order() {
+        let rx = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let tx = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        for i in 0..10u8 {
+            tx.send_to(&[i; 3], rx.local_addr().unwrap()).unwrap();
+        }
+        let mut batch = RecvBatch::new(4);
+        let mut buf = [0u8; 16];
+        for i in 0..10u8 {
+            let (n, peer) = recv_from(&rx, Some(&mut batch), &mut buf).await.unwrap();
+            assert_eq!((n, buf[0]), (3, i));
+            assert_eq!(peer, tx.local_addr().unwrap());
+        }
+    }
+
+    // Lot rempli avec des slots de 16 octets, buffer du pipe agrandi entre-temps: le datagramme coupé
+    // à 16 octets est rendu comme une lecture pleine du nouveau buffer, donc compté tronqué
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn truncated_entries_read_as_full_after_the_buffer_grew() {
+        let rx = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let tx = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        tx.send_to(&[7u8; 4], rx.local_addr().unwrap()).unwrap();
+        tx.send_to(&[8u8; 40], rx.local_addr().unwrap()).unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        let mut batch = RecvBatch::new(4);
+        let mut small = [0u8; 16];
+        let mut large = [0u8; 64];
+        assert_eq!(recv_from(&rx, Some(&mut batch), &mut small).await.unwrap().0, 4);
+        assert_eq!(recv_from(&rx, Some(&mut batch), &mut large).await.unwrap().0, large.len());
+    }
+}
@@ -0,0 +1,122 @@
+use std::time::{Duration, Instant};
+
+// Receive buffer sized from the datagrams actually seen. It starts at one MTU-sized packet and grows
+// (x4, up to the largest UDP payload) when a datagram did not fit. Reads go into one spare byte past
+// the capacity: a read that reaches that byte was truncated, while a datagram of exactly the capacity
+// fits and is kept. It only shrinks after a full window in which every datagram would fit in a quarter
+// of it: a stream with a few large packets keeps its buffer instead of oscillating between two sizes.
+
+// Fits a 1316-byte TS datagram or a full 1500-byte Ethernet frame
+pub const MIN_RECV_BUFFER_LEN: usize = 2048;
+// Largest UDP payload is 65507 bytes (IPv4) / 65527 (IPv6 without jumbograms): every datagram fits
+pub const MAX_RECV_BUFFER_LEN: usize = 64 * 1024;
+const GROWTH_FACTOR: usize = 4;
+const SHRINK_WINDOW: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resize {
+    Grown(usize),
+    Shrunk(usize),
+}
+
+pub struct AdaptiveRecvBuffer {
+    // Capacité + 1 octet témoin de troncature
+    buf: Vec<u8>,
+    // Plus gros datagramme de la fenêtre courante
+    largest: usize,
+    window_start: Instant,
+}
+
+impl AdaptiveRecvBuffer {
+    pub fn new(now: Instant) -> Self {
+        Self { buf: vec![0u8; MIN_RECV_BUFFER_LEN + 1], largest: 0, window_start: now }
+    }
+
+    // Capacité: plus gros datagramme reçu intact
+    pub fn len(&self) -> usize {
+        self.buf.len() - 1
+    }
+
+    // La lecture a atteint l'octet témoin: le datagramme dépassait la capacité
+    pub fn is_full(&self, n: usize) -> bool {
+        n > self.len()
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+
+    pub fn data(&self, n: usize) -> &[u8] {
+        &self.buf[..n]
+    }
+
+    // Called after each read of n bytes; a full read grows the buffer unless it is already at the cap
+    pub fn observe(&mut self, n: usize, now: Instant) -> Option<Resize> {
+        if self.is_full(n) {
+            if self.len() >= MAX_RECV_BUFFER_LEN {
+                return None;
+            }
+            let len = (self.len() * GROWTH_FACTOR).min(MAX_RECV_BUFFER_LEN);
+            self.buf.resize(len + 1, 0);
+            self.reset_window(now);
+            return Some(Resize::Grown(len));
+        }
+        self.largest = self.largest.max(n);
+        if now.saturating_duration_since(self.window_start) < SHRINK_WINDOW {
+            return None;
+        }
+        let target = (self.largest * 2).next_power_of_two().clamp(MIN_RECV_BUFFER_LEN, MAX_RECV_BUFFER_LEN);
+        self.reset_window(now);
+        if target > self.len() / GROWTH_FACTOR {
+            return None;
+        }
+        self.buf.truncate(target + 1);
+        self.buf.shrink_to_fit();
+        Some(Resize::Shrunk(target))
+    }
+
+    fn reset_window(&mut self, now: Instant) {
+        self.largest = 0;
+        self.window_start = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grows_on_full_reads_up_to_the_cap() {
+        let t0 = Instant::now();
+        let mut b = AdaptiveRecvBuffer::new(t0);
+        assert_eq!(b.observe(1316, t0), None);
+        assert_eq!(b.observe(MIN_RECV_BUFFER_LEN + 1, t0), Some(Resize::Grown(8192)));
+        assert_eq!(b.observe(8193, t0), Some(Resize::Grown(32768)));
+        assert_eq!(b.observe(32769, t0), Some(Resize::Grown(MAX_RECV_BUFFER_LEN)));
+        assert_eq!(b.observe(MAX_RECV_BUFFER_LEN, t0), None);
+        assert_eq!(b.len(), MAX_RECV_BUFFER_LEN);
+    }
+
+    #[test]
+    fn a_datagram_of_exactly_the_capacity_is_not_truncated() {
+        let mut b = AdaptiveRecvBuffer::new(Instant::now());
+        assert_eq!(b.as_mut_slice().len(), MIN_RECV_BUFFER_LEN + 1);
+        assert!(!b.is_full(MIN_RECV_BUFFER_LEN));
+        assert!(b.is_full(MIN_RECV_BUFFER_LEN + 1));
+    }
+
+    #[test]
+    fn shrinks_only_after_a_quiet_window() {
+        let t0 = Instant::now();
+        let mut b = AdaptiveRecvBuffer::new(t0);
+        b.observe(MIN_RECV_BUFFER_LEN + 1, t0);
+        b.observe(8193, t0);
+        assert_eq!(b.len(), 32768);
+        // Datagrams between a quarter and the full size keep the buffer
+        assert_eq!(b.observe(9000, t0 + SHRINK_WINDOW), None);
+        assert_eq!(b.len(), 32768);
+        // A full window of small datagrams brings it back down
+        b.observe(1316, t0 + SHRINK_WINDOW + Duration::from_secs(1));
+        assert_eq!(b.observe(1316, t0 + SHRINK_WINDOW * 2), Some(Resize::Shrunk(4096)));
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use once_cell::sync::Lazy;
+
+use crate::common::features;
+use crate::relay::rist::{RistReceiver, RistSender};
+use crate::relay::srt::{SrtReceiver, SrtSender};
+use crate::relay::transport::{RxEndpoint, TxEndpoint};
+use crate::structures::{TResult, TransportError};
+
+// Paramètres communs transmis aux factories (les options propres au protocole restent dans l'URI)
+#[derive(Debug, Clone, Default)]
+pub struct TransportParams {
+    pub latency_ms: u64,
+    // Sonde d'accessibilité des sorties à l'ouverture, sauf ?reachability_check= explicite
+    pub reachability_check: bool,
+}
+
+pub type RxFactory = fn(&str, &TransportParams) -> TResult<Box<dyn RxEndpoint>>;
+pub type TxFactory = fn(&str, &TransportParams) -> TResult<Box<dyn TxEndpoint>>;
+
+#[derive(Clone, Copy)]
+struct TransportFactory {
+    rx: RxFactory,
+    tx: TxFactory,
+}
+
+// Associe un schéma d'URI (srt, rist, ...) aux constructeurs de récepteur/émetteur
+#[derive(Default)]
+pub struct TransportRegistry {
+    factories: HashMap<&'static str, TransportFactory>,
+}
+
+static BUILTIN: Lazy<TransportRegistry> = Lazy::new(TransportRegistry::builtin);
+
+impl TransportRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registre des transports fournis par le binaire
+    pub fn builtin() -> Self {
+        let mut reg = Self::new();
+        if features::SRT_TRANSPORT {
+            reg.register(
+                "srt",
+                |uri, p| Ok(Box::new(SrtReceiver::from_input_uri(uri, p.latency_ms)?)),
+                |uri, p| Ok(Box::new(SrtSender::from_output_uri(uri, p.latency_ms, p.reachability_check)?)),
+            );
+        }
+        if features::RIST_TRANSPORT {
+            reg.register(
+                "rist",
+                |uri, _| Ok(Box::new(RistReceiver::from_input_uri(uri)?)),
+                |uri, _| Ok(Box::new(RistSender::from_output_uri(uri)?)),
+            );
+        }
+        reg
+    }
+
+    pub fn global() -> &'static TransportRegistry {
+        &BUILTIN
+    }
+
+    pub fn register(&mut self, scheme: &'static str, rx: RxFactory, tx: TxFactory) {
+        self.factories.insert(scheme, TransportFactory { rx, tx });
+    }
+
+    // Schéma enregistré correspondant à l'URI (clé &'static, réutilisable comme label de protocole)
+    pub fn resolve(&self, uri: &str) -> TResult<&'static str> {
+        let scheme = scheme_of(uri).ok_or_else(|| TransportError::InvalidUri(format!("missing scheme: {}", uri)))?;
+        self.factories
+            .get_key_value(scheme.to_ascii_lowercase().as_str())
+            .map(|(k, _)| *k)
+            .ok_or_else(|| {
+                // Transport du projet absent de ce registre: dire comment l'obtenir plutôt que lister les autres
+                features::not_compiled_in(scheme)
+                    .unwrap_or_else(|| TransportError::UnsupportedScheme(format!("{} (available: {})", scheme, self.schemes().join(", "))))
+            })
+    }
+
+    pub fn schemes(&self) -> Vec<&'static str> {
+        let mut schemes: Vec<&'static str> = self.factories.keys().copied().collect();
+        schemes.sort_unstable();
+        schemes
+    }
+
+    pub fn build_rx(&self, uri: &str, params: &TransportParams) -> TResult<Box<dyn RxEndpoint>> {
+        let scheme = self.resolve(uri)?;
+        (self.factories[scheme].rx)(uri, params)
+    }
+
+    pub fn build_tx(&self, uri: &str, params: &TransportParams) -> TResult<Box<dyn TxEndpoint>> {
+        let scheme = self.resolve(uri)?;
+        (self.factories[scheme].tx)(uri, params)
+    }
+}
+
+pub fn scheme_of(uri: &str) -> Option<&str> {
+    uri.split_once("://").map(|(s, _)| s).filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispatches_by_scheme() {
+        let reg = TransportRegistry::builtin();
+        assert_eq!(reg.resolve("SRT://@:9000").unwrap(), "srt");
+        assert_eq!(reg.resolve("rist://127.0.0.1:1").unwrap(), "rist");
+        assert!(matches!(reg.resolve("udp://127.0.0.1:1"), Err(TransportError::UnsupportedScheme(_))));
+        assert!(matches!(reg.resolve("127.0.0.1:1"), Err(TransportError::InvalidUri(_))));
+        let tx = reg.build_tx("rist://127.0.0.1:11000", &TransportParams::default()).unwrap();
+        assert!(tx.describe().starts_with("output=rist://127.0.0.1:11000"));
+    }
+
+    #[test]
+    fn missing_project_transport_names_the_feature() {
+        let e = TransportRegistry::new().resolve("srt://@:9000").unwrap_err();
+        assert!(e.to_string().contains("rebuild with --features srt"), "{}", e);
+    }
+}
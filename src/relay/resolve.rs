@@ -0,0 +1,127 @@
+// Résolution de la cible d'une sortie (srt://relay.example.com:9000, rist://a.example:1000,b.example:1000):
+// une adresse IP littérale est prise telle quelle, un nom est résolu une fois, à la construction de
+// l'extrémité (getaddrinfo, dans l'ordre de préférence du système). Résolution et test de route sont
+// bloquants: les sorties sont construites sur le pool bloquant (relay::blocking::run).
+// ?family=ipv4|ipv6 ne garde que les adresses de cette famille et échoue s'il n'y en a aucune; auto
+// (défaut) prend la première adresse pour laquelle l'hôte a une route, et à défaut la première tout
+// court (l'erreur viendra alors de l'envoi).
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+
+use tracing::info;
+
+use crate::common::logging::events;
+use crate::common::uri::query_param;
+use crate::structures::{TResult, TransportError};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AddressFamily {
+    #[default]
+    Auto,
+    Ipv4,
+    Ipv6,
+}
+
+impl AddressFamily {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AddressFamily::Auto => "auto",
+            AddressFamily::Ipv4 => "ipv4",
+            AddressFamily::Ipv6 => "ipv6",
+        }
+    }
+
+    fn accepts(self, addr: &SocketAddr) -> bool {
+        match self {
+            AddressFamily::Auto => true,
+            AddressFamily::Ipv4 => addr.is_ipv4(),
+            AddressFamily::Ipv6 => addr.is_ipv6(),
+        }
+    }
+}
+
+pub fn family_from_uri(uri: &str) -> TResult<AddressFamily> {
+    match query_param(uri, "family").map(|v| v.to_ascii_lowercase()).as_deref() {
+        None | Some("auto") => Ok(AddressFamily::Auto),
+        Some("ipv4" | "4") => Ok(AddressFamily::Ipv4),
+        Some("ipv6" | "6") => Ok(AddressFamily::Ipv6),
+        Some(other) => Err(TransportError::InvalidUri(format!("family must be ipv4, ipv6 or auto, got {}", other))),
+    }
+}
+
+// `host_port`: "1.2.3.4:9000", "[::1]:9000" ou "relay.example.com:9000"
+pub fn resolve_target(host_port: &str, family: AddressFamily) -> TResult<SocketAddr> {
+    if let Ok(addr) = host_port.parse::<SocketAddr>() {
+        if !family.accepts(&addr) {
+            return Err(TransportError::InvalidUri(format!("{} is not an {} address (family={})", addr, if addr.is_ipv4() { "IPv6" } else { "IPv4" }, family.as_str())));
+        }
+        return Ok(addr);
+    }
+    if !host_port.rsplit_once(':').is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok()) {
+        return Err(TransportError::InvalidUri(format!("expected host:port, got {}", host_port)));
+    }
+    let candidates: Vec<SocketAddr> = host_port
+        .to_socket_addrs()
+        .map_err(|e| TransportError::InvalidUri(format!("cannot resolve {}: {}", host_port, e)))?
+        .collect();
+    let target = pick(&candidates, family, has_route).ok_or_else(|| {
+        let found = candidates.iter().map(|a| a.ip().to_string()).collect::<Vec<_>>().join(", ");
+        let kind = match family {
+            AddressFamily::Auto => "",
+            AddressFamily::Ipv4 => "IPv4 ",
+            AddressFamily::Ipv6 => "IPv6 ",
+        };
+        TransportError::InvalidUri(format!("{} has no {}address (family={}; resolved: {})", host_port, kind, family.as_str(), found))
+    })?;
+    info!(event = events::TARGET_RESOLVED, subsystem = "net", host = host_port, address = %target, family = family.as_str(), candidates = candidates.len(), msg = "Output host name resolved");
+    Ok(target)
+}
+
+fn pick(candidates: &[SocketAddr], family: AddressFamily, routable: impl Fn(&SocketAddr) -> bool) -> Option<SocketAddr> {
+    match family {
+        AddressFamily::Auto => candidates.iter().find(|a| routable(a)).or(candidates.first()).copied(),
+        AddressFamily::Ipv4 | AddressFamily::Ipv6 => candidates.iter().find(|a| family.accepts(a)).copied(),
+    }
+}
+
+// connect() d'un socket UDP n'envoie rien: il échoue seulement sans route ni adresse locale de la famille
+fn has_route(addr: &SocketAddr) -> bool {
+    let local = if addr.is_ipv6() { IpAddr::V6(Ipv6Addr::UNSPECIFIED) } else { IpAddr::V4(Ipv4Addr::UNSPECIFIED) };
+    UdpSocket::bind(SocketAddr::new(local, 0)).and_then(|sock| sock.connect(addr)).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_family() {
+        assert_eq!(family_from_uri("srt://h:1").unwrap(), AddressFamily::Auto);
+        assert_eq!(family_from_uri("srt://h:1?family=IPv6").unwrap(), AddressFamily::Ipv6);
+        assert_eq!(family_from_uri("rist://h:1?family=4").unwrap(), AddressFamily::Ipv4);
+        assert!(family_from_uri("srt://h:1?family=any").is_err());
+    }
+
+    #[test]
+    fn filters_the_resolved_addresses() {
+        let v6: SocketAddr = "[2001:db8::1]:9000".parse().unwrap();
+        let v4: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let both = [v6, v4];
+        assert_eq!(pick(&both, AddressFamily::Ipv4, |_| true), Some(v4));
+        assert_eq!(pick(&both, AddressFamily::Ipv6, |_| false), Some(v6));
+        assert_eq!(pick(&[v4], AddressFamily::Ipv6, |_| true), None);
+        // auto: l'ordre du système, sauf adresse sans route
+        assert_eq!(pick(&both, AddressFamily::Auto, |_| true), Some(v6));
+        assert_eq!(pick(&both, AddressFamily::Auto, |a| a.is_ipv4()), Some(v4));
+        assert_eq!(pick(&both, AddressFamily::Auto, |_| false), Some(v6));
+    }
+
+    #[test]
+    fn resolves_literals_and_names() {
+        assert_eq!(resolve_target("127.0.0.1:9000", AddressFamily::Auto).unwrap(), "127.0.0.1:9000".parse().unwrap());
+        assert!(resolve_target("127.0.0.1:9000", AddressFamily::Ipv6).is_err());
+        assert_eq!(resolve_target("localhost:9000", AddressFamily::Ipv4).unwrap(), "127.0.0.1:9000".parse().unwrap());
+        assert!(resolve_target("localhost", AddressFamily::Auto).is_err());
+        assert!(resolve_target(":9000", AddressFamily::Auto).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+// Payload-aware loss detection for RTP-over-UDP inputs.
+// Tracks the 16-bit RTP sequence number and reports gaps, reordering and duplicates.
+
+// Past this forward jump we assume the source restarted rather than lost packets (RFC 3550 MAX_DROPOUT)
+const MAX_DROPOUT: u16 = 3000;
+const RTP_HEADER_LEN: usize = 12;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SeqOutcome {
+    pub lost: u64,
+    pub reordered: bool,
+    pub duplicate: bool,
+    pub resync: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct RtpLossDetector {
+    last_seq: Option<u16>,
+}
+
+// Returns the sequence number if buf looks like an RTP v2 packet
+pub fn parse_rtp_seq(buf: &[u8]) -> Option<u16> {
+    if buf.len() < RTP_HEADER_LEN || buf[0] >> 6 != 2 {
+        return None;
+    }
+    Some(u16::from_be_bytes([buf[2], buf[3]]))
+}
+
+impl RtpLossDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe(&mut self, buf: &[u8]) -> Option<SeqOutcome> {
+        parse_rtp_seq(buf).map(|seq| self.observe_seq(seq))
+    }
+
+    pub fn observe_seq(&mut self, seq: u16) -> SeqOutcome {
+        let mut out = SeqOutcome::default();
+        let Some(last) = self.last_seq else {
+            self.last_seq = Some(seq);
+            return out;
+        };
+        let delta = seq.wrapping_sub(last);
+        if delta == 0 {
+            out.duplicate = true;
+        } else if delta < 0x8000 {
+            if delta > MAX_DROPOUT {
+                out.resync = true;
+            } else {
+                out.lost = u64::from(delta - 1);
+            }
+            self.last_seq = Some(seq);
+        } else {
+            // Older than the highest seen sequence: late arrival, already counted as lost
+            out.reordered = true;
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_gaps_across_wraparound() {
+        let mut d = RtpLossDetector::new();
+        d.observe_seq(65534);
+        assert_eq!(d.observe_seq(65535).lost, 0);
+        assert_eq!(d.observe_seq(2).lost, 2);
+    }
+
+    #[test]
+    fn flags_reorder_and_duplicate() {
+        let mut d = RtpLossDetector::new();
+        d.observe_seq(10);
+        assert_eq!(d.observe_seq(12).lost, 1);
+        assert!(d.observe_seq(11).reordered);
+        assert!(d.observe_seq(12).duplicate);
+    }
+
+    #[test]
+    fn large_jump_is_resync() {
+        let mut d = RtpLossDetector::new();
+        d.observe_seq(100);
+        let out = d.observe_seq(20_000);
+        assert!(out.resync);
+        assert_eq!(out.lost, 0);
+    }
+
+    #[test]
+    fn rejects_non_rtp() {
+        assert_eq!(parse_rtp_seq(&[0x47; 188]), None);
+        let mut pkt = [0u8; 12];
+        pkt[0] = 0x80;
+        pkt[2] = 0x01;
+        pkt[3] = 0x02;
+        assert_eq!(parse_rtp_seq(&pkt), Some(0x0102));
+    }
+}
@@ -0,0 +1,125 @@
+// Auto-test de bout en bout sur loopback: pour chaque schéma du registre, une source locale envoie
+// un motif connu vers un relais (récepteur -> run_pipe -> émetteur) et un puits vérifie ce qui ressort.
+// Aucun pair externe n'est nécessaire; seuls les transports enregistrés sont exercés.
+
+use std::net::UdpSocket as StdUdpSocket;
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, timeout, Duration, Instant};
+
+use crate::relay::open_endpoints;
+use crate::relay::pipe::{run_pipe, PipeOptions, StopReason};
+use crate::relay::registry::{TransportParams, TransportRegistry};
+use crate::structures::RelayProtocols;
+
+const PAYLOAD_LEN: usize = 1316;
+const PUMP_DURATION: Duration = Duration::from_secs(1);
+const PUMP_INTERVAL: Duration = Duration::from_millis(1);
+// Marge laissée au pipe pour vider ses buffers avant l'arrêt par max_runtime
+const DRAIN_GRACE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SelftestReport {
+    pub scheme: &'static str,
+    pub sent_bytes: u64,
+    pub received_bytes: u64,
+    pub corrupted: u64,
+    pub error: Option<String>,
+}
+
+impl SelftestReport {
+    // Sur loopback, tout doit passer intact: aucune perte tolérée
+    pub fn passed(&self) -> bool {
+        self.error.is_none() && self.corrupted == 0 && self.sent_bytes > 0 && self.received_bytes == self.sent_bytes
+    }
+}
+
+// Datagramme n°seq: numéro en tête (big-endian) puis octets dérivés du numéro
+fn pattern(seq: u32) -> Vec<u8> {
+    let mut buf: Vec<u8> = (0..PAYLOAD_LEN).map(|i| (i as u32).wrapping_add(seq) as u8).collect();
+    buf[..4].copy_from_slice(&seq.to_be_bytes());
+    buf
+}
+
+fn matches_pattern(buf: &[u8]) -> bool {
+    buf.len() == PAYLOAD_LEN && buf == pattern(u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]))
+}
+
+// Port libre sur loopback (petite fenêtre de course acceptable pour un auto-test)
+fn free_port() -> std::io::Result<u16> {
+    Ok(StdUdpSocket::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+pub async fn run_selftest() -> Vec<SelftestReport> {
+    let registry = TransportRegistry::global();
+    let mut reports = Vec::new();
+    for scheme in registry.schemes() {
+        let mut report = SelftestReport { scheme, ..SelftestReport::default() };
+        if let Err(e) = selftest_scheme(registry, scheme, &mut report).await {
+            report.error = Some(e.to_string());
+        }
+        reports.push(report);
+    }
+    reports
+}
+
+async fn selftest_scheme(registry: &'static TransportRegistry, scheme: &'static str, report: &mut SelftestReport) -> Result<()> {
+    let sink = UdpSocket::bind("127.0.0.1:0").await?;
+    let relay_port = free_port()?;
+    let input = format!("{}://@:{}?mode=listener", scheme, relay_port);
+    let output = format!("{}://127.0.0.1:{}?mode=caller", scheme, sink.local_addr()?.port());
+
+    let (rx, tx) = open_endpoints(registry, &input, &output, &TransportParams::default()).await?;
+    let opts = PipeOptions { max_runtime: Some(PUMP_DURATION + DRAIN_GRACE), ..PipeOptions::default() };
+    let protocols = RelayProtocols { input: scheme, output: scheme };
+    let pipe = tokio::spawn(async move { run_pipe(rx, tx, protocols, "selftest", opts, None).await });
+
+    let source = UdpSocket::bind("127.0.0.1:0").await?;
+    source.connect(("127.0.0.1", relay_port)).await?;
+    let pump = async {
+        let start = Instant::now();
+        let (mut seq, mut sent) = (0u32, 0u64);
+        while start.elapsed() < PUMP_DURATION {
+            sent += source.send(&pattern(seq)).await? as u64;
+            seq += 1;
+            sleep(PUMP_INTERVAL).await;
+        }
+        Ok::<_, std::io::Error>(sent)
+    };
+    let drain = async {
+        let mut buf = vec![0u8; 64 * 1024];
+        let (mut received, mut corrupted) = (0u64, 0u64);
+        while let Ok(Ok(n)) = timeout(DRAIN_GRACE, sink.recv(&mut buf)).await {
+            received += n as u64;
+            if !matches_pattern(&buf[..n]) {
+                corrupted += 1;
+            }
+        }
+        (received, corrupted)
+    };
+    let (pumped, (received, corrupted)) = tokio::join!(pump, drain);
+    report.sent_bytes = pumped?;
+    report.received_bytes = received;
+    report.corrupted = corrupted;
+
+    match pipe.await? {
+        Ok(StopReason::MaxRuntime) => Ok(()),
+        Ok(other) => Err(anyhow::anyhow!("pipe stopped unexpectedly: {}", other.as_str())),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_roundtrip() {
+        assert!(matches_pattern(&pattern(0)));
+        assert!(matches_pattern(&pattern(123_456)));
+        let mut bad = pattern(7);
+        bad[100] ^= 0xFF;
+        assert!(!matches_pattern(&bad));
+        assert!(!matches_pattern(&bad[..10]));
+    }
+}
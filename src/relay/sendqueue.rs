@@ -0,0 +1,341 @@
+// Decouples receiving from sending (?send_queue=N on an output): send() only pushes the datagram into
+// a bounded queue drained by a worker task, so a short stall of this output no longer holds up the
+// receive loop (and the upstream socket buffer). When the queue is full a datagram is dropped and
+// counted in send_queue_dropped_total: measured loss instead of a silent stall upstream.
+// ?send_queue_policy picks which one: drop_newest (default) refuses the incoming datagram,
+// drop_oldest discards the head of the queue so live output stays current instead of falling behind.
+// Either way the send() that caused the drop returns 0, so the pipe counts exactly one dropped datagram.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::sync::Notify;
+use tracing::warn;
+
+use crate::common::logging::{events, LogThrottle};
+use crate::common::uri::query_param;
+use crate::relay::transport::{BufferOccupancy, EffectiveOptions, TransportMeta, TransportTx, TxEndpoint};
+use crate::structures::{Metrics, TResult, TransportError};
+
+const MAX_SEND_QUEUE: usize = 65_536;
+
+// Which datagram goes when the queue is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DropPolicy {
+    #[default]
+    DropNewest,
+    DropOldest,
+}
+
+impl DropPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DropPolicy::DropNewest => "drop_newest",
+            DropPolicy::DropOldest => "drop_oldest",
+        }
+    }
+}
+
+// Queue shared by send() and the worker; each datagram keeps its enqueue time for the latency estimate
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<VecDeque<(Vec<u8>, Instant)>>,
+    ready: Notify,
+    closed: AtomicBool,
+    queued_bytes: AtomicU64,
+    failure: Mutex<Option<TransportError>>,
+}
+
+impl Shared {
+    fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        self.ready.notify_one();
+    }
+}
+
+// Same worker model as the delay simulation: the inner sender moves into the worker at open(),
+// a send error is reported by the next send() to trigger a reconnect
+pub struct SendQueue {
+    inner: Option<Box<dyn TxEndpoint>>,
+    depth: usize,
+    policy: DropPolicy,
+    description: String,
+    options: EffectiveOptions,
+    shared: Option<Arc<Shared>>,
+    full_log: LogThrottle,
+}
+
+impl SendQueue {
+    pub fn new(inner: Box<dyn TxEndpoint>, depth: usize, policy: DropPolicy) -> Self {
+        Self {
+            description: inner.describe(),
+            options: inner.effective_options(),
+            inner: Some(inner),
+            depth,
+            policy,
+            shared: None,
+            full_log: LogThrottle::new(Duration::from_secs(10)),
+        }
+    }
+
+    fn count_drop(&mut self, saved: Option<Duration>) {
+        if let Some(m) = Metrics::global() {
+            m.send_queue_dropped_total.with_label_values(&[self.policy.as_str()]).inc();
+            if let Some(saved) = saved {
+                m.send_queue_latency_saved_seconds_total.inc_by(saved.as_secs_f64());
+            }
+        }
+        if let Some(suppressed) = self.full_log.allow() {
+            warn!(event = events::SEND_QUEUE_FULL, subsystem = "net", depth = self.depth, policy = self.policy.as_str(), output = %self.description, suppressed = suppressed, msg = "Send queue full, datagram dropped; the output is slower than the input");
+        }
+    }
+}
+
+async fn send_worker(mut inner: Box<dyn TxEndpoint>, shared: Arc<Shared>) {
+    loop {
+        let next = shared.queue.lock().unwrap_or_else(|e| e.into_inner()).pop_front();
+        let Some((buf, _)) = next else {
+            if shared.closed.load(Ordering::Relaxed) {
+                break;
+            }
+            shared.ready.notified().await;
+            continue;
+        };
+        shared.queued_bytes.fetch_sub(buf.len() as u64, Ordering::Relaxed);
+        if let Err(e) = inner.send(&buf).await {
+            *shared.failure.lock().unwrap_or_else(|e| e.into_inner()) = Some(e);
+            // La file est fermée sous son verrou: send() ne peut plus rien y ajouter, et les
+            // datagrammes restants, perdus, sortent du compte
+            let mut queue = shared.queue.lock().unwrap_or_else(|e| e.into_inner());
+            shared.closed.store(true, Ordering::Relaxed);
+            let left: u64 = queue.drain(..).map(|(b, _)| b.len() as u64).sum();
+            shared.queued_bytes.fetch_sub(left, Ordering::Relaxed);
+            break;
+        }
+    }
+    inner.close();
+}
+
+#[async_trait]
+impl TransportTx for SendQueue {
+    // A refused datagram reports 0 bytes sent, like the other egress filters
+    async fn send(&mut self, buf: &[u8]) -> TResult<usize> {
+        let shared = self.shared.clone().ok_or(TransportError::Closed)?;
+        if let Some(e) = shared.failure.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            return Err(e);
+        }
+        let dropped = {
+            let mut queue = shared.queue.lock().unwrap_or_else(|e| e.into_inner());
+            if shared.closed.load(Ordering::Relaxed) {
+                return Err(TransportError::Closed);
+            } else if queue.len() < self.depth {
+                queue.push_back((buf.to_vec(), Instant::now()));
+                shared.queued_bytes.fetch_add(buf.len() as u64, Ordering::Relaxed);
+                None
+            } else if self.policy == DropPolicy::DropNewest {
+                Some(None)
+            } else {
+                // Chaque datagramme en file attend à peu près (âge du plus ancien / profondeur) de plus:
+                // c'est le retard évité pour tous les suivants en écartant la tête de file
+                let (old, queued_at) = queue.pop_front().expect("full queue has a head");
+                shared.queued_bytes.fetch_sub(old.len() as u64, Ordering::Relaxed);
+                let saved = queued_at.elapsed() / self.depth as u32;
+                queue.push_back((buf.to_vec(), Instant::now()));
+                shared.queued_bytes.fetch_add(buf.len() as u64, Ordering::Relaxed);
+                Some(Some(saved))
+            }
+        };
+        match dropped {
+            None => {
+                shared.ready.notify_one();
+                Ok(buf.len())
+            }
+            Some(None) => {
+                self.count_drop(None);
+                Ok(0)
+            }
+            // Le datagramme entre en file mais un autre est perdu à sa place: l'appel rapporte 0 pour que
+            // le pipe compte cette perte (le nombre de datagrammes est exact, les octets écartés peuvent
+            // différer de la taille du datagramme courant)
+            Some(Some(saved)) => {
+                self.count_drop(Some(saved));
+                Ok(0)
+            }
+        }
+    }
+}
+
+impl TransportMeta for SendQueue {
+    fn open(&mut self) -> TResult<()> {
+        let mut inner = self.inner.take().ok_or(TransportError::Closed)?;
+        inner.open()?;
+        self.options = inner.effective_options();
+        let shared = Arc::new(Shared::default());
+        tokio::spawn(send_worker(inner, shared.clone()));
+        self.shared = Some(shared);
+        Ok(())
+    }
+    // Le worker envoie encore les datagrammes en file puis ferme l'émetteur sous-jacent
+    fn close(&mut self) {
+        if let Some(shared) = self.shared.take() {
+            shared.close();
+        }
+        if let Some(inner) = self.inner.as_mut() {
+            inner.close();
+        }
+    }
+    fn describe(&self) -> String {
+        format!("{} send_queue={} send_queue_policy={}", self.description, self.depth, self.policy.as_str())
+    }
+    fn effective_options(&self) -> EffectiveOptions {
+        self.options.clone()
+    }
+    // Octets en attente dans la file, vus comme du buffer d'émission
+    fn buffer_occupancy(&self) -> Option<BufferOccupancy> {
+        let queued = self.shared.as_ref().map_or(0, |s| s.queued_bytes.load(Ordering::Relaxed));
+        Some(BufferOccupancy { recv_bytes: 0, send_bytes: queued })
+    }
+}
+
+// Un pipe en erreur abandonne l'émetteur sans close(): le worker est arrêté ici aussi
+impl Drop for SendQueue {
+    fn drop(&mut self) {
+        if let Some(shared) = self.shared.take() {
+            shared.close();
+        }
+    }
+}
+
+fn send_queue_from_uri(uri: &str) -> TResult<Option<usize>> {
+    let Some(raw) = query_param(uri, "send_queue") else { return Ok(None) };
+    match raw.parse::<usize>() {
+        Ok(0) => Ok(None),
+        Ok(depth) if depth <= MAX_SEND_QUEUE => Ok(Some(depth)),
+        _ => Err(TransportError::InvalidUri(format!("send_queue must be a number of datagrams between 0 and {}, got {}", MAX_SEND_QUEUE, raw))),
+    }
+}
+
+fn drop_policy_from_uri(uri: &str) -> TResult<DropPolicy> {
+    match query_param(uri, "send_queue_policy").map(|v| v.to_ascii_lowercase()).as_deref() {
+        None | Some("drop_newest") => Ok(DropPolicy::DropNewest),
+        Some("drop_oldest") => Ok(DropPolicy::DropOldest),
+        Some(other) => Err(TransportError::InvalidUri(format!("send_queue_policy must be drop_newest or drop_oldest, got {}", other))),
+    }
+}
+
+// Wraps the sender when ?send_queue is set on its URI (0 = disabled); returns it unchanged otherwise
+pub fn wrap_tx(tx: Box<dyn TxEndpoint>, uri: &str) -> TResult<Box<dyn TxEndpoint>> {
+    let policy = drop_policy_from_uri(uri)?;
+    Ok(match send_queue_from_uri(uri)? {
+        Some(depth) => Box::new(SendQueue::new(tx, depth, policy)),
+        None => tx,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct StalledTx;
+
+    #[async_trait]
+    impl TransportTx for StalledTx {
+        async fn send(&mut self, _buf: &[u8]) -> TResult<usize> {
+            std::future::pending().await
+        }
+    }
+
+    impl TransportMeta for StalledTx {
+        fn open(&mut self) -> TResult<()> {
+            Ok(())
+        }
+        fn close(&mut self) {}
+        fn describe(&self) -> String {
+            "output=stalled".into()
+        }
+    }
+
+    // Échoue après un court délai, le temps que des datagrammes s'accumulent derrière
+    struct SlowFailingTx;
+
+    #[async_trait]
+    impl TransportTx for SlowFailingTx {
+        async fn send(&mut self, _buf: &[u8]) -> TResult<usize> {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Err(TransportError::Closed)
+        }
+    }
+
+    impl TransportMeta for SlowFailingTx {
+        fn open(&mut self) -> TResult<()> {
+            Ok(())
+        }
+        fn close(&mut self) {}
+        fn describe(&self) -> String {
+            "output=failing".into()
+        }
+    }
+
+    #[test]
+    fn parses_send_queue_param() {
+        assert_eq!(send_queue_from_uri("srt://h:1").unwrap(), None);
+        assert_eq!(send_queue_from_uri("srt://h:1?send_queue=0").unwrap(), None);
+        assert_eq!(send_queue_from_uri("srt://h:1?send_queue=256").unwrap(), Some(256));
+        assert!(send_queue_from_uri("srt://h:1?send_queue=-1").is_err());
+        assert!(send_queue_from_uri("srt://h:1?send_queue=100000").is_err());
+        assert_eq!(drop_policy_from_uri("srt://h:1?send_queue=8").unwrap(), DropPolicy::DropNewest);
+        assert_eq!(drop_policy_from_uri("srt://h:1?send_queue_policy=Drop_Oldest").unwrap(), DropPolicy::DropOldest);
+        assert!(drop_policy_from_uri("srt://h:1?send_queue_policy=block").is_err());
+    }
+
+    #[tokio::test]
+    async fn drops_instead_of_blocking_when_full() {
+        Metrics::set_global(Arc::new(Metrics::new()));
+        let drops = || Metrics::global().unwrap().send_queue_dropped_total.with_label_values(&["drop_newest"]).get();
+        let mut q = SendQueue::new(Box::new(StalledTx), 2, DropPolicy::DropNewest);
+        q.open().unwrap();
+        // Le worker retire le premier datagramme et reste bloqué dessus: deux places restent dans la file
+        assert_eq!(q.send(b"a").await.unwrap(), 1);
+        tokio::task::yield_now().await;
+        assert_eq!(q.send(b"bb").await.unwrap(), 2);
+        assert_eq!(q.send(b"cc").await.unwrap(), 2);
+        let before = drops();
+        assert_eq!(q.send(b"dd").await.unwrap(), 0);
+        assert!(drops() > before);
+        assert_eq!(q.buffer_occupancy().unwrap().send_bytes, 4);
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_keeps_the_newest_datagrams() {
+        let mut q = SendQueue::new(Box::new(StalledTx), 2, DropPolicy::DropOldest);
+        q.open().unwrap();
+        assert_eq!(q.send(b"a").await.unwrap(), 1);
+        tokio::task::yield_now().await;
+        assert_eq!(q.send(b"bb").await.unwrap(), 2);
+        assert_eq!(q.send(b"cc").await.unwrap(), 2);
+        // "bb" est écarté au profit de "ddd": l'appel rapporte la perte
+        assert_eq!(q.send(b"ddd").await.unwrap(), 0);
+        assert_eq!(q.buffer_occupancy().unwrap().send_bytes, 5);
+        let queued: Vec<Vec<u8>> = q.shared.as_ref().unwrap().queue.lock().unwrap().iter().map(|(b, _)| b.clone()).collect();
+        assert_eq!(queued, vec![b"cc".to_vec(), b"ddd".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn queued_bytes_are_released_when_the_worker_fails() {
+        let mut q = SendQueue::new(Box::new(SlowFailingTx), 8, DropPolicy::DropNewest);
+        q.open().unwrap();
+        assert_eq!(q.send(b"a").await.unwrap(), 1);
+        tokio::task::yield_now().await;
+        assert_eq!(q.send(b"bb").await.unwrap(), 2);
+        assert_eq!(q.send(b"cc").await.unwrap(), 2);
+        assert_eq!(q.buffer_occupancy().unwrap().send_bytes, 4);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(q.buffer_occupancy().unwrap().send_bytes, 0);
+        assert!(q.send(b"dd").await.is_err());
+        assert!(matches!(q.send(b"dd").await, Err(TransportError::Closed)));
+        assert_eq!(q.buffer_occupancy().unwrap().send_bytes, 0);
+    }
+}
@@ -0,0 +1,434 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use tracing::{debug, warn};
+
+use crate::common::logging::events;
+use crate::relay::transport::{BufferOccupancy, EffectiveOptions};
+use crate::common::uri::{query_param, redact_uri_secrets};
+use crate::structures::{TResult, TransportError};
+
+// Role of an endpoint as written in its URI: listener (scheme://@:9000, ?mode=listener) binds a local
+// port and waits; caller (scheme://host:port, ?mode=caller) targets a remote host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointRole {
+    Listener,
+    Caller,
+}
+
+// Role from the URI, rejecting contradictions (@ with mode=caller) and unknown modes
+pub fn role_from_uri(uri: &str) -> TResult<EndpointRole> {
+    let authority = uri.split_once("://").map_or(uri, |(_, rest)| rest).split(['?', '/']).next().unwrap_or("");
+    let local = authority.starts_with('@');
+    match query_param(uri, "mode").map(|m| m.to_ascii_lowercase()).as_deref() {
+        None => Ok(if local { EndpointRole::Listener } else { EndpointRole::Caller }),
+        Some("listener") => Ok(EndpointRole::Listener),
+        Some("caller") if local => Err(TransportError::InvalidUri(format!(
+            "{}: mode=caller needs a remote host:port, but @ means listen on a local port",
+            redact_uri_secrets(uri)
+        ))),
+        Some("caller") => Ok(EndpointRole::Caller),
+        Some(other) => Err(TransportError::InvalidUri(format!("{}: mode must be listener or caller, got {}", redact_uri_secrets(uri), other))),
+    }
+}
+
+// Inputs may listen or call; an output always sends to a remote target
+pub fn require_output_role(uri: &str) -> TResult<()> {
+    match role_from_uri(uri)? {
+        EndpointRole::Caller => Ok(()),
+        EndpointRole::Listener => Err(TransportError::InvalidUri(format!(
+            "{} is a listener URI, but an output sends to a remote target: use scheme://host:port (mode=caller)",
+            redact_uri_secrets(uri)
+        ))),
+    }
+}
+
+// Bound of the optional reachability probe run when a sender opens (?reachability_check=1)
+pub const REACHABILITY_PROBE_TIMEOUT: Duration = Duration::from_millis(200);
+
+// Opt-in reachability probe, from ?reachability_check=1 on the output URI (or the relay's
+// reachability_check setting, passed as the default)
+pub fn reachability_check_from_uri(uri: &str, default: bool) -> TResult<bool> {
+    match query_param(uri, "reachability_check").as_deref() {
+        None => Ok(default),
+        Some("1" | "true" | "yes" | "on") => Ok(true),
+        Some("0" | "false" | "no" | "off") => Ok(false),
+        Some(other) => Err(TransportError::InvalidUri(format!("reachability_check must be a boolean, got {}", other))),
+    }
+}
+
+// One empty datagram to a connected UDP socket, then a short read. Only an ICMP error (port or host
+// unreachable, reported as ECONNREFUSED / EHOSTUNREACH on the connected socket) is conclusive;
+// silence proves nothing, a firewall or a busy receiver may simply not answer. The socket is left
+// non-blocking and the ICMP error consumed, so it does not resurface on the first real send.
+// Blocks for up to `wait`: it runs from open(), which the relay calls on the blocking pool.
+pub fn probe_unreachable(sock: &std::net::UdpSocket, wait: Duration) -> Option<std::io::Error> {
+    use std::io::ErrorKind;
+    let unreachable = |e: std::io::Error| {
+        matches!(e.kind(), ErrorKind::ConnectionRefused | ErrorKind::HostUnreachable | ErrorKind::NetworkUnreachable).then_some(e)
+    };
+    if let Err(e) = sock.set_nonblocking(false).and_then(|_| sock.set_read_timeout(Some(wait))) {
+        debug!(event = events::SOCKET_OPTION, error = %e, msg = "Reachability probe skipped");
+        return None;
+    }
+    let result = match sock.send(&[]) {
+        Err(e) => unreachable(e),
+        Ok(_) => sock.recv(&mut [0u8; 1]).err().and_then(unreachable),
+    };
+    let _ = sock.set_read_timeout(None);
+    let _ = sock.set_nonblocking(true);
+    result
+}
+
+// Local bind address for a sender, from ?localaddr=IP or ?localaddr=IP:PORT, and ?localport=PORT for a
+// fixed source port (firewalls that pin the media source port). Without them we bind the unspecified
+// address of the target's family and let the OS pick the interface and an ephemeral port.
+pub fn sender_bind_addr(uri: &str, target: SocketAddr) -> TResult<SocketAddr> {
+    let local_port = local_port_from_uri(uri)?;
+    let Some(raw) = query_param(uri, "localaddr") else {
+        let ip = if target.is_ipv6() { IpAddr::V6(Ipv6Addr::UNSPECIFIED) } else { IpAddr::V4(Ipv4Addr::UNSPECIFIED) };
+        return Ok(SocketAddr::new(ip, local_port.unwrap_or(0)));
+    };
+    let mut addr = raw
+        .parse::<SocketAddr>()
+        .or_else(|_| raw.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 0)))
+        .map_err(|_| TransportError::InvalidUri(format!("localaddr is not an IP address: {}", raw)))?;
+    if addr.is_ipv6() != target.is_ipv6() {
+        return Err(TransportError::InvalidUri(format!("localaddr {} does not match the target address family ({})", addr, target)));
+    }
+    if let Some(port) = local_port {
+        if addr.port() != 0 && addr.port() != port {
+            return Err(TransportError::InvalidUri(format!("localaddr {} and localport={} name different ports", addr, port)));
+        }
+        addr.set_port(port);
+    }
+    // Binding an ephemeral port on the IP proves it belongs to this host
+    std::net::UdpSocket::bind(SocketAddr::new(addr.ip(), 0))
+        .map_err(|e| TransportError::InvalidUri(format!("localaddr {} is not a local address: {}", addr.ip(), e)))?;
+    Ok(addr)
+}
+
+fn local_port_from_uri(uri: &str) -> TResult<Option<u16>> {
+    match query_param(uri, "localport") {
+        None => Ok(None),
+        Some(v) => match v.parse::<u16>() {
+            Ok(port) if port > 0 => Ok(Some(port)),
+            _ => Err(TransportError::InvalidUri(format!("localport must be a port between 1 and 65535, got {}", v))),
+        },
+    }
+}
+
+// Address reuse for a listener, both opt-in: ?reuseaddr=1 sets SO_REUSEADDR (quick restart over a
+// lingering socket) and ?reuseport=1 sets SO_REUSEPORT (several processes share the port, the kernel
+// load-balances datagrams between them). For UDP on Linux, two sockets that both set SO_REUSEADDR
+// may bind the same port and unicast then goes to the last one bound, so a second relay on the port
+// would silently steal the first one's traffic: without either flag the bind fails instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ListenerReuse {
+    pub addr: bool,
+    pub port: bool,
+}
+
+pub fn listener_reuse_from_uri(uri: &str) -> TResult<ListenerReuse> {
+    Ok(ListenerReuse { addr: flag_from_uri(uri, "reuseaddr")?, port: flag_from_uri(uri, "reuseport")? })
+}
+
+fn flag_from_uri(uri: &str, name: &str) -> TResult<bool> {
+    match query_param(uri, name).map(|v| v.to_ascii_lowercase()).as_deref() {
+        None | Some("0") | Some("false") => Ok(false),
+        Some("1") | Some("true") => Ok(true),
+        Some(other) => Err(TransportError::InvalidUri(format!("{} must be 0/1 or true/false, got {}", name, other))),
+    }
+}
+
+// Binds a listener socket with the requested reuse options. A bind failure names the address
+// instead of a bare OS error.
+pub fn bind_listener(addr: SocketAddr, reuse: ListenerReuse) -> TResult<std::net::UdpSocket> {
+    let sock = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    if reuse.addr {
+        sock.set_reuse_address(true)?;
+    }
+    if reuse.port {
+        set_reuse_port(&sock)?;
+    }
+    sock.bind(&addr.into()).map_err(|e| bind_error(addr, e, "listener"))?;
+    Ok(sock.into())
+}
+
+// Binds a sender's local socket (?localaddr may name a privileged port)
+pub fn bind_sender(addr: SocketAddr) -> TResult<std::net::UdpSocket> {
+    std::net::UdpSocket::bind(addr).map_err(|e| bind_error(addr, e, "sender"))
+}
+
+// A permission error gets its own variant with the likely fix instead of a bare Io error
+fn bind_error(addr: SocketAddr, e: std::io::Error, role: &str) -> TransportError {
+    if e.kind() == std::io::ErrorKind::AddrInUse {
+        return std::io::Error::new(e.kind(), format!("cannot bind {} on {}: port {} is already in use by another socket", role, addr, addr.port())).into();
+    }
+    if e.kind() != std::io::ErrorKind::PermissionDenied {
+        return std::io::Error::new(e.kind(), format!("cannot bind {} on {}: {}", role, addr, e)).into();
+    }
+    let hint = if addr.port() != 0 && addr.port() < 1024 {
+        "ports below 1024 require CAP_NET_BIND_SERVICE (e.g. setcap cap_net_bind_service=+ep on the binary) or use a port >= 1024"
+    } else {
+        "the bind was refused by a security policy (SELinux, AppArmor, seccomp)"
+    };
+    TransportError::BindPermissionDenied { addr: addr.to_string(), hint }
+}
+
+#[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))))]
+fn set_reuse_port(sock: &Socket) -> std::io::Result<()> {
+    sock.set_reuse_port(true)
+}
+
+#[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin")))))]
+fn set_reuse_port(_sock: &Socket) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "SO_REUSEPORT is not supported on this platform"))
+}
+
+// ?backlog= (listen queue length) only means something for a connection-oriented listener. The
+// datagram transports have no listen()/accept(): one socket receives every peer, so the parameter is
+// refused rather than accepted and ignored, until a stream transport (TCP, libsrt listener) passes
+// it to listen().
+pub fn reject_backlog(uri: &str) -> TResult<()> {
+    match query_param(uri, "backlog") {
+        None => Ok(()),
+        Some(_) => Err(TransportError::InvalidUri(format!(
+            "{}: backlog applies to connection-oriented listeners (listen/accept); this listener is a datagram socket with no accept queue",
+            redact_uri_secrets(uri)
+        ))),
+    }
+}
+
+// Suffix for a listener's describe(): with port 0 in the URI, the port the OS assigned once open
+pub fn describe_assigned_port(requested: SocketAddr, sock: Option<&tokio::net::UdpSocket>) -> String {
+    match sock.and_then(|s| s.local_addr().ok()) {
+        Some(bound) if requested.port() == 0 => format!(" assigned_port={}", bound.port()),
+        _ => String::new(),
+    }
+}
+
+// Suffix for a sender's describe(): a fixed source port is shown for firewall coordination
+pub fn describe_local_port(bind_addr: SocketAddr) -> String {
+    if bind_addr.port() == 0 { String::new() } else { format!(" local_port={}", bind_addr.port()) }
+}
+
+// ToS byte for outgoing packets, from ?dscp=46 (6-bit code point) or ?tos=0xb8 (raw byte).
+pub fn tos_from_uri(uri: &str) -> TResult<Option<u32>> {
+    let dscp = query_param(uri, "dscp");
+    let tos = query_param(uri, "tos");
+    match (dscp, tos) {
+        (Some(_), Some(_)) => Err(TransportError::InvalidUri("dscp and tos are mutually exclusive".into())),
+        (Some(d), None) => match parse_u32(&d) {
+            Some(v) if v <= 63 => Ok(Some(v << 2)),
+            _ => Err(TransportError::InvalidUri(format!("dscp must be 0..=63, got {}", d))),
+        },
+        (None, Some(t)) => match parse_u32(&t) {
+            Some(v) if v <= 255 => Ok(Some(v)),
+            _ => Err(TransportError::InvalidUri(format!("tos must be 0..=255, got {}", t))),
+        },
+        (None, None) => Ok(None),
+    }
+}
+
+fn parse_u32(s: &str) -> Option<u32> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+// Applies the ToS byte to a sender socket. IPv4 uses IP_TOS, IPv6 uses the traffic class
+// (IPV6_TCLASS, Unix only). Windows ignores IP_TOS unless the qWAVE/GPO policy allows it,
+// so the call may succeed without any effect there. Failures are logged, never fatal.
+pub fn apply_tos(sock: &std::net::UdpSocket, tos: u32) {
+    let sref = SockRef::from(sock);
+    let is_v6 = sock.local_addr().map(|a| a.is_ipv6()).unwrap_or(false);
+    let res = if is_v6 { set_tclass_v6(&sref, tos) } else { sref.set_tos_v4(tos) };
+    match res {
+        Ok(()) => debug!(event = events::SOCKET_OPTION, subsystem = "net", option = "tos", value = tos, msg = "ToS applied"),
+        Err(e) => warn!(event = events::SOCKET_OPTION, subsystem = "net", option = "tos", value = tos, error = %e, msg = "OS rejected ToS/DSCP marking"),
+    }
+}
+
+// Relit les options du socket ouvert (tailles de buffers noyau, ToS IPv4)
+pub fn read_socket_options(sock: &tokio::net::UdpSocket) -> EffectiveOptions {
+    let sref = SockRef::from(sock);
+    let is_v4 = sock.local_addr().map(|a| a.is_ipv4()).unwrap_or(false);
+    EffectiveOptions {
+        recv_buffer_bytes: sref.recv_buffer_size().ok(),
+        send_buffer_bytes: sref.send_buffer_size().ok(),
+        tos: if is_v4 { sref.tos_v4().ok() } else { None },
+        local_port: sock.local_addr().ok().map(|a| a.port()),
+        ..EffectiveOptions::default()
+    }
+}
+
+// Occupation réelle des buffers noyau via SO_MEMINFO (Linux): mémoire allouée aux files de réception
+// et d'émission. Inclut l'overhead noyau par datagramme, donc légèrement supérieure à la charge utile.
+#[cfg(target_os = "linux")]
+pub fn buffer_occupancy(sock: &tokio::net::UdpSocket) -> Option<BufferOccupancy> {
+    use std::os::fd::AsRawFd;
+    let mut info = [0u32; libc::SK_MEMINFO_DROPS as usize + 1];
+    let mut len = std::mem::size_of_val(&info) as libc::socklen_t;
+    // SAFETY: fd valide pour la durée de l'appel, buffer et longueur cohérents
+    let rc = unsafe {
+        libc::getsockopt(sock.as_raw_fd(), libc::SOL_SOCKET, libc::SO_MEMINFO, info.as_mut_ptr().cast(), &mut len)
+    };
+    (rc == 0).then(|| BufferOccupancy {
+        recv_bytes: u64::from(info[libc::SK_MEMINFO_RMEM_ALLOC as usize]),
+        send_bytes: u64::from(info[libc::SK_MEMINFO_WMEM_ALLOC as usize]),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn buffer_occupancy(_sock: &tokio::net::UdpSocket) -> Option<BufferOccupancy> {
+    None
+}
+
+#[cfg(unix)]
+fn set_tclass_v6(sref: &SockRef<'_>, tos: u32) -> std::io::Result<()> {
+    sref.set_tclass_v6(tos)
+}
+
+#[cfg(not(unix))]
+fn set_tclass_v6(_sref: &SockRef<'_>, _tos: u32) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "IPv6 traffic class not supported on this platform"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bind_error, reject_backlog, require_output_role, role_from_uri, EndpointRole, bind_listener, bind_sender, listener_reuse_from_uri, probe_unreachable, reachability_check_from_uri, sender_bind_addr, tos_from_uri, ListenerReuse, REACHABILITY_PROBE_TIMEOUT};
+    use crate::structures::TransportError;
+
+    #[test]
+    fn backlog_is_refused_on_datagram_listeners() {
+        assert!(reject_backlog("srt://@:9000?mode=listener").is_ok());
+        let err = reject_backlog("srt://@:9000?backlog=128").unwrap_err();
+        assert!(err.to_string().contains("datagram socket"), "{}", err);
+    }
+
+    #[test]
+    fn roles_from_uris() {
+        assert_eq!(role_from_uri("srt://@:9000").unwrap(), EndpointRole::Listener);
+        assert_eq!(role_from_uri("srt://0.0.0.0:9000?mode=listener").unwrap(), EndpointRole::Listener);
+        assert_eq!(role_from_uri("rist://10.0.0.1:9000").unwrap(), EndpointRole::Caller);
+        assert_eq!(role_from_uri("srt://h:9000?mode=Caller").unwrap(), EndpointRole::Caller);
+        assert!(role_from_uri("srt://@:9000?mode=caller").is_err());
+        assert!(role_from_uri("srt://h:9000?mode=rendezvous").is_err());
+        assert!(require_output_role("srt://h:9000").is_ok());
+        assert!(require_output_role("srt://h:9000?mode=listener").is_err());
+    }
+
+    #[test]
+    fn localaddr_defaults_and_parses() {
+        let target = "127.0.0.1:10000".parse().unwrap();
+        let any = sender_bind_addr("srt://127.0.0.1:10000", target).unwrap();
+        assert_eq!(any.to_string(), "0.0.0.0:0");
+        let lo = sender_bind_addr("srt://127.0.0.1:10000?localaddr=127.0.0.1", target).unwrap();
+        assert_eq!(lo.to_string(), "127.0.0.1:0");
+        assert!(sender_bind_addr("srt://127.0.0.1:10000?localaddr=nope", target).is_err());
+        assert!(sender_bind_addr("srt://127.0.0.1:10000?localaddr=::1", target).is_err());
+    }
+
+    #[test]
+    fn localport_fixes_the_source_port() {
+        let target = "127.0.0.1:10000".parse().unwrap();
+        assert_eq!(sender_bind_addr("srt://127.0.0.1:10000?localport=5004", target).unwrap().to_string(), "0.0.0.0:5004");
+        let lo = sender_bind_addr("srt://127.0.0.1:10000?localaddr=127.0.0.1&localport=5004", target).unwrap();
+        assert_eq!(lo.to_string(), "127.0.0.1:5004");
+        assert!(sender_bind_addr("srt://127.0.0.1:10000?localaddr=127.0.0.1:5005&localport=5004", target).is_err());
+        assert!(sender_bind_addr("srt://127.0.0.1:10000?localport=0", target).is_err());
+        let taken = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let e = bind_sender(taken.local_addr().unwrap()).unwrap_err();
+        assert!(e.to_string().contains("already in use"), "{}", e);
+    }
+
+    #[test]
+    fn tos_from_dscp_or_raw() {
+        assert_eq!(tos_from_uri("rist://h:1?dscp=46").unwrap(), Some(0xB8));
+        assert_eq!(tos_from_uri("rist://h:1?tos=0xb8").unwrap(), Some(0xB8));
+        assert_eq!(tos_from_uri("rist://h:1").unwrap(), None);
+        assert!(tos_from_uri("rist://h:1?dscp=64").is_err());
+        assert!(tos_from_uri("rist://h:1?dscp=46&tos=184").is_err());
+    }
+
+    #[test]
+    fn listener_reuse_options() {
+        assert_eq!(listener_reuse_from_uri("srt://@:9000").unwrap(), ListenerReuse::default());
+        assert_eq!(listener_reuse_from_uri("srt://@:9000?reuseport=1").unwrap(), ListenerReuse { addr: false, port: true });
+        assert_eq!(listener_reuse_from_uri("srt://@:9000?reuseaddr=true").unwrap(), ListenerReuse { addr: true, port: false });
+        assert!(listener_reuse_from_uri("srt://@:9000?reuseport=maybe").is_err());
+        assert!(listener_reuse_from_uri("srt://@:9000?reuseaddr=2").is_err());
+
+        // A socket bound without SO_REUSEADDR keeps the port exclusive
+        let first = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = first.local_addr().unwrap();
+        let err = bind_listener(addr, ListenerReuse::default()).unwrap_err().to_string();
+        assert!(err.contains(&addr.to_string()), "{}", err);
+    }
+
+    // Deux relais sur le même port sans option explicite: le second échoue au lieu de voler le trafic
+    #[test]
+    fn second_plain_listener_bind_fails() {
+        let first = bind_listener("127.0.0.1:0".parse().unwrap(), ListenerReuse::default()).unwrap();
+        let err = bind_listener(first.local_addr().unwrap(), ListenerReuse::default()).unwrap_err();
+        assert!(err.to_string().contains("already in use"), "{}", err);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reuseport_listeners_share_a_port() {
+        let reuse = ListenerReuse { addr: false, port: true };
+        let first = bind_listener("127.0.0.1:0".parse().unwrap(), reuse).unwrap();
+        assert!(bind_listener(first.local_addr().unwrap(), reuse).is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn occupancy_reflects_queued_datagrams() {
+        let rx = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let tx = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        assert_eq!(super::buffer_occupancy(&rx).unwrap().recv_bytes, 0);
+        tx.send_to(&[0u8; 1000], rx.local_addr().unwrap()).unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(super::buffer_occupancy(&rx).unwrap().recv_bytes >= 1000);
+    }
+
+    #[test]
+    fn permission_denied_names_the_fix() {
+        let denied = || std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        let err = bind_error("0.0.0.0:443".parse().unwrap(), denied(), "listener");
+        assert!(matches!(err, TransportError::BindPermissionDenied { .. }));
+        assert!(err.to_string().contains("CAP_NET_BIND_SERVICE"));
+        assert!(!bind_error("0.0.0.0:9000".parse().unwrap(), denied(), "listener").to_string().contains("CAP_NET_BIND_SERVICE"));
+        let other = bind_error("0.0.0.0:443".parse().unwrap(), std::io::ErrorKind::AddrInUse.into(), "listener");
+        assert!(matches!(other, TransportError::Io(_)));
+    }
+
+    #[test]
+    fn reachability_check_is_opt_in() {
+        assert!(!reachability_check_from_uri("srt://h:1", false).unwrap());
+        assert!(reachability_check_from_uri("srt://h:1", true).unwrap());
+        assert!(reachability_check_from_uri("srt://h:1?reachability_check=1", false).unwrap());
+        assert!(!reachability_check_from_uri("srt://h:1?reachability_check=off", true).unwrap());
+        assert!(reachability_check_from_uri("srt://h:1?reachability_check=maybe", false).is_err());
+    }
+
+    // Linux renvoie l'ICMP port unreachable du loopback sur le socket connecté
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn probe_detects_a_closed_port() {
+        let closed = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let target = closed.local_addr().unwrap();
+        drop(closed);
+        let sock = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        sock.connect(target).unwrap();
+        assert!(probe_unreachable(&sock, REACHABILITY_PROBE_TIMEOUT).is_some());
+
+        let open = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        sock.connect(open.local_addr().unwrap()).unwrap();
+        assert!(probe_unreachable(&sock, std::time::Duration::from_millis(20)).is_none());
+    }
+}
@@ -1,8 +1,13 @@
-use crate::structures::{TResult, TransportError};
+use crate::structures::TResult;
 use async_trait::async_trait;
+use serde::Serialize;
+use std::time::Duration;
 
 // API commune minimale pour les transports de type « message » (SRT/RIST)
 // Nota: l’implémentation V1 utilise UDP comme stub fonctionnel pour assurer un vrai débit local.
+// Modèle de threads: recv/send s'exécutent sur les workers Tokio et ne doivent jamais bloquer.
+// open() peut bloquer (bind, connect, sonde): il est appelé via relay::blocking::run, sur le pool
+// bloquant de Tokio.
 
 #[async_trait]
 pub trait TransportRx: Send {
@@ -17,8 +22,99 @@ pub trait TransportTx: Send {
     async fn send(&mut self, buf: &[u8]) -> TResult<usize>;
 }
 
+// Options effectivement appliquées à une extrémité ouverte, relues sur le socket quand c'est possible.
+// `negotiated` n'est vrai que si la latence provient d'un handshake (l'implémentation UDP actuelle
+// rapporte la valeur demandée).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct EffectiveOptions {
+    pub latency_ms: Option<u64>,
+    pub negotiated: bool,
+    pub congestion_control: Option<String>,
+    pub recv_buffer_bytes: Option<usize>,
+    pub send_buffer_bytes: Option<usize>,
+    pub tos: Option<u32>,
+    // Port local effectivement lié (celui attribué par l'OS pour un listener sur :0)
+    pub local_port: Option<u16>,
+    // Profil RIST en vigueur (simple, main, advanced); None pour les autres transports
+    pub profile: Option<String>,
+    // Utilisateur présenté au pair (userinfo de l'URI); jamais le mot de passe
+    pub user: Option<String>,
+    // CNAME RIST annoncé dans les RTCP (?cname=), pour corréler avec la supervision en aval
+    pub cname: Option<String>,
+}
+
+// Octets en attente dans les buffers noyau du socket (mesure réelle, pas une estimation)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferOccupancy {
+    pub recv_bytes: u64,
+    pub send_bytes: u64,
+}
+
 pub trait TransportMeta {
     fn open(&mut self) -> TResult<()>;
     fn close(&mut self);
     fn describe(&self) -> String;
+    // Options en vigueur après ouverture (défaut: rien de connu)
+    fn effective_options(&self) -> EffectiveOptions {
+        EffectiveOptions::default()
+    }
+    // None si la plateforme ou le transport ne l'expose pas
+    fn buffer_occupancy(&self) -> Option<BufferOccupancy> {
+        None
+    }
+    // Mesure de RTT arrivée depuis l'appel précédent (stats SRT, RTCP...); None sans mesure nouvelle.
+    // Le stub UDP n'en produit pas.
+    fn take_rtt_sample(&mut self) -> Option<Duration> {
+        None
+    }
+    // Vérification de connectivité d'une sortie ouverte (POST /probe-output), bornée par `wait`; peut
+    // bloquer. Par défaut l'ouverture réussie (bind + connect) suffit.
+    fn check_connectivity(&mut self, _wait: Duration) -> TResult<()> {
+        Ok(())
+    }
+}
+
+// Extrémités complètes (données + cycle de vie), utilisables comme objets dynamiques par le registre
+pub trait RxEndpoint: TransportRx + TransportMeta {}
+impl<T: TransportRx + TransportMeta> RxEndpoint for T {}
+
+pub trait TxEndpoint: TransportTx + TransportMeta {}
+impl<T: TransportTx + TransportMeta> TxEndpoint for T {}
+
+#[async_trait]
+impl<T: TransportRx + ?Sized> TransportRx for Box<T> {
+    async fn recv(&mut self, buf: &mut [u8]) -> TResult<usize> {
+        (**self).recv(buf).await
+    }
+}
+
+#[async_trait]
+impl<T: TransportTx + ?Sized> TransportTx for Box<T> {
+    async fn send(&mut self, buf: &[u8]) -> TResult<usize> {
+        (**self).send(buf).await
+    }
+}
+
+impl<T: TransportMeta + ?Sized> TransportMeta for Box<T> {
+    fn open(&mut self) -> TResult<()> {
+        (**self).open()
+    }
+    fn close(&mut self) {
+        (**self).close()
+    }
+    fn describe(&self) -> String {
+        (**self).describe()
+    }
+    fn effective_options(&self) -> EffectiveOptions {
+        (**self).effective_options()
+    }
+    fn buffer_occupancy(&self) -> Option<BufferOccupancy> {
+        (**self).buffer_occupancy()
+    }
+    fn take_rtt_sample(&mut self) -> Option<Duration> {
+        (**self).take_rtt_sample()
+    }
+    fn check_connectivity(&mut self, wait: Duration) -> TResult<()> {
+        (**self).check_connectivity(wait)
+    }
 }
@@ -0,0 +1,124 @@
+// MPEG-TS continuity checking: validates the sync byte and the 4-bit continuity counter of each PID.
+// Opt-in (payload=ts) since it assumes 188-byte TS packets and parses every packet.
+
+pub const TS_PACKET_LEN: usize = 188;
+const TS_SYNC_BYTE: u8 = 0x47;
+const NULL_PID: u16 = 0x1FFF;
+const PID_COUNT: usize = 0x2000;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TsOutcome {
+    pub packets: u64,
+    pub sync_errors: u64,
+    pub cc_errors: u64,
+    // Estimated number of TS packets missing, derived from the counter gaps
+    pub lost: u64,
+}
+
+pub struct TsContinuityChecker {
+    last_cc: Box<[Option<u8>; PID_COUNT]>,
+}
+
+impl Default for TsContinuityChecker {
+    fn default() -> Self {
+        Self { last_cc: Box::new([None; PID_COUNT]) }
+    }
+}
+
+impl TsContinuityChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Analyses a datagram containing one or more TS packets (7 per datagram in the usual UDP/SRT setup)
+    pub fn observe(&mut self, buf: &[u8]) -> TsOutcome {
+        let mut out = TsOutcome::default();
+        if !buf.len().is_multiple_of(TS_PACKET_LEN) {
+            out.sync_errors += 1;
+        }
+        for pkt in buf.chunks_exact(TS_PACKET_LEN) {
+            out.packets += 1;
+            if pkt[0] != TS_SYNC_BYTE {
+                out.sync_errors += 1;
+                continue;
+            }
+            self.check_packet(pkt, &mut out);
+        }
+        out
+    }
+
+    fn check_packet(&mut self, pkt: &[u8], out: &mut TsOutcome) {
+        let pid = (u16::from(pkt[1] & 0x1F) << 8) | u16::from(pkt[2]);
+        if pid == NULL_PID {
+            return;
+        }
+        let afc = (pkt[3] >> 4) & 0x03;
+        let cc = pkt[3] & 0x0F;
+        let has_payload = afc & 0x01 != 0;
+        // discontinuity_indicator: the source signals a deliberate counter reset
+        let discontinuity = afc & 0x02 != 0 && pkt[4] > 0 && pkt[5] & 0x80 != 0;
+
+        let slot = &mut self.last_cc[pid as usize];
+        if let (Some(last), false) = (*slot, discontinuity) {
+            // Counter only advances on packets carrying payload; one duplicate is allowed
+            let expected = if has_payload { (last + 1) & 0x0F } else { last };
+            if cc != expected && !(has_payload && cc == last) {
+                out.cc_errors += 1;
+                out.lost += u64::from(cc.wrapping_sub(expected) & 0x0F);
+            }
+        }
+        *slot = Some(cc);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(pid: u16, cc: u8) -> [u8; TS_PACKET_LEN] {
+        let mut p = [0xFFu8; TS_PACKET_LEN];
+        p[0] = TS_SYNC_BYTE;
+        p[1] = (pid >> 8) as u8 & 0x1F;
+        p[2] = pid as u8;
+        p[3] = 0x10 | (cc & 0x0F);
+        p
+    }
+
+    #[test]
+    fn continuous_stream_has_no_errors() {
+        let mut c = TsContinuityChecker::new();
+        let buf: Vec<u8> = (0..20u8).flat_map(|i| packet(0x100, i)).collect();
+        let out = c.observe(&buf);
+        assert_eq!(out.packets, 20);
+        assert_eq!(out.cc_errors, 0);
+    }
+
+    #[test]
+    fn detects_gap_per_pid() {
+        let mut c = TsContinuityChecker::new();
+        c.observe(&packet(0x100, 3));
+        c.observe(&packet(0x101, 9));
+        let out = c.observe(&packet(0x100, 6));
+        assert_eq!(out.cc_errors, 1);
+        assert_eq!(out.lost, 2);
+        assert_eq!(c.observe(&packet(0x101, 10)).cc_errors, 0);
+    }
+
+    #[test]
+    fn duplicate_and_null_pid_are_ignored() {
+        let mut c = TsContinuityChecker::new();
+        c.observe(&packet(0x100, 5));
+        assert_eq!(c.observe(&packet(0x100, 5)).cc_errors, 0);
+        c.observe(&packet(NULL_PID, 0));
+        assert_eq!(c.observe(&packet(NULL_PID, 7)).cc_errors, 0);
+    }
+
+    #[test]
+    fn counts_sync_errors() {
+        let mut c = TsContinuityChecker::new();
+        let mut p = packet(0x100, 0);
+        p[0] = 0x00;
+        assert_eq!(c.observe(&p).sync_errors, 1);
+        assert_eq!(c.observe(&[0x47; 100]).sync_errors, 1);
+    }
+}
@@ -0,0 +1,77 @@
+// Watchdog (?watchdog=SECONDS on the input, or watchdog_secs in the config file): when no datagram has
+// been received for the threshold, the relay is considered wedged even if recv() never reports an error
+// (a transport stuck in a bad state keeps returning timeouts, or nothing at all). The default action
+// (reconnect) closes both endpoints and goes through the reconnect loop, which reopens the sockets from
+// scratch and logs reconnect_attempt with reason "watchdog"; ?watchdog_action=stop ends the relay instead.
+// Distinct from idle_timeout (always a clean stop) and max_recv_timeouts (counts recv timeouts, so a recv
+// that blocks forever never trips it). A paused relay is never flagged.
+
+use std::time::Duration;
+use serde::Deserialize;
+
+use crate::common::uri::query_param;
+use crate::structures::{TResult, TransportError};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchdogAction {
+    // Fermeture et réouverture complète des extrémités via la boucle de reconnexion
+    #[default]
+    Reconnect,
+    // Arrêt propre du relais
+    Stop,
+}
+
+impl WatchdogAction {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.to_ascii_lowercase().as_str() {
+            "reconnect" => Some(WatchdogAction::Reconnect),
+            "stop" => Some(WatchdogAction::Stop),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WatchdogAction::Reconnect => "reconnect",
+            WatchdogAction::Stop => "stop",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogConfig {
+    pub silence: Duration,
+    pub action: WatchdogAction,
+}
+
+// ?watchdog=SECONDS [&watchdog_action=reconnect|stop] on an input URI; None when absent
+pub fn watchdog_from_uri(uri: &str) -> TResult<Option<WatchdogConfig>> {
+    let Some(raw) = query_param(uri, "watchdog") else { return Ok(None) };
+    let secs = raw.parse::<u64>().ok().filter(|&secs| secs > 0).ok_or_else(|| {
+        TransportError::InvalidUri(format!("watchdog must be a positive number of seconds, got {}", raw))
+    })?;
+    let action = match query_param(uri, "watchdog_action") {
+        None => WatchdogAction::default(),
+        Some(raw) => WatchdogAction::parse(&raw)
+            .ok_or_else(|| TransportError::InvalidUri(format!("watchdog_action must be reconnect or stop, got {}", raw)))?,
+    };
+    Ok(Some(WatchdogConfig { silence: Duration::from_secs(secs), action }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_watchdog_params() {
+        assert_eq!(watchdog_from_uri("srt://@:9000").unwrap(), None);
+        let cfg = watchdog_from_uri("srt://@:9000?watchdog=15").unwrap().unwrap();
+        assert_eq!(cfg, WatchdogConfig { silence: Duration::from_secs(15), action: WatchdogAction::Reconnect });
+        let cfg = watchdog_from_uri("rist://@:1?watchdog=5&watchdog_action=STOP").unwrap().unwrap();
+        assert_eq!(cfg.action, WatchdogAction::Stop);
+        assert!(watchdog_from_uri("srt://@:9000?watchdog=0").is_err());
+        assert!(watchdog_from_uri("srt://@:9000?watchdog=10s").is_err());
+        assert!(watchdog_from_uri("srt://@:9000?watchdog=10&watchdog_action=restart").is_err());
+    }
+}
@@ -8,8 +8,21 @@ pub enum TransportError {
     #[error("Invalid URI: {0}")]
     InvalidUri(String),
 
-    #[error("Operation timed out")]
-    Timeout,
+    #[error("Unsupported URI scheme: {0}")]
+    UnsupportedScheme(String),
+
+    // `during` précise l'étape hors régime établi (connexion / handshake d'un caller, sonde), pour la
+    // distinguer d'un recv sans donnée (None)
+    #[error("Operation timed out{}", during.as_deref().map(|d| format!(" {}", d)).unwrap_or_default())]
+    Timeout { during: Option<String> },
+
+    // Bind refusé par le système (port privilégié sans capacité, politique de sécurité)
+    #[error("Permission denied binding {addr}: {hint}")]
+    BindPermissionDenied { addr: String, hint: &'static str },
+
+    // Aucune donnée reçue pendant le seuil du watchdog: extrémités fermées pour une reconnexion complète
+    #[error("No data received for {silent_ms} ms; watchdog forcing a reconnect")]
+    Stalled { silent_ms: u64 },
 
     #[error("Transport closed")]
     Closed,
@@ -4,4 +4,157 @@ use serde::Serialize;
 pub struct HealthResponse {
     pub status: &'static str,
     pub code: u16,
-}
\ No newline at end of file
+    // Relais déclarés (configuration, SRTRIST_RELAYS, probes automatiques, socket de contrôle)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relays: Option<RelayCounts>,
+    // Relais arrêtés sur une erreur (bind refusé, ...): le processus répond mais ne relaie pas tout
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<RelayFailure>,
+    // Relais dont la boucle du pipe ne tourne plus (tâche bloquée), vus par le superviseur de vivacité
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stalled: Vec<String>,
+}
+
+impl HealthResponse {
+    pub fn ok() -> Self {
+        Self { status: "ok", code: 200, relays: None, failures: Vec::new(), stalled: Vec::new() }
+    }
+
+    // Sain sans relais déclaré ou sans échec, dégradé si une partie a échoué, hors service si tous ont échoué
+    pub fn from_relays(counts: RelayCounts, failures: Vec<RelayFailure>) -> Self {
+        let (status, code) = match counts.failed {
+            0 => ("ok", 200),
+            failed if failed < counts.configured => ("degraded", 200),
+            _ => ("unhealthy", 503),
+        };
+        Self { status, code, relays: Some(counts), failures, stalled: Vec::new() }
+    }
+
+    // Un pipe bloqué ne fait pas tomber la sonde mais rend l'état dégradé (sauf s'il est déjà hors service)
+    pub fn with_stalled(mut self, stalled: Vec<String>) -> Self {
+        if !stalled.is_empty() && self.code < 500 {
+            self.status = "degraded";
+        }
+        self.stalled = stalled;
+        self
+    }
+
+    // Exposition minimale de /healthz pour les sondes qui ne lisent que le texte Prometheus:
+    // relay_up vaut 0 seulement quand /health répondrait "unhealthy"
+    pub fn exposition(&self, active_relays: u64) -> String {
+        format!(
+            "# TYPE relay_up gauge\nrelay_up {}\n# TYPE relay_active gauge\nrelay_active {}\n",
+            u8::from(self.code < 500),
+            active_relays
+        )
+    }
+}
+
+// Réponse de /ready: un relais est prêt une fois sa période de chauffe passée et des données reçues
+#[derive(Serialize)]
+pub struct ReadyResponse {
+    pub status: &'static str,
+    pub code: u16,
+    pub relays: Vec<RelayReadiness>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayReadiness {
+    pub relay_id: String,
+    // warming (chauffe en cours, neutre), ready, no_data (chauffe passée sans données), connecting
+    pub state: &'static str,
+}
+
+impl RelayReadiness {
+    // `pipe_uptime_secs` absent: relais piloté sans pipe en cours (ouverture ou reconnexion)
+    pub fn new(relay_id: String, pipe_uptime_secs: Option<u64>, warmup_secs: u64, has_data: bool) -> Self {
+        let state = match pipe_uptime_secs {
+            None => "connecting",
+            Some(uptime) if uptime < warmup_secs => "warming",
+            Some(_) if has_data => "ready",
+            Some(_) => "no_data",
+        };
+        Self { relay_id, state }
+    }
+}
+
+impl ReadyResponse {
+    // La chauffe ne fait pas échouer la sonde: "warming" répond 200 tant qu'aucun relais n'est en défaut
+    pub fn from_relays(relays: Vec<RelayReadiness>) -> Self {
+        let (status, code) = if relays.iter().any(|r| matches!(r.state, "no_data" | "connecting")) {
+            ("not_ready", 503)
+        } else if relays.iter().any(|r| r.state == "warming") {
+            ("warming", 200)
+        } else {
+            ("ready", 200)
+        };
+        Self { status, code, relays }
+    }
+}
+
+// Un relais terminé proprement (max_runtime, idle_timeout) n'est ni actif ni en échec
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RelayCounts {
+    pub configured: usize,
+    pub active: usize,
+    pub failed: usize,
+}
+
+// Relais dont la tâche s'est terminée en erreur; relay_id absent pour les probes automatiques
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayFailure {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relay_id: Option<String>,
+    pub input: String,
+    pub error: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn healthz_exposition() {
+        let failure = || RelayFailure { relay_id: None, input: "srt://@:1".into(), error: "bind".into() };
+        let up = HealthResponse::from_relays(RelayCounts { configured: 2, active: 1, failed: 1 }, vec![failure()]);
+        assert_eq!(up.exposition(1), "# TYPE relay_up gauge\nrelay_up 1\n# TYPE relay_active gauge\nrelay_active 1\n");
+        let down = HealthResponse::from_relays(RelayCounts { configured: 1, active: 0, failed: 1 }, vec![failure()]);
+        assert!(down.exposition(0).contains("relay_up 0\n"));
+    }
+
+    fn status(configured: usize, active: usize, failed: usize) -> (&'static str, u16) {
+        let r = HealthResponse::from_relays(RelayCounts { configured, active, failed }, Vec::new());
+        (r.status, r.code)
+    }
+
+    #[test]
+    fn status_from_configured_and_failed_relays() {
+        assert_eq!(status(0, 0, 0), ("ok", 200));
+        assert_eq!(status(3, 3, 0), ("ok", 200));
+        assert_eq!(status(3, 2, 1), ("degraded", 200));
+        assert_eq!(status(2, 0, 2), ("unhealthy", 503));
+    }
+
+    #[test]
+    fn stalled_relays_degrade_health() {
+        let r = HealthResponse::ok().with_stalled(vec!["r1".into()]);
+        assert_eq!((r.status, r.code), ("degraded", 200));
+        assert_eq!(HealthResponse::ok().with_stalled(Vec::new()).status, "ok");
+        let down = HealthResponse::from_relays(RelayCounts { configured: 1, active: 0, failed: 1 }, Vec::new()).with_stalled(vec!["r1".into()]);
+        assert_eq!((down.status, down.code), ("unhealthy", 503));
+    }
+
+    #[test]
+    fn readiness_waits_for_warmup() {
+        let ready = |relays: Vec<RelayReadiness>| {
+            let r = ReadyResponse::from_relays(relays);
+            (r.status, r.code)
+        };
+        assert_eq!(RelayReadiness::new("a".into(), Some(2), 5, false).state, "warming");
+        assert_eq!(RelayReadiness::new("a".into(), Some(5), 5, false).state, "no_data");
+        assert_eq!(RelayReadiness::new("a".into(), Some(9), 5, true).state, "ready");
+        assert_eq!(ready(vec![]), ("ready", 200));
+        assert_eq!(ready(vec![RelayReadiness::new("a".into(), Some(1), 5, false), RelayReadiness::new("b".into(), Some(9), 5, true)]), ("warming", 200));
+        assert_eq!(ready(vec![RelayReadiness::new("a".into(), None, 5, false), RelayReadiness::new("b".into(), Some(1), 5, true)]), ("not_ready", 503));
+    }
+}
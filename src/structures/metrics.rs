@@ -1,18 +1,176 @@
-use std::time::Instant;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicU64, Ordering};
 use once_cell::sync::OnceCell;
-use prometheus::{opts, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Registry, Encoder, TextEncoder};
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::{LabelPair, MetricFamily, MetricType};
+use prometheus::{opts, Counter, CounterVec, GaugeVec, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Registry, Encoder, TextEncoder};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::common::logging::events;
+use crate::relay::ratelimit::RateLimitConfig;
+use crate::relay::transport::BufferOccupancy;
+use crate::structures::relay_stats::{PipeTimingsEntry, RelayProtocols, RelayStats, RelayStatsEntry, RelayTotals, RttSide};
 
 // Global handle to metrics for non-HTTP contexts (e.g., relay pipe)
 pub static GLOBAL_METRICS: OnceCell<Arc<Metrics>> = OnceCell::new();
 
+// Dernier échantillon des compteurs d'octets, pour calculer un débit par différence
+struct RateSample {
+    at: Instant,
+    bytes_in: u64,
+    bytes_out: u64,
+}
+
+type LabelsByRelay = Arc<Mutex<HashMap<String, HashMap<String, String>>>>;
+
+// Série d'information relay_labels{relay_id, <étiquettes>} = 1, une par relais étiqueté. Les noms
+// d'étiquettes varient d'un relais à l'autre, ce qu'un *Vec ne permet pas: d'où ce collecteur.
+// Côté PromQL: `relay_jitter_ms * on(relay_id) group_left(customer) relay_labels`.
+struct RelayLabelsCollector {
+    desc: Desc,
+    labels: LabelsByRelay,
+}
+
+impl RelayLabelsCollector {
+    fn new(labels: LabelsByRelay) -> Self {
+        let desc = Desc::new("relay_labels".into(), "User-supplied labels of each relay (value is always 1)".into(), Vec::new(), HashMap::new())
+            .expect("create desc");
+        Self { desc, labels }
+    }
+}
+
+impl Collector for RelayLabelsCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.desc]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let by_relay = self.labels.lock().unwrap_or_else(|e| e.into_inner());
+        let mut family = MetricFamily::default();
+        family.set_name(self.desc.fq_name.clone());
+        family.set_help(self.desc.help.clone());
+        family.set_field_type(MetricType::GAUGE);
+        for (relay_id, labels) in by_relay.iter() {
+            let mut pairs: Vec<LabelPair> = std::iter::once(("relay_id", relay_id.as_str()))
+                .chain(labels.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+                .map(|(k, v)| {
+                    let mut pair = LabelPair::default();
+                    pair.set_name(k.to_string());
+                    pair.set_value(v.to_string());
+                    pair
+                })
+                .collect();
+            pairs.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+            let mut gauge = prometheus::proto::Gauge::default();
+            gauge.set_value(1.0);
+            let mut metric = prometheus::proto::Metric::default();
+            metric.set_label(pairs.into());
+            metric.set_gauge(gauge);
+            family.mut_metric().push(metric);
+        }
+        vec![family]
+    }
+}
+
+// Préfixe des séries d'auto-surveillance du serveur HTTP (http_requests_total, http_request_duration_seconds)
+const HTTP_SELF_PREFIX: &str = "http_";
+
+// Familles exportées par /metrics: tout (défaut), les seules séries relais/transports, ou les seules séries HTTP
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricsScope {
+    All,
+    Relay,
+    Http,
+}
+
+impl MetricsScope {
+    pub fn parse(only: Option<&str>) -> Result<Self, String> {
+        match only.map(str::to_ascii_lowercase).as_deref() {
+            None | Some("" | "all") => Ok(MetricsScope::All),
+            Some("relay") => Ok(MetricsScope::Relay),
+            Some("http") => Ok(MetricsScope::Http),
+            Some(other) => Err(format!("only must be relay, http or all, got {}", other)),
+        }
+    }
+
+    fn includes(self, name: &str) -> bool {
+        match self {
+            MetricsScope::All => true,
+            MetricsScope::Relay => !name.starts_with(HTTP_SELF_PREFIX),
+            MetricsScope::Http => name.starts_with(HTTP_SELF_PREFIX),
+        }
+    }
+}
+
 // Regroupe le registry Prometheus et les métriques de l'application
 pub struct Metrics {
     pub registry: Registry,
     pub http_requests_total: IntCounterVec,
     pub http_request_duration_seconds: HistogramVec,
     pub uptime_seconds: IntGauge,
+    // Analyse MPEG-TS (payload=ts): erreurs de continuité et d'octet de synchro
+    pub ts_cc_errors_total: IntCounter,
+    pub ts_sync_errors_total: IntCounter,
+    // Datagrammes remplissant tout le buffer de réception (probablement tronqués), écartés
+    pub datagrams_truncated_total: IntCounter,
+    // Datagrammes volontairement écartés par la simulation de perte (?drop_pct), distincts des pertes réelles
+    pub injected_drops_total: IntCounter,
+    // Datagrammes dépassant le ?mtu de la sortie: action = rejected | split
+    pub oversized_datagrams_total: IntCounterVec,
+    // Datagrammes écartés parce que la file d'émission (?send_queue) d'une sortie était pleine,
+    // par politique: drop_newest (le datagramme entrant) ou drop_oldest (le plus ancien en file)
+    pub send_queue_dropped_total: IntCounterVec,
+    // Datagrammes reçus qu'aucune sortie n'a pu émettre (échec d'envoi qui arrête le pipe), par
+    // protocole de sortie
+    pub pkt_dropped_send_total: IntCounterVec,
+    pub bytes_dropped_send_total: IntCounterVec,
+    // Retard évité en écartant les plus anciens datagrammes (estimation, politique drop_oldest)
+    pub send_queue_latency_saved_seconds_total: Counter,
+    // Datagrammes reçus d'une source hors de la liste ?allow de l'entrée, écartés
+    pub rejected_by_acl_total: IntCounter,
+    // Keep-alives envoyés sur une sortie silencieuse (?keepalive_ms), hors octets et paquets relayés
+    pub keepalives_sent_total: IntCounter,
+    // Fragmentation applicative (?appfrag=1): fragments émis, et messages perdus au réassemblage
+    // par cause (incomplete, malformed, oversized)
+    pub appfrag_fragments_sent_total: IntCounter,
+    pub appfrag_reassembly_failures_total: IntCounterVec,
+    // Par lien d'une sortie RIST en bonding (?bonding=1), étiqueté par l'adresse du pair
+    pub rist_link_packets_sent_total: IntCounterVec,
+    pub rist_link_bytes_sent_total: IntCounterVec,
+    pub rist_link_send_errors_total: IntCounterVec,
+    // Somme des délais ajoutés par l'injection de latence (?delay_ms / ?jitter_ms)
+    pub injected_delay_seconds_total: Counter,
+    // Débits instantanés (bps), mis à jour par le sampler en tâche de fond
+    pub current_bps_in: IntGauge,
+    pub current_bps_out: IntGauge,
+    rate_sample: Mutex<RateSample>,
+    // Par sens de pont: labels (input_protocol, output_protocol)
+    pub relays_active: IntGaugeVec,
+    pub relay_bytes_in_total: IntCounterVec,
+    pub relay_bytes_out_total: IntCounterVec,
+    // Occupation réelle des buffers socket par relais (label relay_id)
+    pub recv_buffer_bytes: IntGaugeVec,
+    pub send_buffer_bytes: IntGaugeVec,
+    pub recv_buffer_size_bytes: IntGaugeVec,
+    pub relay_jitter_ms: GaugeVec,
+    // Silence de l'entrée par relais (labels relay_id, protocol), calculé au moment du scrape
+    pub seconds_since_last_recv: GaugeVec,
+    // Limitation d'émission par relais: plafonds configurés et temps passé à attendre
+    pub relay_rate_limit_bps: IntGaugeVec,
+    pub relay_rate_limit_pps: IntGaugeVec,
+    pub relay_throttled_seconds_total: CounterVec,
+    // 1 tant que le débit entrant lissé dépasse ?overrun_bitrate (relais avec un plafond seulement)
+    pub relay_overrun: IntGaugeVec,
+    // 1 tant que la boucle du pipe n'a pas tourné depuis le seuil (--stall-threshold-secs)
+    pub relay_stalled: IntGaugeVec,
+    // Reconnexions: essais et durée passée déconnecté (outcome = recovered | giveup)
+    pub reconnect_attempts_total: IntCounter,
+    pub reconnect_duration_seconds: HistogramVec,
+    // RTT remonté par les transports qui le mesurent, par côté du relais (input, output)
+    pub rtt_seconds: HistogramVec,
     pub start_time: Instant,
     // Runtime I/O stats aggregated across all pipes
     pub bytes_in_total: AtomicU64,
@@ -21,6 +179,22 @@ pub struct Metrics {
     pub pkt_out_total: AtomicU64,
     pub timeouts_total: AtomicU64,
     pub active_relays: AtomicU64,
+    // Pertes réelles détectées par analyse de payload (trous de séquence RTP, discontinuités MPEG-TS):
+    // datagrammes jamais reçus
+    pub pkt_rcv_loss_total: AtomicU64,
+    // Datagrammes reçus mais écartés ici: buffer de réception débordé, source hors ?allow, file
+    // d'émission pleine, ?mtu dépassé, simulation ?drop_pct
+    pub pkt_rcv_drop_total: AtomicU64,
+    pub pkt_reordered_total: AtomicU64,
+    // Relais actifs, indexés par relay_id
+    relays: Mutex<HashMap<String, Arc<RelayStats>>>,
+    // Relais suivis sur toute leur vie (run_relay): entre deux pipes, leurs stats attendent ici la
+    // reconnexion, qui les reprend (relay_id, compteurs cumulés) au lieu de repartir de zéro
+    detached_relays: Mutex<HashMap<String, Option<Arc<RelayStats>>>>,
+    // Étiquettes utilisateur par relay_id (exposées par RelayLabelsCollector)
+    relay_labels: LabelsByRelay,
+    // Incrémenté à chaque passage du sampler: les pipes publient leurs mesures quand il change
+    sample_epoch: AtomicU64,
 }
 
 impl Metrics {
@@ -45,22 +219,231 @@ impl Metrics {
         let uptime_seconds = IntGauge::new("uptime_seconds", "Process uptime in seconds")
             .expect("create gauge");
 
+        let ts_cc_errors_total = IntCounter::new("ts_cc_errors_total", "MPEG-TS continuity counter errors")
+            .expect("create counter");
+        let ts_sync_errors_total = IntCounter::new("ts_sync_errors_total", "MPEG-TS sync byte errors")
+            .expect("create counter");
+        let datagrams_truncated_total = IntCounter::new("datagrams_truncated_total", "Received datagrams dropped because they did not fit in the receive buffer")
+            .expect("create counter");
+        let injected_drops_total = IntCounter::new("injected_drops_total", "Outgoing datagrams dropped on purpose by the drop simulation (drop_pct)")
+            .expect("create counter");
+        let injected_delay_seconds_total = Counter::new("injected_delay_seconds_total", "Total delay added to outgoing datagrams by the latency injection (delay_ms, jitter_ms)")
+            .expect("create counter");
+        let oversized_datagrams_total = IntCounterVec::new(
+            opts!("oversized_datagrams_total", "Outgoing datagrams larger than the output mtu, by action (rejected, split)"),
+            &["action"],
+        )
+        .expect("create counter vec");
+        let pkt_dropped_send_total = IntCounterVec::new(
+            opts!("pkt_dropped_send_total", "Received datagrams not delivered because the send to the output failed, by output protocol"),
+            &["protocol"],
+        )
+        .expect("create counter vec");
+        let bytes_dropped_send_total = IntCounterVec::new(
+            opts!("bytes_dropped_send_total", "Bytes of received datagrams not delivered because the send to the output failed, by output protocol"),
+            &["protocol"],
+        )
+        .expect("create counter vec");
+        let send_queue_dropped_total = IntCounterVec::new(
+            opts!("send_queue_dropped_total", "Outgoing datagrams dropped because the output send queue (send_queue) was full, by policy (drop_newest, drop_oldest)"),
+            &["policy"],
+        )
+        .expect("create counter vec");
+        let send_queue_latency_saved_seconds_total = Counter::new("send_queue_latency_saved_seconds_total", "Estimated queueing delay avoided by dropping the oldest queued datagrams (send_queue_policy=drop_oldest)")
+            .expect("create counter");
+        let rejected_by_acl_total = IntCounter::new("rejected_by_acl_total", "Received datagrams dropped because their source is not in the input allow list (allow)")
+            .expect("create counter");
+        let keepalives_sent_total = IntCounter::new("keepalives_sent_total", "Keep-alive datagrams sent on idle outputs (keepalive_ms); not counted as forwarded traffic")
+            .expect("create counter");
+        let appfrag_fragments_sent_total = IntCounter::new("appfrag_fragments_sent_total", "Application-layer fragments sent on outputs with appfrag=1")
+            .expect("create counter");
+        let appfrag_reassembly_failures_total = IntCounterVec::new(
+            opts!("appfrag_reassembly_failures_total", "Messages lost while reassembling application-layer fragments (appfrag=1 input), by reason"),
+            &["reason"],
+        )
+        .expect("create counter vec");
+        let rist_link_packets_sent_total = IntCounterVec::new(
+            opts!("rist_link_packets_sent_total", "Datagrams sent on each link of a bonded RIST output (bonding=1), by peer address"),
+            &["link"],
+        )
+        .expect("create counter vec");
+        let rist_link_bytes_sent_total = IntCounterVec::new(
+            opts!("rist_link_bytes_sent_total", "Bytes sent on each link of a bonded RIST output (bonding=1), by peer address"),
+            &["link"],
+        )
+        .expect("create counter vec");
+        let rist_link_send_errors_total = IntCounterVec::new(
+            opts!("rist_link_send_errors_total", "Failed sends on each link of a bonded RIST output (bonding=1), by peer address"),
+            &["link"],
+        )
+        .expect("create counter vec");
+        let current_bps_in = IntGauge::new("current_bps_in", "Current inbound throughput in bits per second")
+            .expect("create gauge");
+        let current_bps_out = IntGauge::new("current_bps_out", "Current outbound throughput in bits per second")
+            .expect("create gauge");
+        let relays_active = IntGaugeVec::new(
+            opts!("relays_active", "Active relays by input and output protocol"),
+            &["input_protocol", "output_protocol"],
+        ).expect("create gauge vec");
+        let relay_bytes_in_total = IntCounterVec::new(
+            opts!("relay_bytes_in_total", "Bytes received by relays, by input and output protocol"),
+            &["input_protocol", "output_protocol"],
+        ).expect("create counter vec");
+        let relay_bytes_out_total = IntCounterVec::new(
+            opts!("relay_bytes_out_total", "Bytes sent by relays, by input and output protocol"),
+            &["input_protocol", "output_protocol"],
+        ).expect("create counter vec");
+        let recv_buffer_bytes = IntGaugeVec::new(
+            opts!("recv_buffer_bytes", "Bytes queued in the input socket receive buffer"),
+            &["relay_id"],
+        ).expect("create gauge vec");
+        let send_buffer_bytes = IntGaugeVec::new(
+            opts!("send_buffer_bytes", "Bytes queued in the output socket send buffer"),
+            &["relay_id"],
+        ).expect("create gauge vec");
+        let recv_buffer_size_bytes = IntGaugeVec::new(
+            opts!("recv_buffer_size_bytes", "Current size of the relay's adaptive datagram receive buffer"),
+            &["relay_id"],
+        ).expect("create gauge vec");
+        let relay_jitter_ms = GaugeVec::new(
+            opts!("relay_jitter_ms", "Smoothed inter-arrival jitter of the relay input in milliseconds"),
+            &["relay_id"],
+        ).expect("create gauge vec");
+        let seconds_since_last_recv = GaugeVec::new(
+            opts!("seconds_since_last_recv", "Seconds since the relay input last received a datagram (since start if none yet)"),
+            &["relay_id", "protocol"],
+        ).expect("create gauge vec");
+        let relay_rate_limit_bps = IntGaugeVec::new(
+            opts!("relay_rate_limit_bps", "Configured egress limit of the relay in bits per second"),
+            &["relay_id"],
+        ).expect("create gauge vec");
+        let relay_rate_limit_pps = IntGaugeVec::new(
+            opts!("relay_rate_limit_pps", "Configured egress limit of the relay in packets per second"),
+            &["relay_id"],
+        ).expect("create gauge vec");
+        let relay_throttled_seconds_total = CounterVec::new(
+            opts!("relay_throttled_seconds_total", "Time the relay spent waiting to honour its egress limit"),
+            &["relay_id"],
+        ).expect("create counter vec");
+        let relay_overrun = IntGaugeVec::new(
+            opts!("relay_overrun", "1 while the relay's smoothed input bitrate is above its overrun ceiling (overrun_bitrate)"),
+            &["relay_id"],
+        ).expect("create gauge vec");
+        let relay_stalled = IntGaugeVec::new(
+            opts!("relay_stalled", "1 while the relay's pipe loop has not run within the stall threshold (task likely deadlocked)"),
+            &["relay_id"],
+        ).expect("create gauge vec");
+        let reconnect_attempts_total = IntCounter::new("reconnect_attempts_total", "Relay reconnect attempts")
+            .expect("create counter");
+        let reconnect_duration_seconds = HistogramVec::new(
+            HistogramOpts::new("reconnect_duration_seconds", "Time a relay spent disconnected before recovering or giving up")
+                .buckets(reconnect_buckets()),
+            &["outcome"],
+        ).expect("create histogram vec");
+        let rtt_seconds = HistogramVec::new(
+            HistogramOpts::new("rtt_seconds", "Round-trip time samples reported by the relay transports")
+                .buckets(rtt_buckets()),
+            &["side"],
+        ).expect("create histogram vec");
+
         registry.register(Box::new(http_requests_total.clone())).expect("register counter vec");
         registry.register(Box::new(http_request_duration_seconds.clone())).expect("register histogram vec");
         registry.register(Box::new(uptime_seconds.clone())).expect("register gauge");
+        registry.register(Box::new(ts_cc_errors_total.clone())).expect("register counter");
+        registry.register(Box::new(ts_sync_errors_total.clone())).expect("register counter");
+        registry.register(Box::new(datagrams_truncated_total.clone())).expect("register counter");
+        registry.register(Box::new(injected_drops_total.clone())).expect("register counter");
+        registry.register(Box::new(oversized_datagrams_total.clone())).expect("register counter vec");
+        registry.register(Box::new(send_queue_dropped_total.clone())).expect("register counter vec");
+        registry.register(Box::new(pkt_dropped_send_total.clone())).expect("register counter vec");
+        registry.register(Box::new(bytes_dropped_send_total.clone())).expect("register counter vec");
+        registry.register(Box::new(send_queue_latency_saved_seconds_total.clone())).expect("register counter");
+        registry.register(Box::new(rejected_by_acl_total.clone())).expect("register counter");
+        registry.register(Box::new(keepalives_sent_total.clone())).expect("register counter");
+        registry.register(Box::new(appfrag_fragments_sent_total.clone())).expect("register counter");
+        registry.register(Box::new(appfrag_reassembly_failures_total.clone())).expect("register counter vec");
+        registry.register(Box::new(rist_link_packets_sent_total.clone())).expect("register counter vec");
+        registry.register(Box::new(rist_link_bytes_sent_total.clone())).expect("register counter vec");
+        registry.register(Box::new(rist_link_send_errors_total.clone())).expect("register counter vec");
+        let relay_labels = LabelsByRelay::default();
+        registry.register(Box::new(RelayLabelsCollector::new(relay_labels.clone()))).expect("register collector");
+        registry.register(Box::new(injected_delay_seconds_total.clone())).expect("register counter");
+        registry.register(Box::new(current_bps_in.clone())).expect("register gauge");
+        registry.register(Box::new(current_bps_out.clone())).expect("register gauge");
+        registry.register(Box::new(relays_active.clone())).expect("register gauge vec");
+        registry.register(Box::new(relay_bytes_in_total.clone())).expect("register counter vec");
+        registry.register(Box::new(relay_bytes_out_total.clone())).expect("register counter vec");
+        registry.register(Box::new(recv_buffer_bytes.clone())).expect("register gauge vec");
+        registry.register(Box::new(send_buffer_bytes.clone())).expect("register gauge vec");
+        registry.register(Box::new(recv_buffer_size_bytes.clone())).expect("register gauge vec");
+        registry.register(Box::new(relay_jitter_ms.clone())).expect("register gauge vec");
+        registry.register(Box::new(seconds_since_last_recv.clone())).expect("register gauge vec");
+        registry.register(Box::new(relay_rate_limit_bps.clone())).expect("register gauge vec");
+        registry.register(Box::new(relay_rate_limit_pps.clone())).expect("register gauge vec");
+        registry.register(Box::new(relay_throttled_seconds_total.clone())).expect("register counter vec");
+        registry.register(Box::new(relay_overrun.clone())).expect("register gauge vec");
+        registry.register(Box::new(relay_stalled.clone())).expect("register gauge vec");
+        registry.register(Box::new(reconnect_attempts_total.clone())).expect("register counter");
+        registry.register(Box::new(reconnect_duration_seconds.clone())).expect("register histogram vec");
+        registry.register(Box::new(rtt_seconds.clone())).expect("register histogram vec");
+
+        let start_time = Instant::now();
 
         Self {
             registry,
             http_requests_total,
             http_request_duration_seconds,
             uptime_seconds,
-            start_time: Instant::now(),
+            ts_cc_errors_total,
+            ts_sync_errors_total,
+            datagrams_truncated_total,
+            injected_drops_total,
+            oversized_datagrams_total,
+            send_queue_dropped_total,
+            pkt_dropped_send_total,
+            bytes_dropped_send_total,
+            send_queue_latency_saved_seconds_total,
+            rejected_by_acl_total,
+            keepalives_sent_total,
+            appfrag_fragments_sent_total,
+            appfrag_reassembly_failures_total,
+            rist_link_packets_sent_total,
+            rist_link_bytes_sent_total,
+            rist_link_send_errors_total,
+            injected_delay_seconds_total,
+            current_bps_in,
+            current_bps_out,
+            rate_sample: Mutex::new(RateSample { at: start_time, bytes_in: 0, bytes_out: 0 }),
+            relays_active,
+            relay_bytes_in_total,
+            relay_bytes_out_total,
+            recv_buffer_bytes,
+            send_buffer_bytes,
+            recv_buffer_size_bytes,
+            relay_jitter_ms,
+            seconds_since_last_recv,
+            relay_rate_limit_bps,
+            relay_rate_limit_pps,
+            relay_throttled_seconds_total,
+            relay_overrun,
+            relay_stalled,
+            reconnect_attempts_total,
+            reconnect_duration_seconds,
+            rtt_seconds,
+            start_time,
             bytes_in_total: AtomicU64::new(0),
             bytes_out_total: AtomicU64::new(0),
             pkt_in_total: AtomicU64::new(0),
             pkt_out_total: AtomicU64::new(0),
             timeouts_total: AtomicU64::new(0),
             active_relays: AtomicU64::new(0),
+            pkt_rcv_loss_total: AtomicU64::new(0),
+            pkt_rcv_drop_total: AtomicU64::new(0),
+            pkt_reordered_total: AtomicU64::new(0),
+            relays: Mutex::new(HashMap::new()),
+            detached_relays: Mutex::new(HashMap::new()),
+            relay_labels,
+            sample_epoch: AtomicU64::new(0),
         }
     }
 
@@ -72,14 +455,374 @@ impl Metrics {
         GLOBAL_METRICS.get()
     }
 
-    pub fn gather_text(&self) -> String {
-        let metric_families = self.registry.gather();
+    // Export des familles retenues par `scope` (GET /metrics?only=relay|http), triées par préfixe de nom
+    pub fn gather_text(&self, scope: MetricsScope) -> String {
+        self.refresh_scrape_gauges();
+        let mut metric_families = self.registry.gather();
+        metric_families.retain(|family| scope.includes(family.get_name()));
+        let mut buffer = Vec::new();
+        let encoder = TextEncoder::new();
+        encoder.encode(&metric_families, &mut buffer).expect("encode metrics");
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+
+    // Séries d'un seul relais (GET /relays/<id>/metrics): échantillons étiquetés relay_id=<id>, familles
+    // sans échantillon écartées; l'étiquette relay_id est conservée pour les scrapers par cible
+    pub fn gather_relay_text(&self, relay_id: &str) -> String {
+        self.refresh_scrape_gauges();
+        let mut metric_families = self.registry.gather();
+        for family in metric_families.iter_mut() {
+            let kept: Vec<_> = family
+                .take_metric()
+                .into_iter()
+                .filter(|m| m.get_label().iter().any(|l| l.get_name() == "relay_id" && l.get_value() == relay_id))
+                .collect();
+            family.set_metric(kept.into());
+        }
+        metric_families.retain(|family| !family.get_metric().is_empty());
         let mut buffer = Vec::new();
         let encoder = TextEncoder::new();
         encoder.encode(&metric_families, &mut buffer).expect("encode metrics");
         String::from_utf8(buffer).unwrap_or_default()
     }
 
+    // Jauges dérivées de l'horloge, recalculées à chaque scrape pour rester exactes entre deux mises à jour
+    fn refresh_scrape_gauges(&self) {
+        let relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        for stats in relays.values() {
+            self.seconds_since_last_recv
+                .with_label_values(&[&stats.relay_id, stats.protocols.input])
+                .set(stats.seconds_since_last_recv());
+        }
+    }
+
+    // Remet à zéro les atomiques runtime derrière /stats (bytes_*, pkt_*, timeouts, pertes, réordonnancements).
+    // Les séries Prometheus enregistrées restent intactes (compteurs monotones). active_relays n'est pas un
+    // compteur et n'est donc pas concerné.
+    pub fn reset_io_counters(&self) {
+        for counter in [
+            &self.bytes_in_total,
+            &self.bytes_out_total,
+            &self.pkt_in_total,
+            &self.pkt_out_total,
+            &self.timeouts_total,
+            &self.pkt_rcv_loss_total,
+            &self.pkt_rcv_drop_total,
+            &self.pkt_reordered_total,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        // Évite un débit instantané nul au prochain échantillon
+        let mut sample = self.rate_sample.lock().unwrap_or_else(|e| e.into_inner());
+        sample.bytes_in = 0;
+        sample.bytes_out = 0;
+    }
+
+    // Débits (bps entrant, bps sortant) depuis l'échantillon précédent; l'échantillon est ensuite remplacé
+    pub fn instantaneous_rates(&self) -> (f64, f64) {
+        let now = Instant::now();
+        let bytes_in = self.bytes_in_total.load(Ordering::Relaxed);
+        let bytes_out = self.bytes_out_total.load(Ordering::Relaxed);
+        let mut prev = self.rate_sample.lock().unwrap_or_else(|e| e.into_inner());
+        let secs = now.duration_since(prev.at).as_secs_f64();
+        let rates = if secs > 0.0 {
+            (
+                bytes_in.saturating_sub(prev.bytes_in) as f64 * 8.0 / secs,
+                bytes_out.saturating_sub(prev.bytes_out) as f64 * 8.0 / secs,
+            )
+        } else {
+            (0.0, 0.0)
+        };
+        *prev = RateSample { at: now, bytes_in, bytes_out };
+        rates
+    }
+
+    // Unique tâche d'échantillonnage (--sample-interval-ms): à chaque passage elle rafraîchit les
+    // jauges dérivées (current_bps_*, gigue par relais) puis avance sample_epoch, que chaque pipe
+    // surveille pour publier ce que lui seul peut lire (occupation des buffers de ses sockets).
+    // Un scrape Prometheus lit la dernière valeur échantillonnée: un intervalle plus court que celui
+    // du scrape coûte du CPU sans rien montrer de plus, un intervalle plus long répète les mêmes valeurs.
+    pub fn spawn_sampler(self: Arc<Self>, period: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                self.sample();
+            }
+        })
+    }
+
+    // Ligne relay_stats périodique par relais actif (--relay-stats-interval-secs), pour qui suit les
+    // journaux plutôt que /metrics: trafic depuis la ligne précédente, débit lissé, pertes et RTT
+    pub fn spawn_relay_summaries(self: Arc<Self>, period: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // Le premier tick est immédiat: la première ligne couvre une période entière
+            ticker.tick().await;
+            let mut previous: HashMap<String, RelayTotals> = HashMap::new();
+            loop {
+                ticker.tick().await;
+                self.log_relay_summaries(&mut previous);
+            }
+        })
+    }
+
+    fn log_relay_summaries(&self, previous: &mut HashMap<String, RelayTotals>) {
+        let relays = self.relay_snapshots();
+        previous.retain(|id, _| relays.iter().any(|r| &r.relay_id == id));
+        for relay in relays {
+            let totals = RelayTotals::of(&relay);
+            let delta = totals.since(&previous.insert(relay.relay_id.clone(), totals).unwrap_or_default());
+            let rtt_ms = [relay.input_rtt_ms, relay.output_rtt_ms].into_iter().flatten().fold(0.0, f64::max);
+            info!(event = events::RELAY_STATS, subsystem = relay.input_protocol, protocol = relay.input_protocol, output_protocol = relay.output_protocol, relay_id = %relay.relay_id, bytes_in = delta.bytes_in, packets_in = delta.packets_in, bytes_out = delta.bytes_out, packets_out = delta.packets_out, mbps = relay.bitrate_bps as f64 / 1_000_000.0, lost = delta.lost, dropped = delta.dropped, rtt_ms = rtt_ms, msg = "Relay traffic summary");
+        }
+    }
+
+    // Superviseur de vivacité (--stall-threshold-secs): un pipe dont la boucle n'a pas tourné depuis
+    // `threshold` (tâche bloquée dans un appel qui ne rend pas la main) est signalé "stalled" dans /health
+    // et /relays; active_relays ne le révèle pas. Tourne sur un autre worker que le pipe bloqué.
+    pub fn spawn_stall_supervisor(self: Arc<Self>, threshold: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(threshold.min(Duration::from_secs(1)));
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                self.check_heartbeats(threshold);
+            }
+        })
+    }
+
+    // Les relais sont copiés puis le verrou relâché: journaliser ne bloque pas les pipes qui s'enregistrent
+    fn check_heartbeats(&self, threshold: Duration) {
+        let relays: Vec<Arc<RelayStats>> = self.relays.lock().unwrap_or_else(|e| e.into_inner()).values().cloned().collect();
+        for stats in &relays {
+            let age = stats.heartbeat_age();
+            let stalled = age >= threshold;
+            self.relay_stalled.with_label_values(&[&stats.relay_id]).set(i64::from(stalled));
+            if stats.set_stalled(stalled) == stalled {
+                continue;
+            }
+            let protocol = stats.protocols.input;
+            if stalled {
+                warn!(event = events::RELAY_STALLED, subsystem = protocol, protocol = protocol, relay_id = %stats.relay_id, heartbeat_age_ms = age.as_millis() as u64, threshold_ms = threshold.as_millis() as u64, msg = "Pipe loop has not run within the stall threshold; relay task may be deadlocked");
+            } else {
+                info!(event = events::RELAY_STALL_CLEARED, subsystem = protocol, protocol = protocol, relay_id = %stats.relay_id, msg = "Pipe loop running again");
+            }
+        }
+    }
+
+    // Pipes en cours signalés par le superviseur, triés
+    pub fn stalled_relays(&self) -> Vec<String> {
+        let relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out: Vec<String> = relays.values().filter(|r| r.stalled()).map(|r| r.relay_id.clone()).collect();
+        out.sort();
+        out
+    }
+
+    pub fn relay_stalled(&self, relay_id: &str) -> bool {
+        self.relays.lock().unwrap_or_else(|e| e.into_inner()).get(relay_id).is_some_and(|r| r.stalled())
+    }
+
+    // Datagramme reçu perdu sur un échec d'envoi: compté par protocole, et comme drop (pktRcvDrop)
+    pub fn record_send_failure(&self, stats: Option<&RelayStats>, protocol: &str, len: usize) {
+        self.count_send_failure(protocol, len);
+        self.inc_pkt_drop();
+        if let Some(stats) = stats {
+            stats.add_dropped(1);
+        }
+    }
+
+    // Envoi en échec par protocole seulement: un keep-alive n'a pas été reçu, il n'est pas un drop
+    pub fn count_send_failure(&self, protocol: &str, len: usize) {
+        self.pkt_dropped_send_total.with_label_values(&[protocol]).inc();
+        self.bytes_dropped_send_total.with_label_values(&[protocol]).inc_by(len as u64);
+    }
+
+    fn sample(&self) {
+        let (bps_in, bps_out) = self.instantaneous_rates();
+        self.current_bps_in.set(bps_in as i64);
+        self.current_bps_out.set(bps_out as i64);
+        for stats in self.relays.lock().unwrap_or_else(|e| e.into_inner()).values() {
+            stats.sample_rate();
+            self.record_jitter(stats);
+        }
+        self.sample_epoch.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_relay_labels(&self, relay_id: &str, labels: HashMap<String, String>) {
+        let mut by_relay = self.relay_labels.lock().unwrap_or_else(|e| e.into_inner());
+        if labels.is_empty() {
+            by_relay.remove(relay_id);
+        } else {
+            by_relay.insert(relay_id.to_string(), labels);
+        }
+    }
+
+    pub fn relay_labels(&self, relay_id: &str) -> Option<HashMap<String, String>> {
+        self.relay_labels.lock().unwrap_or_else(|e| e.into_inner()).get(relay_id).cloned()
+    }
+
+    pub fn sample_epoch(&self) -> u64 {
+        self.sample_epoch.load(Ordering::Relaxed)
+    }
+
+    // Enregistre (ou ré-arme en cas de reconnexion) les stats d'un relais
+    pub fn register_relay(&self, relay_id: &str, protocols: RelayProtocols) -> Arc<RelayStats> {
+        let resumed = self.detached_relays.lock().unwrap_or_else(|e| e.into_inner()).get_mut(relay_id).and_then(Option::take);
+        let mut relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = relays
+            .entry(relay_id.to_string())
+            .or_insert_with(|| {
+                let labels = [protocols.input, protocols.output];
+                self.relays_active.with_label_values(&labels).inc();
+                match resumed {
+                    Some(stats) => {
+                        stats.mark_restart();
+                        stats
+                    }
+                    None => Arc::new(RelayStats::new(
+                        relay_id,
+                        protocols,
+                        self.relay_bytes_in_total.with_label_values(&labels),
+                        self.relay_bytes_out_total.with_label_values(&labels),
+                    )),
+                }
+            })
+            .clone();
+        stats.mark_start();
+        stats
+    }
+
+    pub fn unregister_relay(&self, relay_id: &str) {
+        let removed = self.relays.lock().unwrap_or_else(|e| e.into_inner()).remove(relay_id);
+        if let Some(stats) = removed {
+            let _ = self.recv_buffer_bytes.remove_label_values(&[relay_id]);
+            let _ = self.send_buffer_bytes.remove_label_values(&[relay_id]);
+            let _ = self.recv_buffer_size_bytes.remove_label_values(&[relay_id]);
+            let _ = self.relay_jitter_ms.remove_label_values(&[relay_id]);
+            let _ = self.seconds_since_last_recv.remove_label_values(&[relay_id, stats.protocols.input]);
+            let _ = self.relay_rate_limit_bps.remove_label_values(&[relay_id]);
+            let _ = self.relay_rate_limit_pps.remove_label_values(&[relay_id]);
+            let _ = self.relay_throttled_seconds_total.remove_label_values(&[relay_id]);
+            let _ = self.relay_overrun.remove_label_values(&[relay_id]);
+            let _ = self.relay_stalled.remove_label_values(&[relay_id]);
+            self.relays_active.with_label_values(&[stats.protocols.input, stats.protocols.output]).dec();
+            if let Some(slot) = self.detached_relays.lock().unwrap_or_else(|e| e.into_inner()).get_mut(relay_id) {
+                *slot = Some(stats);
+            }
+        }
+    }
+
+    // Début et fin de vie d'un relais reconnectable: entre les deux, ses stats survivent aux pipes
+    pub fn track_relay(&self, relay_id: &str) {
+        self.detached_relays.lock().unwrap_or_else(|e| e.into_inner()).insert(relay_id.to_string(), None);
+    }
+
+    pub fn untrack_relay(&self, relay_id: &str) {
+        self.detached_relays.lock().unwrap_or_else(|e| e.into_inner()).remove(relay_id);
+    }
+
+    // Stats d'un relais, pipe en cours ou en attente de reconnexion
+    fn relay_stats(&self, relay_id: &str) -> Option<Arc<RelayStats>> {
+        if let Some(stats) = self.relays.lock().unwrap_or_else(|e| e.into_inner()).get(relay_id) {
+            return Some(stats.clone());
+        }
+        self.detached_relays.lock().unwrap_or_else(|e| e.into_inner()).get(relay_id).cloned().flatten()
+    }
+
+    pub fn record_reconnect_attempt(&self, relay_id: &str) {
+        self.reconnect_attempts_total.inc();
+        if let Some(stats) = self.relay_stats(relay_id) {
+            stats.add_reconnect_attempt();
+        }
+    }
+
+    // (redémarrages du pipe, tentatives de reconnexion) depuis le lancement du relais
+    pub fn relay_restarts(&self, relay_id: &str) -> Option<(u64, u64)> {
+        self.relay_stats(relay_id).map(|s| (s.restart_count(), s.total_reconnects()))
+    }
+
+    pub fn record_buffer_occupancy(&self, stats: &RelayStats, input: Option<BufferOccupancy>, output: Option<BufferOccupancy>) {
+        stats.set_buffer_occupancy(input, output);
+        if let Some(o) = input {
+            self.recv_buffer_bytes.with_label_values(&[&stats.relay_id]).set(saturating_i64(o.recv_bytes));
+        }
+        if let Some(o) = output {
+            self.send_buffer_bytes.with_label_values(&[&stats.relay_id]).set(saturating_i64(o.send_bytes));
+        }
+    }
+
+    // Chaque mesure nouvelle alimente l'histogramme; la dernière reste lisible dans /stats
+    pub fn record_rtt(&self, stats: &RelayStats, side: RttSide, rtt: Duration) {
+        stats.set_rtt(side, rtt);
+        self.rtt_seconds.with_label_values(&[side.as_str()]).observe(rtt.as_secs_f64());
+    }
+
+    pub fn record_recv_buffer_len(&self, stats: &RelayStats, len: usize) {
+        self.recv_buffer_size_bytes.with_label_values(&[&stats.relay_id]).set(len as i64);
+    }
+
+    pub fn record_jitter(&self, stats: &RelayStats) {
+        self.relay_jitter_ms.with_label_values(&[&stats.relay_id]).set(stats.jitter_ms());
+    }
+
+    // Seuls les plafonds configurés sont exposés: une série absente signifie "illimité"
+    pub fn record_rate_limit(&self, stats: &RelayStats, cfg: RateLimitConfig) {
+        stats.set_rate_limit(cfg);
+        // Un plafond levé en cours de vie (PipeCommand::SetRateLimit) retire la série
+        match cfg.max_bitrate {
+            Some(bps) => self.relay_rate_limit_bps.with_label_values(&[&stats.relay_id]).set(saturating_i64(bps)),
+            None => drop(self.relay_rate_limit_bps.remove_label_values(&[&stats.relay_id])),
+        }
+        match cfg.max_pps {
+            Some(pps) => self.relay_rate_limit_pps.with_label_values(&[&stats.relay_id]).set(saturating_i64(pps)),
+            None => drop(self.relay_rate_limit_pps.remove_label_values(&[&stats.relay_id])),
+        }
+    }
+
+    pub fn record_throttled(&self, stats: &RelayStats, waited: Duration) {
+        stats.add_throttled(waited);
+        self.relay_throttled_seconds_total.with_label_values(&[&stats.relay_id]).inc_by(waited.as_secs_f64());
+    }
+
+    // Sans plafond configuré, pas de série relay_overrun
+    pub fn record_overrun(&self, stats: &RelayStats, ceiling_bps: Option<u64>, active: bool) {
+        stats.set_overrun(ceiling_bps, active);
+        match ceiling_bps {
+            Some(_) => self.relay_overrun.with_label_values(&[&stats.relay_id]).set(active as i64),
+            None => drop(self.relay_overrun.remove_label_values(&[&stats.relay_id])),
+        }
+    }
+
+    pub fn relay_overrun(&self, relay_id: &str) -> bool {
+        self.relay_stats(relay_id).is_some_and(|s| s.overrun())
+    }
+
+    // Taille moyenne des paquets reçus depuis le démarrage (0 si rien reçu)
+    pub fn avg_pkt_size_in(&self) -> f64 {
+        let pkts = self.pkt_in_total.load(Ordering::Relaxed);
+        if pkts == 0 { 0.0 } else { self.bytes_in_total.load(Ordering::Relaxed) as f64 / pkts as f64 }
+    }
+
+    // Octets en attente de lecture, tous relais confondus
+    pub fn total_recv_buffer_bytes(&self) -> u64 {
+        saturating_sum(self.relays.lock().unwrap_or_else(|e| e.into_inner()).values().map(|r| r.recv_buffer_bytes()))
+    }
+
+    pub fn relay_snapshots(&self) -> Vec<RelayStatsEntry> {
+        let relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out: Vec<RelayStatsEntry> = relays.values().map(|r| r.snapshot()).collect();
+        out.sort_by(|a, b| a.relay_id.cmp(&b.relay_id));
+        out
+    }
+
+    pub fn relay_timing(&self, relay_id: &str) -> Option<PipeTimingsEntry> {
+        let relays = self.relays.lock().unwrap_or_else(|e| e.into_inner());
+        relays.get(relay_id).map(|r| r.timings_snapshot())
+    }
+
     // Convenience helpers
     #[inline]
     pub fn inc_active_relays(&self) { self.active_relays.fetch_add(1, Ordering::SeqCst); }
@@ -95,6 +838,33 @@ impl Metrics {
     pub fn inc_pkt_out(&self) { self.pkt_out_total.fetch_add(1, Ordering::Relaxed); }
     #[inline]
     pub fn inc_timeout(&self) { self.timeouts_total.fetch_add(1, Ordering::Relaxed); }
+    #[inline]
+    pub fn add_pkt_loss(&self, n: u64) { self.pkt_rcv_loss_total.fetch_add(n, Ordering::Relaxed); }
+    #[inline]
+    pub fn inc_pkt_drop(&self) { self.pkt_rcv_drop_total.fetch_add(1, Ordering::Relaxed); }
+    #[inline]
+    pub fn inc_pkt_reordered(&self) { self.pkt_reordered_total.fetch_add(1, Ordering::Relaxed); }
+}
+
+// Compteur u64 vers les jauges Prometheus et les champs i64 de /stats: plafonné à i64::MAX plutôt que
+// de passer en négatif comme `as i64` (un plafond ?max_bitrate démesuré, un compteur resté très longtemps actif)
+pub fn saturating_i64(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+// Somme de compteurs u64 bornée à u64::MAX (sum() déborde, et panique en debug)
+pub fn saturating_sum(values: impl IntoIterator<Item = u64>) -> u64 {
+    values.into_iter().fold(0, u64::saturating_add)
+}
+
+// Estimation (en ms) de la durée de flux que représente un buffer de `buffer_bytes` au rythme actuel:
+// pps paquets/s de avg_pkt_size octets. Repli quand l'occupation réelle n'est pas mesurable; 0 si inactif.
+pub fn estimate_receive_buffer_ms(pps: f64, avg_pkt_size: f64, buffer_bytes: u64) -> i64 {
+    let bytes_per_sec = pps * avg_pkt_size;
+    if bytes_per_sec <= 0.0 {
+        return 0;
+    }
+    (buffer_bytes as f64 / bytes_per_sec * 1000.0).round() as i64
 }
 
 // Buckets d'histogramme adaptés à des latences HTTP (secondes)
@@ -103,4 +873,151 @@ fn duration_buckets() -> Vec<f64> {
         0.005, 0.01, 0.025, 0.05, 0.1, 0.25,
         0.5, 1.0, 2.5, 5.0,
     ]
-}
\ No newline at end of file
+}
+
+// Exponentiels de 1 ms à ~4 s: du LAN au lien satellite
+fn rtt_buckets() -> Vec<f64> {
+    prometheus::exponential_buckets(0.001, 2.0, 13).expect("valid rtt buckets")
+}
+
+// Buckets pour les durées de déconnexion (secondes), du glitch réseau à la panne longue
+fn reconnect_buckets() -> Vec<f64> {
+    vec![
+        0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+        60.0, 120.0, 300.0, 900.0,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+    use super::{estimate_receive_buffer_ms, saturating_i64, saturating_sum, Metrics, MetricsScope};
+    use crate::structures::RelayProtocols;
+
+    #[test]
+    fn estimate_uses_rate_and_buffer_size() {
+        // 1000 pkt/s de 1316 octets = 1.316 Mo/s; 131600 octets de buffer = 100 ms
+        assert_eq!(estimate_receive_buffer_ms(1000.0, 1316.0, 131_600), 100);
+        assert_eq!(estimate_receive_buffer_ms(500.0, 1316.0, 131_600), 200);
+    }
+
+    #[test]
+    fn estimate_is_zero_when_idle() {
+        assert_eq!(estimate_receive_buffer_ms(0.0, 1316.0, 131_600), 0);
+        assert_eq!(estimate_receive_buffer_ms(1000.0, 0.0, 131_600), 0);
+    }
+
+    #[test]
+    fn conversions_saturate_instead_of_wrapping() {
+        assert_eq!(saturating_i64(42), 42);
+        assert_eq!(saturating_i64(u64::MAX), i64::MAX);
+        assert_eq!(saturating_sum([u64::MAX - 1, 5]), u64::MAX);
+        // Compteur remis à zéro entre deux échantillons: débit nul, pas de bouclage
+        let m = Metrics::new();
+        m.add_bytes_in(1_000_000);
+        m.instantaneous_rates();
+        m.reset_io_counters();
+        assert_eq!(m.instantaneous_rates().0, 0.0);
+    }
+
+    #[test]
+    fn sample_publishes_jitter_and_advances_epoch() {
+        let m = Metrics::new();
+        let stats = m.register_relay("r1", RelayProtocols { input: "srt", output: "srt" });
+        stats.set_jitter_ms(2.5);
+        assert_eq!(m.sample_epoch(), 0);
+        m.sample();
+        assert_eq!(m.sample_epoch(), 1);
+        assert_eq!(m.relay_jitter_ms.with_label_values(&["r1"]).get(), 2.5);
+    }
+
+    #[test]
+    fn send_failures_count_the_lost_datagram() {
+        let m = Metrics::new();
+        let stats = m.register_relay("r1", RelayProtocols { input: "srt", output: "rist" });
+        m.record_send_failure(Some(&stats), "rist", 1316);
+        assert_eq!(m.pkt_dropped_send_total.with_label_values(&["rist"]).get(), 1);
+        assert_eq!(m.bytes_dropped_send_total.with_label_values(&["rist"]).get(), 1316);
+        assert_eq!(m.pkt_rcv_drop_total.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.snapshot().packets_dropped, 1);
+    }
+
+    #[test]
+    fn relay_labels_are_exported_per_relay() {
+        let m = Metrics::new();
+        m.set_relay_labels("r1", [("customer".to_string(), "acme".to_string())].into());
+        m.set_relay_labels("r2", [("event".to_string(), "finals".to_string())].into());
+        let text = m.gather_text(MetricsScope::All);
+        assert!(text.contains(r#"relay_labels{customer="acme",relay_id="r1"} 1"#), "{}", text);
+        assert!(text.contains(r#"relay_labels{event="finals",relay_id="r2"} 1"#));
+        m.set_relay_labels("r1", HashMap::new());
+        assert!(!m.gather_text(MetricsScope::All).contains("acme"));
+    }
+
+    #[test]
+    fn supervisor_flags_a_stale_heartbeat() {
+        let m = Metrics::new();
+        let stats = m.register_relay("r1", RelayProtocols { input: "srt", output: "srt" });
+        m.check_heartbeats(Duration::from_secs(60));
+        assert!(m.stalled_relays().is_empty());
+        std::thread::sleep(Duration::from_millis(30));
+        m.check_heartbeats(Duration::from_millis(20));
+        assert_eq!(m.stalled_relays(), vec!["r1".to_string()]);
+        assert!(m.relay_stalled("r1"));
+        assert!(m.gather_text(MetricsScope::All).contains(r#"relay_stalled{relay_id="r1"} 1"#));
+        stats.beat();
+        m.check_heartbeats(Duration::from_millis(20));
+        assert!(!m.relay_stalled("r1"));
+        // Attente du limiteur de débit: pas un blocage tant qu'elle dure
+        stats.beat_through(Duration::from_millis(200));
+        std::thread::sleep(Duration::from_millis(30));
+        m.check_heartbeats(Duration::from_millis(20));
+        assert!(!m.relay_stalled("r1"));
+    }
+
+    #[test]
+    fn relay_export_keeps_only_that_relay() {
+        let m = Metrics::new();
+        m.set_relay_labels("r1", [("customer".to_string(), "acme".to_string())].into());
+        m.set_relay_labels("r2", [("customer".to_string(), "globex".to_string())].into());
+        m.http_requests_total.with_label_values(&["GET", "200"]).inc();
+        let text = m.gather_relay_text("r1");
+        assert!(text.contains(r#"relay_labels{customer="acme",relay_id="r1"} 1"#), "{}", text);
+        assert!(!text.contains("r2") && !text.contains("http_requests_total") && !text.contains("uptime_seconds"));
+        assert!(m.gather_relay_text("r3").is_empty());
+    }
+
+    #[test]
+    fn scoped_export_drops_the_http_series() {
+        let m = Metrics::new();
+        m.http_requests_total.with_label_values(&["GET", "200"]).inc();
+        let relay = m.gather_text(MetricsScope::Relay);
+        assert!(!relay.contains("http_requests_total"));
+        assert!(relay.contains("uptime_seconds"));
+        let http = m.gather_text(MetricsScope::Http);
+        assert!(http.contains("http_requests_total") && !http.contains("uptime_seconds"));
+        assert!(m.gather_text(MetricsScope::All).contains("http_requests_total"));
+        assert!(MetricsScope::parse(Some("transport")).is_err());
+    }
+
+    #[test]
+    fn reconnect_resumes_the_relay_stats() {
+        let m = Metrics::new();
+        let protocols = RelayProtocols { input: "srt", output: "srt" };
+        m.track_relay("r1");
+        m.register_relay("r1", protocols).mark_recv(1316);
+        m.unregister_relay("r1");
+        m.record_reconnect_attempt("r1");
+        m.record_reconnect_attempt("r1");
+        let stats = m.register_relay("r1", protocols);
+        assert_eq!(stats.snapshot().bytes_recv, 1316);
+        assert_eq!(m.relay_restarts("r1"), Some((1, 2)));
+        m.unregister_relay("r1");
+        m.untrack_relay("r1");
+        assert_eq!(m.relay_restarts("r1"), None);
+        // Relais non suivi (selftest): chaque pipe repart de zéro
+        assert_eq!(m.register_relay("r1", protocols).snapshot().bytes_recv, 0);
+    }
+}
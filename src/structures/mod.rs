@@ -2,8 +2,14 @@ pub mod health;
 pub mod stats_data;
 pub mod metrics;
 pub mod error;
+pub mod relay_stats;
+pub mod run_summary;
+pub mod statsd;
 
-pub use health::HealthResponse;
-pub use stats_data::{StatsData, StatsResponse};
-pub use metrics::Metrics;
+pub use health::{HealthResponse, ReadyResponse, RelayCounts, RelayFailure, RelayReadiness};
+pub use stats_data::{GroupedStatsResponse, StatsFields, StatsResponse};
+pub use metrics::{Metrics, MetricsScope};
+pub use run_summary::RunSummary;
+pub use relay_stats::{BitrateThresholds, RelayProtocols, RelayStats, RelayStatsEntry, RttSide};
+pub use relay_stats::PipeTimingsEntry;
 pub use error::{TransportError, TResult};
@@ -0,0 +1,555 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use prometheus::IntCounter;
+use serde::Serialize;
+
+use crate::relay::ratelimit::RateLimitConfig;
+use crate::relay::transport::{BufferOccupancy, EffectiveOptions};
+
+// Protocoles de chaque côté du relais; différents lors d'un pont (ex: srt -> rist)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelayProtocols {
+    pub input: &'static str,
+    pub output: &'static str,
+}
+
+// Constante de temps du débit lissé par relais (moyenne exponentielle)
+const RATE_SMOOTHING: Duration = Duration::from_secs(5);
+
+// État du débit lissé, avancé par le sampler partagé
+struct SmoothedRate {
+    at: Instant,
+    bytes: u64,
+    bps: Option<f64>,
+}
+
+// Seuils d'alerte de débit entrant (bits/s) fixés à la création du relais
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct BitrateThresholds {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_bps: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_bps: Option<u64>,
+}
+
+impl BitrateThresholds {
+    // "ok" sans seuil configuré
+    pub fn status(&self, bps: f64) -> &'static str {
+        match (self.min_bps, self.max_bps) {
+            (Some(min), _) if bps < min as f64 => "under",
+            (_, Some(max)) if bps > max as f64 => "over",
+            _ => "ok",
+        }
+    }
+}
+
+// Extrémité du relais ayant fourni une mesure de RTT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RttSide {
+    Input,
+    Output,
+}
+
+impl RttSide {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RttSide::Input => "input",
+            RttSide::Output => "output",
+        }
+    }
+}
+
+// Compteurs de boucle du pipe (GET /relays/<id>, GET /debug/tasks): tours de boucle, temps passé dans
+// recv (attente comprise) et dans send, timeouts de lecture. Quelques opérations atomiques par
+// datagramme, donc toujours actifs.
+#[derive(Default)]
+pub struct PipeTimings {
+    pub iterations: AtomicU64,
+    pub recv_ns: AtomicU64,
+    pub send_ns: AtomicU64,
+    pub timeouts: AtomicU64,
+    pub packets: AtomicU64,
+}
+
+impl PipeTimings {
+    pub fn add(&self, counter: &AtomicU64, elapsed: Duration) {
+        counter.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn reset(&self) {
+        for counter in [&self.iterations, &self.recv_ns, &self.send_ns, &self.timeouts, &self.packets] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+// Un relais sain fait environ un tour de boucle par datagramme (plus un par timeout de lecture):
+// iterations_per_sec très au-dessus de packets_per_sec signale une attente active
+#[derive(Debug, Clone, Serialize)]
+pub struct PipeTimingsEntry {
+    pub relay_id: String,
+    pub loop_iterations: u64,
+    pub packets: u64,
+    pub timeouts: u64,
+    pub recv_ms: f64,
+    pub send_ms: f64,
+    pub iterations_per_sec: f64,
+    pub packets_per_sec: f64,
+}
+
+// Statistiques propres à un relais (une instance de pipe, ou toute sa vie pour un relais reconnectable),
+// indexées par relay_id dans Metrics
+pub struct RelayStats {
+    pub relay_id: String,
+    pub protocols: RelayProtocols,
+    // Compteurs Prometheus déjà résolus pour les labels (input_protocol, output_protocol)
+    pub bytes_in: IntCounter,
+    pub bytes_out: IntCounter,
+    started_at: Mutex<Instant>,
+    first_byte_at: Mutex<Option<Instant>>,
+    // Dernière réception, en ns depuis created_at (0 = rien reçu depuis le (re)démarrage)
+    created_at: Instant,
+    last_recv_ns: AtomicU64,
+    // Dernier tour de boucle du pipe, en ns depuis created_at: figé quand la tâche est bloquée
+    heartbeat_ns: AtomicU64,
+    // Battement trop ancien, posé par le superviseur (Metrics::spawn_stall_supervisor)
+    stalled: AtomicBool,
+    // Options effectives (entrée, sortie) relues à l'ouverture du pipe
+    options: Mutex<(EffectiveOptions, EffectiveOptions)>,
+    // Dernière occupation mesurée des buffers (réception côté entrée, émission côté sortie)
+    recv_buffer_bytes: AtomicU64,
+    send_buffer_bytes: AtomicU64,
+    // Gigue d'arrivée lissée, en microsecondes
+    jitter_us: AtomicU64,
+    // Plafond d'émission configuré et temps passé à attendre pour le respecter
+    rate_limit: Mutex<RateLimitConfig>,
+    throttled_us: AtomicU64,
+    // Plafond de débit entrant (?overrun_bitrate, 0 = aucun) et état d'alarme courant
+    overrun_ceiling_bps: AtomicU64,
+    overrun: AtomicBool,
+    // Octets reçus par ce seul relais (bytes_in est partagé par les relais de même sens de pont)
+    recv_bytes: AtomicU64,
+    // Compteurs propres au relais, cumulés depuis son lancement (reconnexions comprises, voir
+    // Metrics::track_relay)
+    recv_packets: AtomicU64,
+    sent_bytes: AtomicU64,
+    sent_packets: AtomicU64,
+    lost_packets: AtomicU64,
+    dropped_packets: AtomicU64,
+    keepalives_sent: AtomicU64,
+    rate: Mutex<SmoothedRate>,
+    // Pipes rouverts après une erreur et tentatives de reconnexion (réussies ou non)
+    restart_count: AtomicU64,
+    total_reconnects: AtomicU64,
+    // Dernier RTT mesuré de chaque côté, en microsecondes (0 = aucune mesure)
+    input_rtt_us: AtomicU64,
+    output_rtt_us: AtomicU64,
+    pub timings: PipeTimings,
+}
+
+impl RelayStats {
+    pub fn new(relay_id: &str, protocols: RelayProtocols, bytes_in: IntCounter, bytes_out: IntCounter) -> Self {
+        Self {
+            relay_id: relay_id.to_string(),
+            protocols,
+            bytes_in,
+            bytes_out,
+            started_at: Mutex::new(Instant::now()),
+            first_byte_at: Mutex::new(None),
+            created_at: Instant::now(),
+            last_recv_ns: AtomicU64::new(0),
+            heartbeat_ns: AtomicU64::new(0),
+            stalled: AtomicBool::new(false),
+            options: Mutex::new(Default::default()),
+            recv_buffer_bytes: AtomicU64::new(0),
+            send_buffer_bytes: AtomicU64::new(0),
+            jitter_us: AtomicU64::new(0),
+            rate_limit: Mutex::new(RateLimitConfig::default()),
+            throttled_us: AtomicU64::new(0),
+            overrun_ceiling_bps: AtomicU64::new(0),
+            overrun: AtomicBool::new(false),
+            recv_bytes: AtomicU64::new(0),
+            recv_packets: AtomicU64::new(0),
+            sent_bytes: AtomicU64::new(0),
+            sent_packets: AtomicU64::new(0),
+            lost_packets: AtomicU64::new(0),
+            dropped_packets: AtomicU64::new(0),
+            keepalives_sent: AtomicU64::new(0),
+            rate: Mutex::new(SmoothedRate { at: Instant::now(), bytes: 0, bps: None }),
+            restart_count: AtomicU64::new(0),
+            total_reconnects: AtomicU64::new(0),
+            input_rtt_us: AtomicU64::new(0),
+            output_rtt_us: AtomicU64::new(0),
+            timings: PipeTimings::default(),
+        }
+    }
+
+    // (Re)démarrage du relais: repart de zéro pour le time-to-first-byte
+    pub fn mark_start(&self) {
+        *self.started_at.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+        *self.first_byte_at.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        self.last_recv_ns.store(0, Ordering::Relaxed);
+        self.beat();
+        self.stalled.store(false, Ordering::Relaxed);
+    }
+
+    // Appelé à chaque tour de boucle du pipe
+    pub fn beat(&self) {
+        self.heartbeat_ns.store(self.created_at.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    // Attente voulue de la boucle (limiteur de débit): le battement couvre l'attente, qui ne compte pas
+    // comme un blocage pour le superviseur
+    pub fn beat_through(&self, wait: Duration) {
+        self.heartbeat_ns.store((self.created_at.elapsed() + wait).as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn heartbeat_age(&self) -> Duration {
+        self.created_at.elapsed().saturating_sub(Duration::from_nanos(self.heartbeat_ns.load(Ordering::Relaxed)))
+    }
+
+    // Renvoie l'état précédent, pour ne journaliser que les changements
+    pub fn set_stalled(&self, stalled: bool) -> bool {
+        self.stalled.swap(stalled, Ordering::Relaxed)
+    }
+
+    pub fn stalled(&self) -> bool {
+        self.stalled.load(Ordering::Relaxed)
+    }
+
+    // Reprise par le pipe suivant d'un relais reconnecté: les mesures de boucle repartent de zéro,
+    // les totaux sont conservés
+    pub fn mark_restart(&self) {
+        self.restart_count.fetch_add(1, Ordering::Relaxed);
+        self.timings.reset();
+    }
+
+    pub fn add_reconnect_attempt(&self) {
+        self.total_reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn restart_count(&self) -> u64 {
+        self.restart_count.load(Ordering::Relaxed)
+    }
+
+    pub fn total_reconnects(&self) -> u64 {
+        self.total_reconnects.load(Ordering::Relaxed)
+    }
+
+    // Appelé à chaque recv réussi: mémorise le premier et le dernier
+    pub fn mark_recv(&self, len: usize) {
+        self.recv_bytes.fetch_add(len as u64, Ordering::Relaxed);
+        self.recv_packets.fetch_add(1, Ordering::Relaxed);
+        let ns = self.created_at.elapsed().as_nanos().max(1) as u64;
+        self.last_recv_ns.store(ns, Ordering::Relaxed);
+        let mut first = self.first_byte_at.lock().unwrap_or_else(|e| e.into_inner());
+        if first.is_none() {
+            *first = Some(Instant::now());
+        }
+    }
+
+    pub fn mark_sent(&self, len: usize) {
+        self.bytes_out.inc_by(len as u64);
+        self.sent_bytes.fetch_add(len as u64, Ordering::Relaxed);
+        if len > 0 {
+            self.sent_packets.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Keep-alive envoyé: compté à part, pas dans bytes_sent
+    pub fn mark_keepalive(&self) {
+        self.keepalives_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_lost(&self, lost: u64) {
+        self.lost_packets.fetch_add(lost, Ordering::Relaxed);
+    }
+
+    pub fn add_dropped(&self, dropped: u64) {
+        self.dropped_packets.fetch_add(dropped, Ordering::Relaxed);
+    }
+
+    pub fn set_options(&self, input: EffectiveOptions, output: EffectiveOptions) {
+        *self.options.lock().unwrap_or_else(|e| e.into_inner()) = (input, output);
+    }
+
+    pub fn set_buffer_occupancy(&self, input: Option<BufferOccupancy>, output: Option<BufferOccupancy>) {
+        self.recv_buffer_bytes.store(input.map(|o| o.recv_bytes).unwrap_or(0), Ordering::Relaxed);
+        self.send_buffer_bytes.store(output.map(|o| o.send_bytes).unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn recv_buffer_bytes(&self) -> u64 {
+        self.recv_buffer_bytes.load(Ordering::Relaxed)
+    }
+
+    fn rtt_slot(&self, side: RttSide) -> &AtomicU64 {
+        match side {
+            RttSide::Input => &self.input_rtt_us,
+            RttSide::Output => &self.output_rtt_us,
+        }
+    }
+
+    pub fn set_rtt(&self, side: RttSide, rtt: Duration) {
+        self.rtt_slot(side).store((rtt.as_micros() as u64).max(1), Ordering::Relaxed);
+    }
+
+    pub fn rtt_ms(&self, side: RttSide) -> Option<f64> {
+        match self.rtt_slot(side).load(Ordering::Relaxed) {
+            0 => None,
+            us => Some(us as f64 / 1000.0),
+        }
+    }
+
+    pub fn set_jitter_ms(&self, jitter_ms: f64) {
+        self.jitter_us.store((jitter_ms * 1000.0) as u64, Ordering::Relaxed);
+    }
+
+    pub fn jitter_ms(&self) -> f64 {
+        self.jitter_us.load(Ordering::Relaxed) as f64 / 1000.0
+    }
+
+    pub fn set_rate_limit(&self, cfg: RateLimitConfig) {
+        *self.rate_limit.lock().unwrap_or_else(|e| e.into_inner()) = cfg;
+    }
+
+    pub fn add_throttled(&self, waited: std::time::Duration) {
+        self.throttled_us.fetch_add(waited.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn set_overrun(&self, ceiling_bps: Option<u64>, active: bool) {
+        self.overrun_ceiling_bps.store(ceiling_bps.unwrap_or(0), Ordering::Relaxed);
+        self.overrun.store(active, Ordering::Relaxed);
+    }
+
+    pub fn overrun(&self) -> bool {
+        self.overrun.load(Ordering::Relaxed)
+    }
+
+    // Silence de l'entrée: depuis la dernière réception, ou depuis le (re)démarrage si rien n'est arrivé
+    pub fn seconds_since_last_recv(&self) -> f64 {
+        match self.last_recv_ns.load(Ordering::Relaxed) {
+            0 => self.started_at.lock().unwrap_or_else(|e| e.into_inner()).elapsed().as_secs_f64(),
+            ns => self.created_at.elapsed().as_secs_f64() - ns as f64 / 1e9,
+        }
+    }
+
+    // Appelé par le sampler: intègre le débit depuis le passage précédent dans la moyenne lissée
+    pub fn sample_rate(&self) {
+        let now = Instant::now();
+        let bytes = self.recv_bytes.load(Ordering::Relaxed);
+        let mut rate = self.rate.lock().unwrap_or_else(|e| e.into_inner());
+        let secs = now.duration_since(rate.at).as_secs_f64();
+        if secs <= 0.0 {
+            return;
+        }
+        let instant_bps = bytes.saturating_sub(rate.bytes) as f64 * 8.0 / secs;
+        let alpha = 1.0 - (-secs / RATE_SMOOTHING.as_secs_f64()).exp();
+        rate.bps = Some(rate.bps.map_or(instant_bps, |prev| prev + alpha * (instant_bps - prev)));
+        rate.at = now;
+        rate.bytes = bytes;
+    }
+
+    pub fn smoothed_bps(&self) -> f64 {
+        self.rate.lock().unwrap_or_else(|e| e.into_inner()).bps.unwrap_or(0.0)
+    }
+
+    pub fn uptime_secs(&self) -> u64 {
+        self.started_at.lock().unwrap_or_else(|e| e.into_inner()).elapsed().as_secs()
+    }
+
+    // None tant qu'aucun datagramme n'a été reçu depuis le (re)démarrage
+    pub fn time_to_first_byte_ms(&self) -> Option<u64> {
+        let started = *self.started_at.lock().unwrap_or_else(|e| e.into_inner());
+        let first = (*self.first_byte_at.lock().unwrap_or_else(|e| e.into_inner()))?;
+        Some(first.saturating_duration_since(started).as_millis() as u64)
+    }
+
+    // Moyennes calculées depuis le (re)démarrage du pipe
+    pub fn timings_snapshot(&self) -> PipeTimingsEntry {
+        let ms = |counter: &AtomicU64| counter.load(Ordering::Relaxed) as f64 / 1e6;
+        let elapsed = self.started_at.lock().unwrap_or_else(|e| e.into_inner()).elapsed().as_secs_f64();
+        let per_sec = |n: u64| if elapsed > 0.0 { n as f64 / elapsed } else { 0.0 };
+        let loop_iterations = self.timings.iterations.load(Ordering::Relaxed);
+        let packets = self.timings.packets.load(Ordering::Relaxed);
+        PipeTimingsEntry {
+            relay_id: self.relay_id.clone(),
+            loop_iterations,
+            packets,
+            timeouts: self.timings.timeouts.load(Ordering::Relaxed),
+            recv_ms: ms(&self.timings.recv_ns),
+            send_ms: ms(&self.timings.send_ns),
+            iterations_per_sec: per_sec(loop_iterations),
+            packets_per_sec: per_sec(packets),
+        }
+    }
+
+    pub fn snapshot(&self) -> RelayStatsEntry {
+        let (input_options, output_options) = self.options.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        RelayStatsEntry {
+            relay_id: self.relay_id.clone(),
+            input_protocol: self.protocols.input,
+            output_protocol: self.protocols.output,
+            uptime: self.uptime_secs(),
+            time_to_first_byte_ms: self.time_to_first_byte_ms(),
+            input_options,
+            output_options,
+            recv_buffer_bytes: self.recv_buffer_bytes(),
+            send_buffer_bytes: self.send_buffer_bytes.load(Ordering::Relaxed),
+            jitter_ms: self.jitter_ms(),
+            input_rtt_ms: self.rtt_ms(RttSide::Input),
+            output_rtt_ms: self.rtt_ms(RttSide::Output),
+            seconds_since_last_recv: self.seconds_since_last_recv(),
+            rate_limit: *self.rate_limit.lock().unwrap_or_else(|e| e.into_inner()),
+            throttled_ms: self.throttled_us.load(Ordering::Relaxed) / 1000,
+            bitrate_bps: self.smoothed_bps() as u64,
+            bitrate_thresholds: BitrateThresholds::default(),
+            bitrate_status: "ok",
+            overrun: self.overrun(),
+            overrun_ceiling_bps: Some(self.overrun_ceiling_bps.load(Ordering::Relaxed)).filter(|&bps| bps > 0),
+            bytes_recv: self.recv_bytes.load(Ordering::Relaxed),
+            bytes_sent: self.sent_bytes.load(Ordering::Relaxed),
+            packets_recv: self.recv_packets.load(Ordering::Relaxed),
+            packets_sent: self.sent_packets.load(Ordering::Relaxed),
+            packets_lost: self.lost_packets.load(Ordering::Relaxed),
+            packets_dropped: self.dropped_packets.load(Ordering::Relaxed),
+            keepalives_sent: self.keepalives_sent.load(Ordering::Relaxed),
+            lifetime_secs: self.created_at.elapsed().as_secs_f64(),
+            restart_count: self.restart_count(),
+            total_reconnects: self.total_reconnects(),
+        }
+    }
+}
+
+// Vue sérialisable d'un relais pour /stats
+#[derive(Serialize)]
+pub struct RelayStatsEntry {
+    pub relay_id: String,
+    pub input_protocol: &'static str,
+    pub output_protocol: &'static str,
+    pub uptime: u64,
+    pub time_to_first_byte_ms: Option<u64>,
+    pub input_options: EffectiveOptions,
+    pub output_options: EffectiveOptions,
+    pub recv_buffer_bytes: u64,
+    pub send_buffer_bytes: u64,
+    pub jitter_ms: f64,
+    // Dernier RTT connu de chaque côté; null si le transport ne le mesure pas
+    pub input_rtt_ms: Option<f64>,
+    pub output_rtt_ms: Option<f64>,
+    pub seconds_since_last_recv: f64,
+    pub rate_limit: RateLimitConfig,
+    pub throttled_ms: u64,
+    // Débit entrant lissé et son état par rapport aux seuils (complétés par StatsResponse::collect)
+    pub bitrate_bps: u64,
+    pub bitrate_thresholds: BitrateThresholds,
+    pub bitrate_status: &'static str,
+    // Débit entrant au-dessus du plafond ?overrun_bitrate (alarme distincte de bitrate_status)
+    pub overrun: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overrun_ceiling_bps: Option<u64>,
+    // Totaux du relais depuis son lancement (reconnexions comprises)
+    pub bytes_recv: u64,
+    pub bytes_sent: u64,
+    pub packets_recv: u64,
+    pub packets_sent: u64,
+    // lost: manquants d'après les numéros de séquence (jamais reçus); dropped: reçus mais pas transmis
+    // (datagramme tronqué, écarté par drop_pct, mtu, une file d'émission pleine ou un envoi en échec)
+    pub packets_lost: u64,
+    pub packets_dropped: u64,
+    pub keepalives_sent: u64,
+    // Durée couverte par ces totaux, pour les débits moyens de /stats/grouped
+    #[serde(skip)]
+    pub lifetime_secs: f64,
+    pub restart_count: u64,
+    pub total_reconnects: u64,
+}
+
+// Totaux d'un relais à un instant, pour les écarts entre deux lignes relay_stats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RelayTotals {
+    pub bytes_in: u64,
+    pub packets_in: u64,
+    pub bytes_out: u64,
+    pub packets_out: u64,
+    pub lost: u64,
+    pub dropped: u64,
+}
+
+impl RelayTotals {
+    pub fn of(entry: &RelayStatsEntry) -> Self {
+        Self {
+            bytes_in: entry.bytes_recv,
+            packets_in: entry.packets_recv,
+            bytes_out: entry.bytes_sent,
+            packets_out: entry.packets_sent,
+            lost: entry.packets_lost,
+            dropped: entry.packets_dropped,
+        }
+    }
+
+    // Écart depuis `earlier`; un compteur remis à zéro entre-temps repart de zéro au lieu de déborder
+    pub fn since(&self, earlier: &Self) -> Self {
+        Self {
+            bytes_in: self.bytes_in.saturating_sub(earlier.bytes_in),
+            packets_in: self.packets_in.saturating_sub(earlier.packets_in),
+            bytes_out: self.bytes_out.saturating_sub(earlier.bytes_out),
+            packets_out: self.packets_out.saturating_sub(earlier.packets_out),
+            lost: self.lost.saturating_sub(earlier.lost),
+            dropped: self.dropped.saturating_sub(earlier.dropped),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bitrate_status_against_thresholds() {
+        let t = BitrateThresholds { min_bps: Some(1_000_000), max_bps: Some(8_000_000) };
+        assert_eq!(t.status(500_000.0), "under");
+        assert_eq!(t.status(4_000_000.0), "ok");
+        assert_eq!(t.status(9_000_000.0), "over");
+        assert_eq!(BitrateThresholds::default().status(0.0), "ok");
+    }
+
+    #[test]
+    fn rtt_keeps_last_sample_per_side() {
+        let counter = || IntCounter::new("t", "t").unwrap();
+        let stats = RelayStats::new("r", RelayProtocols { input: "srt", output: "srt" }, counter(), counter());
+        assert_eq!(stats.rtt_ms(RttSide::Input), None);
+        stats.set_rtt(RttSide::Input, Duration::from_millis(40));
+        stats.set_rtt(RttSide::Input, Duration::from_micros(12_500));
+        assert_eq!(stats.rtt_ms(RttSide::Input), Some(12.5));
+        assert_eq!(stats.rtt_ms(RttSide::Output), None);
+    }
+
+    #[test]
+    fn loss_and_drops_are_counted_apart() {
+        let counter = || IntCounter::new("t", "t").unwrap();
+        let stats = RelayStats::new("r", RelayProtocols { input: "srt", output: "srt" }, counter(), counter());
+        stats.add_lost(3);
+        stats.add_dropped(1);
+        stats.add_dropped(1);
+        let entry = stats.snapshot();
+        assert_eq!((entry.packets_lost, entry.packets_dropped), (3, 2));
+    }
+
+    #[test]
+    fn totals_since_the_previous_summary() {
+        let counter = || IntCounter::new("t", "t").unwrap();
+        let stats = RelayStats::new("r", RelayProtocols { input: "srt", output: "srt" }, counter(), counter());
+        stats.mark_recv(1316);
+        stats.mark_sent(1316);
+        let first = RelayTotals::of(&stats.snapshot());
+        assert_eq!(first.since(&RelayTotals::default()), RelayTotals { bytes_in: 1316, packets_in: 1, bytes_out: 1316, packets_out: 1, lost: 0, dropped: 0 });
+        stats.mark_recv(188);
+        stats.mark_sent(0);
+        stats.add_dropped(1);
+        let delta = RelayTotals::of(&stats.snapshot()).since(&first);
+        assert_eq!(delta, RelayTotals { bytes_in: 188, packets_in: 1, bytes_out: 0, packets_out: 0, lost: 0, dropped: 1 });
+        // Compteurs remis à zéro: pas de débordement
+        assert_eq!(RelayTotals::default().since(&first), RelayTotals::default());
+    }
+}
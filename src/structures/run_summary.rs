@@ -0,0 +1,38 @@
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+use serde::Serialize;
+
+use crate::structures::Metrics;
+
+// Bilan d'une exécution en ligne de commande (relay, srt2srt, rist2rist), pour --json-summary
+#[derive(Debug, Serialize)]
+pub struct RunSummary {
+    pub command: &'static str,
+    pub input: String,
+    pub output: String,
+    pub duration_ms: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub packets_in: u64,
+    pub packets_out: u64,
+    pub error: Option<String>,
+    pub exit_code: i32,
+}
+
+impl RunSummary {
+    // `input` et `output` déjà masqués; les totaux couvrent toute l'exécution, reconnexions comprises
+    pub fn collect(command: &'static str, input: String, output: String, started: Instant, metrics: &Metrics, error: Option<String>) -> Self {
+        Self {
+            command,
+            input,
+            output,
+            duration_ms: started.elapsed().as_millis() as u64,
+            bytes_in: metrics.bytes_in_total.load(Ordering::Relaxed),
+            bytes_out: metrics.bytes_out_total.load(Ordering::Relaxed),
+            packets_in: metrics.pkt_in_total.load(Ordering::Relaxed),
+            packets_out: metrics.pkt_out_total.load(Ordering::Relaxed),
+            exit_code: if error.is_some() { 1 } else { 0 },
+            error,
+        }
+    }
+}
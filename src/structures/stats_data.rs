@@ -1,5 +1,13 @@
-use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::Ordering;
+use serde::{Deserialize, Serialize};
 
+use crate::relay::manager::RelayManager;
+use crate::structures::{Metrics, RelayStatsEntry};
+use crate::structures::metrics::{estimate_receive_buffer_ms, saturating_i64, saturating_sum};
+
+// Les compteurs u64 sont plafonnés à i64::MAX (saturating_i64) et les débits f64 convertis par `as`, qui
+// sature aussi: aucun champ entier ne peut apparaître négatif
 #[allow(non_snake_case)]
 #[derive(Serialize)]
 pub struct StatsData {
@@ -9,15 +17,272 @@ pub struct StatsData {
     pub mbpsBandwidth: f64,
     pub mbpsRecvRate: f64,
     pub msRcvBuf: i64,
+    // Reçus mais écartés par le relais (buffer de réception débordé, ?allow, file d'émission pleine,
+    // ?mtu, ?drop_pct)
     pub pktRcvDrop: i64,
+    // Jamais reçus, d'après les numéros de séquence (payload=rtp|ts); 0 sans analyse de payload
     pub pktRcvLoss: i64,
+    // Dernier RTT le plus élevé parmi les relais actifs (ms), 0 sans mesure
     pub rtt: f64,
     pub uptime: i64,
+    // null tant qu'un relais actif n'a encore rien reçu (pire cas parmi les relais)
+    pub time_to_first_byte_ms: Option<u64>,
+    // Gigue d'arrivée la plus forte parmi les relais actifs
+    pub jitter_ms: f64,
+}
+
+// Version du schéma de /stats (et de la commande "stats" du socket de contrôle). Politique:
+// - ajouter un champ (dans data, relays[] ou à la racine) ne change pas la version: les clients
+//   doivent ignorer les champs inconnus;
+// - renommer, supprimer un champ, ou changer son type ou son unité incrémente la version.
+pub const STATS_SCHEMA_VERSION: u32 = 1;
+
+// Clés sélectionnables par ?fields= sur /stats (ou stats_fields dans le fichier de configuration): les
+// champs de StatsData, plus "relays" pour garder le détail par relais
+pub const STATS_FIELDS: &[&str] = &[
+    "bitrate", "bytesRcvDrop", "bytesRcvLoss", "mbpsBandwidth", "mbpsRecvRate", "msRcvBuf", "pktRcvDrop",
+    "pktRcvLoss", "rtt", "uptime", "time_to_first_byte_ms", "jitter_ms", "relays",
+];
+
+// Projection de /stats, validée contre STATS_FIELDS
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "Vec<String>")]
+pub struct StatsFields(Vec<String>);
+
+impl StatsFields {
+    // Liste séparée par des virgules (?fields=bitrate,rtt,uptime)
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        Self::try_from(raw.split(',').map(str::trim).filter(|f| !f.is_empty()).map(str::to_string).collect::<Vec<_>>())
+    }
+
+    pub fn contains(&self, field: &str) -> bool {
+        self.0.iter().any(|f| f == field)
+    }
+}
+
+impl TryFrom<Vec<String>> for StatsFields {
+    type Error = String;
+
+    fn try_from(fields: Vec<String>) -> Result<Self, String> {
+        if fields.is_empty() {
+            return Err(format!("empty stats field list (known fields: {})", STATS_FIELDS.join(", ")));
+        }
+        if let Some(unknown) = fields.iter().find(|f| !STATS_FIELDS.contains(&f.as_str())) {
+            return Err(format!("unknown stats field {:?} (known fields: {})", unknown, STATS_FIELDS.join(", ")));
+        }
+        Ok(Self(fields))
+    }
 }
 
 #[allow(non_snake_case)]
 #[derive(Serialize)]
 pub struct StatsResponse {
+    // STATS_SCHEMA_VERSION, pour que les clients choisissent leur lecture selon la forme de la réponse
+    pub schema_version: u32,
     pub data: StatsData,
+    pub relays: Vec<RelayStatsEntry>,
+    pub status: &'static str,
+}
+
+impl StatsResponse {
+    // Agrégat servi par GET /stats et par la commande "stats" du socket de contrôle
+    pub fn collect(metrics: &Metrics) -> Self {
+        let uptime_secs = saturating_i64(metrics.start_time.elapsed().as_secs());
+        metrics.uptime_seconds.set(uptime_secs);
+
+        // Agrégation simple depuis les compteurs globaux
+        let bytes_in = metrics.bytes_in_total.load(Ordering::Relaxed) as f64;
+        let bytes_out = metrics.bytes_out_total.load(Ordering::Relaxed) as f64;
+        let pkt_loss = saturating_i64(metrics.pkt_rcv_loss_total.load(Ordering::Relaxed));
+        let pkt_drop = saturating_i64(metrics.pkt_rcv_drop_total.load(Ordering::Relaxed));
+
+        let seconds = uptime_secs.max(1) as f64;
+        let bps_out = (bytes_out * 8.0) / seconds; // bitrate moyen sortant en bps
+        let mbps_recv = (bytes_in * 8.0) / seconds / 1_000_000.0; // Mbps moyen entrant
+
+        let mut relays = metrics.relay_snapshots();
+        // Seuils d'alerte tenus par le RelayManager (relais pilotés), comparés au débit lissé une fois
+        // la période de chauffe passée
+        let manager = RelayManager::global();
+        for relay in relays.iter_mut() {
+            relay.bitrate_thresholds = manager.bitrate_thresholds(&relay.relay_id);
+            relay.bitrate_status = if relay.uptime < manager.warmup(&relay.relay_id).as_secs() {
+                "warming"
+            } else {
+                relay.bitrate_thresholds.status(relay.bitrate_bps as f64)
+            };
+        }
+
+        // msRcvBuf: octets réellement en attente dans les buffers de réception (Linux), convertis en durée
+        // au débit entrant courant; ailleurs, estimation à partir de la taille configurée des buffers.
+        let bps_in = metrics.current_bps_in.get() as f64;
+        let avg_pkt_size = metrics.avg_pkt_size_in();
+        let pps_in = if avg_pkt_size > 0.0 { bps_in / 8.0 / avg_pkt_size } else { 0.0 };
+        let buffer_bytes = if cfg!(target_os = "linux") {
+            metrics.total_recv_buffer_bytes()
+        } else {
+            saturating_sum(relays.iter().filter_map(|r| r.input_options.recv_buffer_bytes).map(|b| b as u64))
+        };
+        let ms_rcv_buf = estimate_receive_buffer_ms(pps_in, avg_pkt_size, buffer_bytes);
+        let time_to_first_byte_ms = if relays.is_empty() {
+            None
+        } else {
+            relays.iter().map(|r| r.time_to_first_byte_ms).collect::<Option<Vec<u64>>>().and_then(|v| v.into_iter().max())
+        };
+
+        let data = StatsData {
+            bitrate: bps_out as i64,
+            bytesRcvDrop: 0,
+            bytesRcvLoss: 0,
+            mbpsBandwidth: 0.0,
+            mbpsRecvRate: mbps_recv,
+            msRcvBuf: ms_rcv_buf,
+            pktRcvDrop: pkt_drop,
+            pktRcvLoss: pkt_loss,
+            rtt: relays.iter().flat_map(|r| [r.input_rtt_ms, r.output_rtt_ms]).flatten().fold(0.0, f64::max),
+            uptime: uptime_secs,
+            time_to_first_byte_ms,
+            jitter_ms: relays.iter().map(|r| r.jitter_ms).fold(0.0, f64::max),
+        };
+
+        StatsResponse { schema_version: STATS_SCHEMA_VERSION, data, relays, status: "ok" }
+    }
+
+    // Réponse réduite aux champs demandés: schema_version et status restent, relays[] seulement sur demande
+    pub fn project(&self, fields: &StatsFields) -> serde_json::Result<serde_json::Value> {
+        let mut value = serde_json::to_value(self)?;
+        if let Some(data) = value.get_mut("data").and_then(serde_json::Value::as_object_mut) {
+            data.retain(|key, _| fields.contains(key));
+        }
+        if !fields.contains("relays")
+            && let Some(root) = value.as_object_mut()
+        {
+            root.remove("relays");
+        }
+        Ok(value)
+    }
+}
+
+#[derive(Serialize)]
+pub struct GroupedStatsResponse {
+    pub by: String,
+    // Valeur de l'étiquette -> agrégat des relais qui la portent (les relais sans l'étiquette sont omis)
+    pub groups: BTreeMap<String, StatsData>,
     pub status: &'static str,
-}
\ No newline at end of file
+}
+
+impl GroupedStatsResponse {
+    // None si aucun relais ne porte l'étiquette `by`
+    pub fn collect(metrics: &Metrics, by: &str) -> Option<Self> {
+        let mut grouped: BTreeMap<String, Vec<RelayStatsEntry>> = BTreeMap::new();
+        for relay in metrics.relay_snapshots() {
+            if let Some(value) = metrics.relay_labels(&relay.relay_id).and_then(|mut l| l.remove(by)) {
+                grouped.entry(value).or_default().push(relay);
+            }
+        }
+        if grouped.is_empty() {
+            return None;
+        }
+        let groups = grouped.into_iter().map(|(value, relays)| (value, StatsData::aggregate(&relays))).collect();
+        Some(GroupedStatsResponse { by: by.to_string(), groups, status: "ok" })
+    }
+}
+
+impl StatsData {
+    // Mêmes champs que l'agrégat global, calculés sur les seuls compteurs des relais donnés:
+    // débits moyens sommés, pires cas pour rtt, gigue et time-to-first-byte
+    pub fn aggregate(relays: &[RelayStatsEntry]) -> Self {
+        let per_sec = |bytes: u64, secs: f64| bytes as f64 * 8.0 / secs.max(1.0);
+        let bytes_recv = saturating_sum(relays.iter().map(|r| r.bytes_recv));
+        let packets_recv = saturating_sum(relays.iter().map(|r| r.packets_recv));
+        let avg_pkt_size = if packets_recv == 0 { 0.0 } else { bytes_recv as f64 / packets_recv as f64 };
+        let bps_in: f64 = relays.iter().map(|r| r.bitrate_bps as f64).sum();
+        let pps_in = if avg_pkt_size > 0.0 { bps_in / 8.0 / avg_pkt_size } else { 0.0 };
+        let buffer_bytes = saturating_sum(relays.iter().map(|r| r.recv_buffer_bytes));
+        StatsData {
+            bitrate: relays.iter().map(|r| per_sec(r.bytes_sent, r.lifetime_secs)).sum::<f64>() as i64,
+            bytesRcvDrop: 0,
+            bytesRcvLoss: 0,
+            mbpsBandwidth: 0.0,
+            mbpsRecvRate: relays.iter().map(|r| per_sec(r.bytes_recv, r.lifetime_secs)).sum::<f64>() / 1_000_000.0,
+            msRcvBuf: estimate_receive_buffer_ms(pps_in, avg_pkt_size, buffer_bytes),
+            // Les refus ?allow et drop_oldest ne sont pas attribués à un relais: seul l'agrégat global les compte
+            pktRcvDrop: saturating_i64(saturating_sum(relays.iter().map(|r| r.packets_dropped))),
+            pktRcvLoss: saturating_i64(saturating_sum(relays.iter().map(|r| r.packets_lost))),
+            rtt: relays.iter().flat_map(|r| [r.input_rtt_ms, r.output_rtt_ms]).flatten().fold(0.0, f64::max),
+            uptime: saturating_i64(relays.iter().map(|r| r.uptime).max().unwrap_or(0)),
+            time_to_first_byte_ms: relays.iter().map(|r| r.time_to_first_byte_ms).collect::<Option<Vec<u64>>>().and_then(|v| v.into_iter().max()),
+            jitter_ms: relays.iter().map(|r| r.jitter_ms).fold(0.0, f64::max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::IntCounter;
+    use crate::structures::{RelayProtocols, RelayStats};
+
+    fn entry(id: &str, bytes_sent: u64, lost: u64, rtt_ms: Option<f64>, ttfb_ms: Option<u64>) -> RelayStatsEntry {
+        let counter = || IntCounter::new("t", "t").unwrap();
+        let mut e = RelayStats::new(id, RelayProtocols { input: "srt", output: "srt" }, counter(), counter()).snapshot();
+        e.bytes_sent = bytes_sent;
+        e.bytes_recv = bytes_sent;
+        e.packets_lost = lost;
+        e.packets_dropped = lost * 2;
+        e.input_rtt_ms = rtt_ms;
+        e.time_to_first_byte_ms = ttfb_ms;
+        e.lifetime_secs = 10.0;
+        e
+    }
+
+    #[test]
+    fn aggregates_sums_and_worst_cases() {
+        let relays = [entry("a", 1_250_000, 3, Some(20.0), Some(40)), entry("b", 2_500_000, 4, Some(80.0), Some(15))];
+        let data = StatsData::aggregate(&relays);
+        assert_eq!(data.bitrate, 3_000_000);
+        assert!((data.mbpsRecvRate - 3.0).abs() < 1e-9);
+        assert_eq!(data.pktRcvLoss, 7);
+        assert_eq!(data.pktRcvDrop, 14);
+        assert_eq!(data.rtt, 80.0);
+        assert_eq!(data.time_to_first_byte_ms, Some(40));
+        // Un relais sans premier octet rend le time-to-first-byte du groupe inconnu
+        let relays = [entry("a", 0, 0, None, Some(40)), entry("b", 0, 0, None, None)];
+        assert_eq!(StatsData::aggregate(&relays).time_to_first_byte_ms, None);
+    }
+
+    #[test]
+    fn huge_counters_never_turn_negative() {
+        let relays = [entry("a", u64::MAX, u64::MAX / 2, None, None), entry("b", u64::MAX, u64::MAX / 2, None, None)];
+        let data = StatsData::aggregate(&relays);
+        assert_eq!(data.pktRcvLoss, i64::MAX);
+        assert_eq!(data.pktRcvDrop, i64::MAX);
+        assert!(data.bitrate > 0 && data.msRcvBuf >= 0);
+    }
+
+    #[test]
+    fn stats_response_carries_the_schema_version() {
+        let stats = StatsResponse { schema_version: STATS_SCHEMA_VERSION, data: StatsData::aggregate(&[]), relays: Vec::new(), status: "ok" };
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["schema_version"], 1);
+    }
+
+    #[test]
+    fn projects_only_the_requested_fields() {
+        let stats = StatsResponse { schema_version: STATS_SCHEMA_VERSION, data: StatsData::aggregate(&[entry("a", 10, 0, Some(5.0), None)]), relays: vec![entry("a", 10, 0, None, None)], status: "ok" };
+        // STATS_FIELDS suit les clés sérialisées de StatsData
+        let full = serde_json::to_value(&stats).unwrap();
+        let keys: Vec<&str> = full["data"].as_object().unwrap().keys().map(String::as_str).collect();
+        assert_eq!(keys.len() + 1, STATS_FIELDS.len());
+        assert!(keys.iter().all(|k| STATS_FIELDS.contains(k)));
+
+        let projected = stats.project(&StatsFields::parse("bitrate, rtt,uptime").unwrap()).unwrap();
+        assert_eq!(projected["data"].as_object().unwrap().len(), 3);
+        assert_eq!(projected["data"]["rtt"], 5.0);
+        assert_eq!(projected["schema_version"], 1);
+        assert!(projected.get("relays").is_none());
+        assert_eq!(stats.project(&StatsFields::parse("relays").unwrap()).unwrap()["relays"].as_array().unwrap().len(), 1);
+        assert!(StatsFields::parse("bitrate,latency").unwrap_err().contains("\"latency\""));
+        assert!(StatsFields::parse(" , ").is_err());
+    }
+}
@@ -0,0 +1,323 @@
+// Export StatsD / DogStatsD (--statsd-addr / SRTRIST_STATSD_ADDR=host:8125; désactivé sans adresse).
+// À chaque période, les compteurs de trafic partent en écarts depuis l'envoi précédent (type c) et les
+// jauges en valeur courante (type g), en UDP au format DogStatsD: nom:valeur|type|#tag:valeur,...
+// Totaux globaux sans étiquette, puis une série par relais actif étiquetée relay_id / protocol /
+// output_protocol. Lu depuis Metrics comme /stats: un envoi raté est journalisé (limité) sans jamais
+// gêner les relais.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::common::logging::{events, LogThrottle};
+use crate::structures::Metrics;
+use crate::structures::relay_stats::{RelayStatsEntry, RelayTotals};
+
+const PREFIX: &str = "stream_relay";
+// Plusieurs lignes par datagramme (séparées par '\n'), sous le MTU Ethernet courant
+const MAX_DATAGRAM: usize = 1432;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatsdConfig {
+    // host:port, résolu à chaque envoi pour suivre un changement d'adresse de l'agent
+    pub addr: String,
+    pub period: Duration,
+}
+
+impl StatsdConfig {
+    // None sans adresse (export désactivé); une adresse sans port valide est une erreur de démarrage
+    pub fn from_args(addr: Option<String>, period: Duration) -> Result<Option<Self>, String> {
+        let Some(addr) = addr.map(|a| a.trim().to_string()).filter(|a| !a.is_empty()) else { return Ok(None) };
+        let valid = addr.rsplit_once(':').is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok_and(|p| p > 0));
+        if !valid {
+            return Err(format!("StatsD address must be host:port, got {:?}", addr));
+        }
+        if period.is_zero() {
+            return Err("StatsD push interval must be at least 1 s".into());
+        }
+        Ok(Some(Self { addr, period }))
+    }
+}
+
+// Totaux déjà livrés à l'agent, pour n'envoyer que les écarts des compteurs
+#[derive(Default)]
+struct PushState {
+    global: RelayTotals,
+    relays: HashMap<String, RelayTotals>,
+}
+
+impl PushState {
+    // Références conservées pour la période: seuls les relais encore actifs gardent la leur
+    fn retained(&self, series: &[Series]) -> Self {
+        let relays = series
+            .iter()
+            .filter_map(|s| match &s.scope {
+                Scope::Relay(id) => self.relays.get(id).map(|t| (id.clone(), *t)),
+                Scope::Global => None,
+            })
+            .collect();
+        Self { global: self.global, relays }
+    }
+
+    // Une série livrée devient la référence de son périmètre
+    fn advance(&mut self, series: &Series) {
+        match &series.scope {
+            Scope::Global => self.global = series.totals,
+            Scope::Relay(id) => {
+                self.relays.insert(id.clone(), series.totals);
+            }
+        }
+    }
+}
+
+enum Scope {
+    Global,
+    Relay(String),
+}
+
+// Lignes d'un périmètre (totaux globaux ou un relais) et les totaux dont elles portent l'écart
+struct Series {
+    scope: Scope,
+    totals: RelayTotals,
+    lines: Vec<String>,
+}
+
+pub struct StatsdExporter {
+    cfg: StatsdConfig,
+    state: PushState,
+    socket: Option<UdpSocket>,
+    error_log: LogThrottle,
+}
+
+impl StatsdExporter {
+    pub fn new(cfg: StatsdConfig) -> Self {
+        Self { cfg, state: PushState::default(), socket: None, error_log: LogThrottle::new(Duration::from_secs(60)) }
+    }
+
+    pub fn spawn(mut self, metrics: Arc<Metrics>) -> JoinHandle<()> {
+        info!(event = events::STATSD_STARTED, subsystem = "statsd", addr = %self.cfg.addr, interval_secs = self.cfg.period.as_secs(), msg = "StatsD exporter started");
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.cfg.period);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // Le premier tick est immédiat: le premier envoi couvre une période entière
+            ticker.tick().await;
+            self.state.global = global_totals(&metrics);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.push(&metrics).await
+                    && let Some(suppressed) = self.error_log.allow()
+                {
+                    warn!(event = events::STATSD_ERROR, subsystem = "statsd", addr = %self.cfg.addr, error = %e, suppressed = suppressed, msg = "StatsD push failed");
+                }
+            }
+        })
+    }
+
+    // Une série ne devient la référence qu'une fois son dernier datagramme envoyé: après un échec en
+    // cours de période, seules les séries non livrées repartent au suivant (ni perdues, ni comptées deux fois)
+    async fn push(&mut self, metrics: &Metrics) -> std::io::Result<()> {
+        let series = render(metrics, &self.state);
+        let target = tokio::net::lookup_host(&self.cfg.addr)
+            .await?
+            .next()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no address for the StatsD host"))?;
+        let socket = match self.socket.take() {
+            Some(socket) if socket.local_addr()?.is_ipv4() == target.is_ipv4() => socket,
+            _ => UdpSocket::bind(unspecified_for(target)).await?,
+        };
+        let socket = self.socket.insert(socket);
+        let mut next = self.state.retained(&series);
+        let mut delivered = 0;
+        for (datagram, complete) in pack(&series) {
+            if let Err(e) = socket.send_to(datagram.as_bytes(), target).await {
+                self.state = next;
+                return Err(e);
+            }
+            for s in &series[delivered..complete] {
+                next.advance(s);
+            }
+            delivered = complete;
+        }
+        self.state = next;
+        Ok(())
+    }
+}
+
+fn unspecified_for(target: SocketAddr) -> SocketAddr {
+    if target.is_ipv4() { SocketAddr::from(([0, 0, 0, 0], 0)) } else { SocketAddr::from(([0u16; 8], 0)) }
+}
+
+fn global_totals(metrics: &Metrics) -> RelayTotals {
+    RelayTotals {
+        bytes_in: metrics.bytes_in_total.load(Ordering::Relaxed),
+        packets_in: metrics.pkt_in_total.load(Ordering::Relaxed),
+        bytes_out: metrics.bytes_out_total.load(Ordering::Relaxed),
+        packets_out: metrics.pkt_out_total.load(Ordering::Relaxed),
+        lost: metrics.pkt_rcv_loss_total.load(Ordering::Relaxed),
+        dropped: metrics.pkt_rcv_drop_total.load(Ordering::Relaxed),
+    }
+}
+
+// Les valeurs d'étiquette DogStatsD ne peuvent contenir ni ',' ni '|' ni '#' ni fin de ligne
+fn tag_value(value: &str) -> String {
+    value.chars().map(|c| if matches!(c, ',' | '|' | '#' | '\n' | '\r') { '_' } else { c }).collect()
+}
+
+fn counters(lines: &mut Vec<String>, scope: &str, delta: &RelayTotals, tags: &str) {
+    for (name, value) in [
+        ("bytes_in", delta.bytes_in),
+        ("packets_in", delta.packets_in),
+        ("bytes_out", delta.bytes_out),
+        ("packets_out", delta.packets_out),
+        ("packets_lost", delta.lost),
+        ("packets_dropped", delta.dropped),
+    ] {
+        lines.push(format!("{}{}.{}:{}|c{}", PREFIX, scope, name, value, tags));
+    }
+}
+
+fn relay_tags(relay: &RelayStatsEntry) -> String {
+    format!("|#relay_id:{},protocol:{},output_protocol:{}", tag_value(&relay.relay_id), relay.input_protocol, relay.output_protocol)
+}
+
+// Séries de la période, écarts calculés depuis `state` (qui n'est pas modifié): la série globale
+// d'abord, puis une par relais actif
+fn render(metrics: &Metrics, state: &PushState) -> Vec<Series> {
+    let mut lines = Vec::new();
+    lines.push(format!("{}.active_relays:{}|g", PREFIX, metrics.active_relays.load(Ordering::SeqCst)));
+    lines.push(format!("{}.bitrate_in_bps:{}|g", PREFIX, metrics.current_bps_in.get()));
+    lines.push(format!("{}.bitrate_out_bps:{}|g", PREFIX, metrics.current_bps_out.get()));
+    let global = global_totals(metrics);
+    counters(&mut lines, "", &global.since(&state.global), "");
+    let mut series = vec![Series { scope: Scope::Global, totals: global, lines }];
+
+    for relay in metrics.relay_snapshots() {
+        let tags = relay_tags(&relay);
+        let totals = RelayTotals::of(&relay);
+        let delta = totals.since(&state.relays.get(&relay.relay_id).copied().unwrap_or_default());
+        let mut lines = Vec::new();
+        counters(&mut lines, ".relay", &delta, &tags);
+        lines.push(format!("{}.relay.bitrate_bps:{}|g{}", PREFIX, relay.bitrate_bps, tags));
+        for (side, rtt) in [("input", relay.input_rtt_ms), ("output", relay.output_rtt_ms)] {
+            if let Some(rtt) = rtt {
+                lines.push(format!("{}.relay.rtt_ms:{}|g{},side:{}", PREFIX, rtt, tags, side));
+            }
+        }
+        series.push(Series { scope: Scope::Relay(relay.relay_id), totals, lines });
+    }
+    series
+}
+
+// Regroupe les lignes en datagrammes d'au plus MAX_DATAGRAM octets (une ligne plus longue part seule).
+// Chaque datagramme est accompagné du nombre de séries entièrement envoyées une fois qu'il est parti.
+fn pack(series: &[Series]) -> Vec<(String, usize)> {
+    let mut datagrams: Vec<(String, usize)> = Vec::new();
+    for (i, s) in series.iter().enumerate() {
+        for line in &s.lines {
+            match datagrams.last_mut() {
+                Some((current, _)) if current.len() + 1 + line.len() <= MAX_DATAGRAM => {
+                    current.push('\n');
+                    current.push_str(line);
+                }
+                _ => datagrams.push((line.clone(), i)),
+            }
+        }
+        if let Some((_, complete)) = datagrams.last_mut() {
+            *complete = i + 1;
+        }
+    }
+    datagrams
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structures::RelayProtocols;
+
+    #[test]
+    fn validates_the_address() {
+        let period = Duration::from_secs(10);
+        assert_eq!(StatsdConfig::from_args(None, period), Ok(None));
+        assert_eq!(StatsdConfig::from_args(Some(" ".into()), period), Ok(None));
+        assert_eq!(StatsdConfig::from_args(Some("statsd.local:8125".into()), period).unwrap().unwrap().addr, "statsd.local:8125");
+        assert!(StatsdConfig::from_args(Some("[::1]:8125".into()), period).unwrap().is_some());
+        assert!(StatsdConfig::from_args(Some("statsd.local".into()), period).is_err());
+        assert!(StatsdConfig::from_args(Some(":8125".into()), period).is_err());
+        assert!(StatsdConfig::from_args(Some("h:8125".into()), Duration::ZERO).is_err());
+    }
+
+    fn all_lines(series: &[Series]) -> Vec<String> {
+        series.iter().flat_map(|s| s.lines.iter().cloned()).collect()
+    }
+
+    #[test]
+    fn renders_deltas_and_tagged_relay_series() {
+        let m = Metrics::new();
+        let stats = m.register_relay("r,1", RelayProtocols { input: "srt", output: "rist" });
+        stats.mark_recv(100);
+        m.add_bytes_in(100);
+        let series = render(&m, &PushState::default());
+        let lines = all_lines(&series);
+        assert!(lines.contains(&"stream_relay.bytes_in:100|c".to_string()), "{:?}", lines);
+        assert!(lines.contains(&"stream_relay.relay.bytes_in:100|c|#relay_id:r_1,protocol:srt,output_protocol:rist".to_string()), "{:?}", lines);
+        assert!(lines.iter().any(|l| l.starts_with("stream_relay.active_relays:")));
+        // Sans nouvelle référence (envoi raté), le même écart repart à la période suivante
+        let lines = all_lines(&render(&m, &PushState::default()));
+        assert!(lines.contains(&"stream_relay.bytes_in:100|c".to_string()));
+        // Rien de nouveau depuis la référence: les compteurs repartent de zéro
+        let mut state = PushState::default();
+        series.iter().for_each(|s| state.advance(s));
+        let lines = all_lines(&render(&m, &state));
+        assert!(lines.contains(&"stream_relay.bytes_in:0|c".to_string()));
+        assert!(lines.contains(&"stream_relay.relay.bytes_in:0|c|#relay_id:r_1,protocol:srt,output_protocol:rist".to_string()));
+    }
+
+    #[test]
+    fn packs_lines_under_the_datagram_limit() {
+        let m = Metrics::new();
+        for i in 0..40 {
+            m.register_relay(&format!("relay-{:02}", i), RelayProtocols { input: "srt", output: "srt" });
+        }
+        let series = render(&m, &PushState::default());
+        let datagrams = pack(&series);
+        assert!(datagrams.len() > 1);
+        assert!(datagrams.iter().all(|(d, _)| d.len() <= MAX_DATAGRAM));
+        assert_eq!(datagrams.iter().map(|(d, _)| d.lines().count()).sum::<usize>(), all_lines(&series).len());
+        assert_eq!(datagrams.last().unwrap().1, series.len());
+        assert!(datagrams.windows(2).all(|w| w[0].1 <= w[1].1));
+    }
+
+    #[test]
+    fn only_undelivered_series_are_sent_again() {
+        let m = Metrics::new();
+        for i in 0..40 {
+            m.register_relay(&format!("relay-{:02}", i), RelayProtocols { input: "srt", output: "srt" }).mark_recv(100);
+        }
+        let series = render(&m, &PushState::default());
+        let (_, complete) = pack(&series)[0].clone();
+        assert!(complete > 1 && complete < series.len());
+        // Seul le premier datagramme est parti: ses séries complètes avancent, les autres gardent leur écart
+        let mut state = PushState::default().retained(&series);
+        series[..complete].iter().for_each(|s| state.advance(s));
+        let again = render(&m, &state);
+        let repeated: Vec<bool> = again[1..].iter().map(|s| s.lines[0].contains(":100|c")).collect();
+        assert_eq!(repeated.iter().filter(|r| !**r).count(), complete - 1);
+        assert!(repeated[complete - 1..].iter().all(|r| *r));
+    }
+
+    #[tokio::test]
+    async fn pushes_to_the_agent_over_udp() {
+        let agent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let cfg = StatsdConfig::from_args(Some(agent.local_addr().unwrap().to_string()), Duration::from_secs(1)).unwrap().unwrap();
+        let mut exporter = StatsdExporter::new(cfg);
+        exporter.push(&Metrics::new()).await.unwrap();
+        let mut buf = vec![0u8; 2048];
+        let n = tokio::time::timeout(Duration::from_secs(1), agent.recv(&mut buf)).await.unwrap().unwrap();
+        assert!(std::str::from_utf8(&buf[..n]).unwrap().starts_with("stream_relay.active_relays:0|g\n"));
+    }
+}
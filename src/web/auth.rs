@@ -0,0 +1,51 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+
+use crate::web::error::ErrorDetail;
+
+// Jeton d'administration (--admin-token / SRTRIST_ADMIN_TOKEN) protégeant les routes qui modifient l'état.
+// Sans jeton configuré, ces routes sont désactivées plutôt qu'ouvertes.
+#[derive(Debug, Clone, Default)]
+pub struct AdminAuth(pub Option<String>);
+
+// Garde Rocket: exige "Authorization: Bearer <jeton>"
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = &'static str;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(expected) = req.rocket().state::<AdminAuth>().and_then(|a| a.0.as_deref()) else {
+            return refuse(req, Status::Forbidden, "admin routes are disabled (no admin token configured)");
+        };
+        let provided = req.headers().get_one("Authorization").and_then(|h| h.strip_prefix("Bearer "));
+        match provided {
+            Some(token) if constant_time_eq(token.trim().as_bytes(), expected.as_bytes()) => Outcome::Success(Admin),
+            _ => refuse(req, Status::Unauthorized, "missing or invalid admin token"),
+        }
+    }
+}
+
+// Le motif est mémorisé pour le catcher, qui le renvoie dans le corps d'erreur JSON
+fn refuse(req: &Request<'_>, status: Status, reason: &'static str) -> Outcome<Admin, &'static str> {
+    req.local_cache(|| ErrorDetail(reason));
+    Outcome::Error((status, reason))
+}
+
+// Comparaison sans sortie anticipée, pour ne pas révéler le préfixe correct par le temps de réponse
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::constant_time_eq;
+
+    #[test]
+    fn compares_tokens() {
+        assert!(constant_time_eq(b"s3cret", b"s3cret"));
+        assert!(!constant_time_eq(b"s3cret", b"s3creT"));
+        assert!(!constant_time_eq(b"s3cret", b"s3cre"));
+    }
+}
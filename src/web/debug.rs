@@ -0,0 +1,31 @@
+use rocket::get;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+use crate::structures::{Metrics, PipeTimingsEntry};
+
+// Diagnostic interne (feature debug-endpoints): pas un profileur, mais de quoi repérer un relais
+// qui tourne à vide (beaucoup de tours de boucle) ou qui passe son temps dans send
+#[derive(Serialize)]
+pub struct DebugTasksResponse {
+    pub worker_threads: usize,
+    pub alive_tasks: usize,
+    pub global_queue_depth: usize,
+    pub active_relays: u64,
+    pub relays: Vec<PipeTimingsEntry>,
+}
+
+#[get("/debug/tasks")]
+pub fn debug_tasks(metrics: &State<Arc<Metrics>>) -> Json<DebugTasksResponse> {
+    let runtime = rocket::tokio::runtime::Handle::current().metrics();
+    Json(DebugTasksResponse {
+        worker_threads: runtime.num_workers(),
+        alive_tasks: runtime.num_alive_tasks(),
+        global_queue_depth: runtime.global_queue_depth(),
+        active_relays: metrics.active_relays.load(Ordering::Relaxed),
+        relays: metrics.relay_snapshots().iter().filter_map(|r| metrics.relay_timing(&r.relay_id)).collect(),
+    })
+}
@@ -0,0 +1,89 @@
+use rocket::http::Status;
+use rocket::response::{self, Responder, Response};
+use rocket::serde::json::Json;
+use rocket::{catch, Request};
+use serde::Serialize;
+
+use crate::structures::TransportError;
+
+// Réponse d'erreur commune à toute l'API (routes et catchers):
+// { "status": "error", "error": "<type>", "code": <statut HTTP>, "detail": "<message>" }
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: Status,
+    pub error: String,
+    pub detail: String,
+}
+
+#[derive(Serialize)]
+struct ApiErrorBody<'a> {
+    status: &'static str,
+    error: &'a str,
+    code: u16,
+    detail: &'a str,
+}
+
+// Motif posé par une garde de requête avant d'échouer, repris dans le "detail" du catcher
+#[derive(Debug, Default)]
+pub struct ErrorDetail(pub &'static str);
+
+impl ApiError {
+    pub fn new(status: Status, error: &str, detail: impl Into<String>) -> Self {
+        Self { status, error: error.to_string(), detail: detail.into() }
+    }
+
+    // Type dérivé du statut: 404 -> "not_found", 401 -> "unauthorized"
+    pub fn from_status(status: Status, detail: impl Into<String>) -> Self {
+        let error = status.reason().unwrap_or("error").to_ascii_lowercase().replace([' ', '-'], "_");
+        Self { status, error, detail: detail.into() }
+    }
+}
+
+// Les routes de gestion renvoient Result<_, ApiError> et propagent les erreurs de transport avec `?`
+impl From<TransportError> for ApiError {
+    fn from(e: TransportError) -> Self {
+        let (status, error) = match &e {
+            TransportError::InvalidUri(_) => (Status::BadRequest, "invalid_uri"),
+            TransportError::UnsupportedScheme(_) => (Status::BadRequest, "unsupported_scheme"),
+            TransportError::Timeout { .. } => (Status::GatewayTimeout, "timeout"),
+            TransportError::Stalled { .. } => (Status::GatewayTimeout, "stalled"),
+            TransportError::BindPermissionDenied { .. } => (Status::Forbidden, "permission_denied"),
+            TransportError::Closed => (Status::Conflict, "closed"),
+            TransportError::Io(io) if io.kind() == std::io::ErrorKind::AddrInUse => (Status::Conflict, "address_in_use"),
+            TransportError::Io(_) => (Status::InternalServerError, "io_error"),
+            TransportError::Other(_) => (Status::InternalServerError, "internal"),
+        };
+        Self::new(status, error, e.to_string())
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let body = ApiErrorBody { status: "error", error: &self.error, code: self.status.code, detail: &self.detail };
+        Response::build_from(Json(body).respond_to(req)?).status(self.status).ok()
+    }
+}
+
+// Catcher par défaut: toute réponse 4xx/5xx sans corps (route inconnue, garde refusée, panique) suit le même schéma
+#[catch(default)]
+pub fn default_catcher(status: Status, req: &Request<'_>) -> ApiError {
+    let detail = req.local_cache(ErrorDetail::default).0;
+    let detail = if detail.is_empty() { status.reason().unwrap_or("error") } else { detail };
+    ApiError::from_status(status, detail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_transport_errors_to_statuses() {
+        let e = ApiError::from(TransportError::InvalidUri("srt://".into()));
+        assert_eq!((e.status, e.error.as_str()), (Status::BadRequest, "invalid_uri"));
+        assert_eq!(ApiError::from(TransportError::Closed).status, Status::Conflict);
+        assert_eq!(ApiError::from(TransportError::Timeout { during: None }).status, Status::GatewayTimeout);
+        let in_use = TransportError::Io(std::io::ErrorKind::AddrInUse.into());
+        assert_eq!(ApiError::from(in_use).error, "address_in_use");
+        assert_eq!(ApiError::from_status(Status::NotFound, "x").error, "not_found");
+    }
+}
@@ -1,16 +1,49 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use rocket::{Data, Request, Response};
 use rocket::fairing::{Fairing, Info, Kind};
-use tracing::{info, debug};
+use tracing::{info, debug, warn};
 
 use std::sync::Arc;
 use crate::structures::Metrics;
 use crate::common::logging::events;
 
+pub mod auth;
+#[cfg(feature = "debug-endpoints")]
+pub mod debug;
+pub mod error;
+pub mod proxy;
 pub mod routes;
+pub mod timeout;
+pub mod tls;
 
-// Fairing Rocket: intercepte chaque requête pour mesurer la durée et incrémenter les compteurs
-pub struct HttpMetricsFairing;
+// Préfixe de montage des routes HTTP (ex: "/relay" derrière un reverse proxy); "" = racine
+#[derive(Debug, Clone, Default)]
+pub struct HttpPrefix(pub String);
+
+impl HttpPrefix {
+    // Normalise "relay/", "/relay" ou "/" en "/relay" / ""
+    pub fn new(raw: &str) -> Self {
+        let trimmed = raw.trim().trim_matches('/');
+        if trimmed.is_empty() { Self(String::new()) } else { Self(format!("/{}", trimmed)) }
+    }
+
+    // Base passée à Rocket::mount
+    pub fn base(&self) -> &str {
+        if self.0.is_empty() { "/" } else { &self.0 }
+    }
+
+    pub fn path(&self, route: &str) -> String {
+        format!("{}{}", self.0, route)
+    }
+}
+
+// Fairing Rocket: intercepte chaque requête pour mesurer la durée et incrémenter les compteurs.
+// `trust_proxy` (--trust-proxy) fait journaliser le client annoncé par les en-têtes X-Forwarded-*;
+// au-delà de `slow_request` (--http-slow-request-ms), la réponse est aussi signalée par un avertissement.
+pub struct HttpMetricsFairing {
+    pub trust_proxy: bool,
+    pub slow_request: Option<Duration>,
+}
 
 #[rocket::async_trait]
 impl Fairing for HttpMetricsFairing {
@@ -20,20 +53,20 @@ impl Fairing for HttpMetricsFairing {
 
     async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
         // Mémorise l'instant de début de traitement de la requête
-        req.local_cache(|| Instant::now());
+        req.local_cache(Instant::now);
         // Génère ou récupère un request_id
         let req_id = req.headers().get_one("X-Request-ID").map(|s| s.to_string()).unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
         req.local_cache(|| req_id);
         // Log debug http_request
         let method = req.method().as_str();
         let path = req.uri().path().to_string();
-        let rid: &String = req.local_cache(|| String::new());
+        let rid: &String = req.local_cache(String::new);
         debug!(event = events::HTTP_REQUEST, subsystem = "http", request_id = %rid, method = method, path = %path, msg = "HTTP request");
     }
 
     async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
         // Récupère l'instant de début et calcule la durée
-        let start = req.local_cache(|| Instant::now());
+        let start = req.local_cache(Instant::now);
         let elapsed = start.elapsed();
         let method = req.method().as_str().to_string();
         let status_code = res.status().code;
@@ -45,7 +78,28 @@ impl Fairing for HttpMetricsFairing {
             // Observe la latence (en secondes) par méthode
             metrics.http_request_duration_seconds.with_label_values(&[&method]).observe(elapsed.as_secs_f64());
         }
-        let rid: &String = req.local_cache(|| String::new());
-        info!(event = events::HTTP_RESPONSE, subsystem = "http", request_id = %rid, method = %method, status = status_code, dur_ms = elapsed.as_millis() as u64, msg = "HTTP response");
+        let rid: &String = req.local_cache(String::new);
+        let client = proxy::resolve_client(|name| req.headers().get_one(name), req.remote().map(|a| a.ip()), self.trust_proxy);
+        let client_ip = client.ip.map(|ip| ip.to_string()).unwrap_or_default();
+        info!(event = events::HTTP_RESPONSE, subsystem = "http", request_id = %rid, method = %method, status = status_code, dur_ms = elapsed.as_millis() as u64, client_ip = %client_ip, scheme = %client.scheme, host = client.host.as_deref().unwrap_or(""), msg = "HTTP response");
+        if let Some(slow) = self.slow_request
+            && elapsed > slow
+            && !timeout::is_long_running(req)
+        {
+            warn!(event = events::HTTP_SLOW_REQUEST, subsystem = "http", request_id = %rid, method = %method, path = %req.uri().path(), status = status_code, dur_ms = elapsed.as_millis() as u64, slow_ms = slow.as_millis() as u64, msg = "Slow HTTP request");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HttpPrefix;
+
+    #[test]
+    fn prefix_is_normalized() {
+        assert_eq!(HttpPrefix::new("/").base(), "/");
+        assert_eq!(HttpPrefix::new("relay/").base(), "/relay");
+        assert_eq!(HttpPrefix::new("/relay").path("/health"), "/relay/health");
+        assert_eq!(HttpPrefix::new("").path("/health"), "/health");
     }
 }
@@ -0,0 +1,61 @@
+use std::net::IpAddr;
+
+// Client réel d'une requête pour le journal d'accès (http_response). Derrière un load balancer, l'adresse
+// du pair TCP est celle du proxy; avec --trust-proxy, X-Forwarded-For (premier élément), puis X-Real-IP,
+// et X-Forwarded-Proto / X-Forwarded-Host prennent le relais. Ces en-têtes sont falsifiables par n'importe
+// quel client direct: désactivé par défaut, le pair TCP fait foi (Rocket lit X-Real-IP de lui-même dans
+// client_ip(), d'où l'usage de remote() par l'appelant).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub ip: Option<IpAddr>,
+    pub scheme: String,
+    pub host: Option<String>,
+}
+
+// `header` lit un en-tête de la requête; une valeur illisible est ignorée au profit de la suivante
+pub fn resolve_client<'a>(header: impl Fn(&str) -> Option<&'a str>, remote: Option<IpAddr>, trust_proxy: bool) -> ClientInfo {
+    let host = header("Host").map(str::to_string);
+    if !trust_proxy {
+        return ClientInfo { ip: remote, scheme: "http".into(), host };
+    }
+    let forwarded_ip = header("X-Forwarded-For")
+        .and_then(|v| v.split(',').next())
+        .and_then(|ip| ip.trim().parse().ok())
+        .or_else(|| header("X-Real-IP").and_then(|ip| ip.trim().parse().ok()));
+    let scheme = header("X-Forwarded-Proto")
+        .map(|p| p.split(',').next().unwrap_or(p).trim().to_ascii_lowercase())
+        .filter(|p| p == "http" || p == "https")
+        .unwrap_or_else(|| "http".into());
+    ClientInfo {
+        ip: forwarded_ip.or(remote),
+        scheme,
+        host: header("X-Forwarded-Host").map(|h| h.split(',').next().unwrap_or(h).trim().to_string()).or(host),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers<'a>(pairs: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<&'a str> {
+        move |name| pairs.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| *v)
+    }
+
+    #[test]
+    fn forwarded_headers_only_when_trusted() {
+        let remote = Some("10.0.0.2".parse().unwrap());
+        let pairs = [("Host", "lb.internal"), ("X-Forwarded-For", "203.0.113.7, 10.0.0.1"), ("X-Forwarded-Proto", "HTTPS"), ("X-Forwarded-Host", "relay.example.com")];
+        let untrusted = resolve_client(headers(&pairs), remote, false);
+        assert_eq!(untrusted, ClientInfo { ip: remote, scheme: "http".into(), host: Some("lb.internal".into()) });
+        let trusted = resolve_client(headers(&pairs), remote, true);
+        assert_eq!(trusted, ClientInfo { ip: Some("203.0.113.7".parse().unwrap()), scheme: "https".into(), host: Some("relay.example.com".into()) });
+    }
+
+    #[test]
+    fn falls_back_on_real_ip_then_peer() {
+        let remote = Some("10.0.0.2".parse().unwrap());
+        let real_ip = [("X-Forwarded-For", "not-an-ip"), ("X-Real-IP", "198.51.100.4")];
+        assert_eq!(resolve_client(headers(&real_ip), remote, true).ip, Some("198.51.100.4".parse().unwrap()));
+        assert_eq!(resolve_client(headers(&[("X-Forwarded-Proto", "gopher")]), remote, true), ClientInfo { ip: remote, scheme: "http".into(), host: None });
+    }
+}
@@ -1,49 +1,311 @@
 use rocket::serde::json::Json;
-use rocket::get;
+use rocket::{get, post};
 use rocket::State;
-use rocket::response::content::RawText;
+use rocket::http::{ContentType, Header, Status};
+use rocket::Responder;
+use rocket::request::{FromRequest, Outcome, Request};
+use std::sync::atomic::Ordering;
+use rocket::response::content::{RawJson, RawText};
 use std::sync::Arc;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tracing::info;
 
-use crate::structures::{HealthResponse, Metrics, StatsData, StatsResponse};
+use crate::common::config::validate_label_name;
+use crate::common::gzip;
+use crate::common::logging::events;
+use crate::common::relay_logs::{RelayLogBuffer, RelayLogEntry};
+use crate::web::error::ApiError;
+use crate::relay::capture::{DEFAULT_CAPTURE_SECS, MAX_CAPTURE_PACKETS, MAX_CAPTURE_SECS};
+use crate::relay::command::{PauseMode, DEFAULT_SWITCH_OVERLAP_MS};
+use crate::relay::manager::{ManagedRelayInfo, RelayManager};
+use crate::relay::probe::{self, OutputProbeResult, DEFAULT_PROBE_TIMEOUT_MS, MAX_PROBE_TIMEOUT_MS};
+use crate::relay::registry::TransportParams;
+use crate::web::auth::Admin;
+use crate::structures::{GroupedStatsResponse, HealthResponse, Metrics, MetricsScope, PipeTimingsEntry, ReadyResponse, RelayReadiness, StatsFields, StatsResponse};
 
-// Endpoint de santé: renvoie un JSON minimal { "status": "ok" }
+// Réponse de /health: corps JSON inchangé, chiffres clés en en-têtes pour les sondes qui ne lisent
+// pas le corps (HEAD /health compris, Rocket y répond via la route GET)
+#[derive(Responder)]
+pub struct HealthReply {
+    body: (Status, Json<HealthResponse>),
+    relay_count: Header<'static>,
+    bytes_in_total: Header<'static>,
+    uptime_seconds: Header<'static>,
+}
+
+// Endpoint de santé: { "status": "ok" } avec le décompte des relais déclarés, "degraded" avec la cause
+// lorsqu'un relais s'est arrêté sur une erreur (ex: bind refusé sur un port privilégié), "unhealthy" (503)
+// lorsque tous les relais déclarés ont échoué; "degraded" aussi avec la liste "stalled" des relais dont la
+// boucle du pipe ne tourne plus (superviseur de vivacité, --stall-threshold-secs)
 #[get("/health")]
-pub fn health() -> Json<HealthResponse> {
-    Json(HealthResponse { status: "ok", code: 200 })
-}
-
-// Endpoint stats: renvoie un JSON complet (format inspiré de TemplateStatsResponse.json)
-#[get("/stats")]
-pub fn stats_endpoint(metrics: &State<Arc<Metrics>>) -> Json<StatsResponse> {
-    let uptime_secs = metrics.start_time.elapsed().as_secs() as i64;
-    metrics.uptime_seconds.set(uptime_secs);
-
-    // Agrégation simple depuis les compteurs globaux
-    let bytes_in = metrics.bytes_in_total.load(std::sync::atomic::Ordering::Relaxed) as f64;
-    let bytes_out = metrics.bytes_out_total.load(std::sync::atomic::Ordering::Relaxed) as f64;
-
-    let seconds = uptime_secs.max(1) as f64;
-    let bps_out = (bytes_out * 8.0) / seconds; // bitrate moyen sortant en bps
-    let mbps_recv = (bytes_in * 8.0) / seconds / 1_000_000.0; // Mbps moyen entrant
-
-    let data = StatsData {
-        bitrate: bps_out as i64,
-        bytesRcvDrop: 0,
-        bytesRcvLoss: 0,
-        mbpsBandwidth: 0.0,
-        mbpsRecvRate: mbps_recv,
-        msRcvBuf: 0,
-        pktRcvDrop: 0,
-        pktRcvLoss: 0,
-        rtt: 0.0,
-        uptime: uptime_secs,
+pub fn health(metrics: &State<Arc<Metrics>>) -> HealthReply {
+    let manager = RelayManager::global();
+    let health = HealthResponse::from_relays(manager.counts(), manager.failures()).with_stalled(metrics.stalled_relays());
+    HealthReply {
+        body: (Status::new(health.code), Json(health)),
+        relay_count: Header::new("X-Relay-Count", metrics.active_relays.load(Ordering::Relaxed).to_string()),
+        bytes_in_total: Header::new("X-Bytes-In-Total", metrics.bytes_in_total.load(Ordering::Relaxed).to_string()),
+        uptime_seconds: Header::new("X-Uptime-Seconds", metrics.start_time.elapsed().as_secs().to_string()),
+    }
+}
+
+// Variante texte de /health pour les sondes légères qui ne savent pas lire le JSON ni tout /metrics
+#[get("/healthz")]
+pub fn healthz(metrics: &State<Arc<Metrics>>) -> RawText<String> {
+    let manager = RelayManager::global();
+    let health = HealthResponse::from_relays(manager.counts(), manager.failures());
+    RawText(health.exposition(metrics.active_relays.load(Ordering::Relaxed)))
+}
+
+// Sonde de disponibilité: chaque relais (pipe en cours ou relais piloté en (re)connexion) doit avoir
+// passé sa période de chauffe et reçu des données; pendant la chauffe la réponse reste 200 "warming"
+#[get("/ready")]
+pub fn ready(metrics: &State<Arc<Metrics>>) -> (Status, Json<ReadyResponse>) {
+    let manager = RelayManager::global();
+    let snapshots = metrics.relay_snapshots();
+    let mut relays: Vec<RelayReadiness> = snapshots
+        .iter()
+        .map(|r| RelayReadiness::new(r.relay_id.clone(), Some(r.uptime), manager.warmup(&r.relay_id).as_secs(), r.time_to_first_byte_ms.is_some()))
+        .collect();
+    relays.extend(
+        manager.list().into_iter()
+            .filter(|m| m.running && !snapshots.iter().any(|r| r.relay_id == m.relay_id))
+            .map(|m| RelayReadiness::new(m.relay_id, None, 0, false)),
+    );
+    let response = ReadyResponse::from_relays(relays);
+    (Status::new(response.code), Json(response))
+}
+
+// Réponse de /stats: JSON compact par défaut, indenté pour une lecture humaine (?pretty=1)
+#[derive(Responder)]
+pub enum StatsReply {
+    Compact(Json<StatsResponse>),
+    Projected(Json<serde_json::Value>),
+    Pretty(RawJson<String>),
+}
+
+// Projection appliquée à /stats sans ?fields= (stats_fields du fichier de configuration); None = tout
+#[derive(Debug, Clone, Default)]
+pub struct DefaultStatsFields(pub Option<StatsFields>);
+
+// Endpoint stats: renvoie un JSON complet (format inspiré de TemplateStatsResponse.json), ou réduit aux
+// clés de ?fields=bitrate,rtt,uptime
+#[get("/stats?<pretty>&<fields>")]
+pub fn stats_endpoint(metrics: &State<Arc<Metrics>>, defaults: &State<DefaultStatsFields>, pretty: Option<&str>, fields: Option<&str>) -> Result<StatsReply, ApiError> {
+    let fields = match fields {
+        Some(raw) => Some(StatsFields::parse(raw).map_err(|e| ApiError::from_status(Status::BadRequest, e))?),
+        None => defaults.0.clone(),
     };
+    let stats = StatsResponse::collect(metrics);
+    let internal = |e: serde_json::Error| ApiError::from_status(Status::InternalServerError, e.to_string());
+    // Le bool de Rocket refuse "1"; toute autre valeur garde la sortie compacte
+    let pretty = matches!(pretty.map(|v| v.to_ascii_lowercase()).as_deref(), Some("" | "1" | "true" | "yes" | "on"));
+    match (fields, pretty) {
+        (None, false) => Ok(StatsReply::Compact(Json(stats))),
+        (Some(fields), false) => stats.project(&fields).map(|v| StatsReply::Projected(Json(v))).map_err(internal),
+        (None, true) => serde_json::to_string_pretty(&stats).map(|body| StatsReply::Pretty(RawJson(body))).map_err(internal),
+        (Some(fields), true) => stats.project(&fields)
+            .and_then(|v| serde_json::to_string_pretty(&v))
+            .map(|body| StatsReply::Pretty(RawJson(body)))
+            .map_err(internal),
+    }
+}
+
+// Agrégat de /stats par valeur d'une étiquette de relais (?by=customer)
+#[get("/stats/grouped?<by>")]
+pub fn stats_grouped(metrics: &State<Arc<Metrics>>, by: Option<&str>) -> Result<Json<GroupedStatsResponse>, ApiError> {
+    let by = by.ok_or_else(|| ApiError::from_status(Status::BadRequest, "missing query parameter by=<label>"))?;
+    validate_label_name(by).map_err(|e| ApiError::from_status(Status::BadRequest, e))?;
+    GroupedStatsResponse::collect(metrics, by)
+        .map(Json)
+        .ok_or_else(|| ApiError::from_status(Status::BadRequest, format!("no relay carries label {:?}", by)))
+}
+
+// Relais pilotés par le RelayManager (configuration, SRTRIST_RELAYS, socket de contrôle) avec leurs étiquettes
+#[get("/relays")]
+pub fn relays_list() -> Json<Vec<ManagedRelayInfo>> {
+    Json(RelayManager::global().list())
+}
+
+// Détail d'un relais piloté; `pipe` est absent tant que le pipe n'est pas en cours (reconnexion)
+#[derive(Serialize)]
+pub struct RelayDetail {
+    #[serde(flatten)]
+    pub relay: ManagedRelayInfo,
+    pub pipe: Option<PipeTimingsEntry>,
+}
+
+#[get("/relays/<relay_id>")]
+pub fn relay_detail(relay_id: &str, metrics: &State<Arc<Metrics>>) -> Result<Json<RelayDetail>, ApiError> {
+    let relay = RelayManager::global()
+        .info(relay_id)
+        .ok_or_else(|| ApiError::from_status(Status::NotFound, format!("unknown relay {}", relay_id)))?;
+    Ok(Json(RelayDetail { relay, pipe: metrics.relay_timing(relay_id) }))
+}
+
+// Derniers événements de log d'un relais (200 au plus), du plus ancien au plus récent
+#[get("/relays/<relay_id>/logs")]
+pub fn relay_logs(relay_id: &str) -> Result<Json<Vec<RelayLogEntry>>, ApiError> {
+    RelayLogBuffer::global()
+        .entries(relay_id)
+        .map(Json)
+        .ok_or_else(|| ApiError::from_status(Status::NotFound, format!("no log events recorded for relay {}", relay_id)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SwitchOutputRequest {
+    pub output: String,
+    pub overlap_ms: Option<u64>,
+}
+
+// Bascule la sortie d'un relais sans couper l'entrée: la nouvelle sortie (même protocole) est ouverte,
+// reçoit le flux en même temps que l'ancienne pendant `overlap_ms`, puis l'ancienne est fermée.
+// Protégé par le jeton d'administration.
+#[post("/relays/<relay_id>/switch-output", data = "<req>")]
+pub async fn relay_switch_output(_admin: Admin, relay_id: &str, req: Json<SwitchOutputRequest>) -> Result<Json<ManagedRelayInfo>, ApiError> {
+    let req = req.into_inner();
+    let overlap = Duration::from_millis(req.overlap_ms.unwrap_or(DEFAULT_SWITCH_OVERLAP_MS));
+    RelayManager::global()
+        .switch_output(relay_id, req.output, overlap)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::from_status(Status::NotFound, format!("unknown relay {}", relay_id)))
+}
+
+// Met un relais en pause sans le détruire: il cesse de transmettre mais garde ses sockets, sa
+// configuration et son entrée dans /relays (status "paused"). Par défaut l'entrée est encore lue et
+// jetée pour ne pas laisser le buffer amont se remplir; ?drain=0 cesse aussi de lire.
+// Protégé par le jeton d'administration.
+#[post("/relays/<relay_id>/pause?<drain>")]
+pub async fn relay_pause(_admin: Admin, relay_id: &str, drain: Option<&str>) -> Result<Json<ManagedRelayInfo>, ApiError> {
+    let mode = match drain.map(|v| v.to_ascii_lowercase()).as_deref() {
+        None | Some("" | "1" | "true" | "yes" | "on") => PauseMode::Drain,
+        Some("0" | "false" | "no" | "off") => PauseMode::Hold,
+        Some(other) => return Err(ApiError::from_status(Status::BadRequest, format!("drain must be 0/1 or true/false, got {}", other))),
+    };
+    RelayManager::global()
+        .pause(relay_id, mode)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::from_status(Status::NotFound, format!("unknown relay {}", relay_id)))
+}
+
+// Reprise immédiate d'un relais en pause (sans effet sur un relais qui transmet déjà)
+#[post("/relays/<relay_id>/resume")]
+pub async fn relay_resume(_admin: Admin, relay_id: &str) -> Result<Json<ManagedRelayInfo>, ApiError> {
+    RelayManager::global()
+        .resume(relay_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::from_status(Status::NotFound, format!("unknown relay {}", relay_id)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProbeOutputRequest {
+    pub output: String,
+    pub timeout_ms: Option<u64>,
+}
+
+// Vérifie qu'une sortie est joignable sans lancer de relais: ouverture (handshake pour SRT) bornée par
+// `timeout_ms` (2000 par défaut, 10000 au plus), puis fermeture. 200 avec reachable=false si la cible ne
+// répond pas, 400 si l'URI est invalide. Ouvre des sockets vers une cible arbitraire: protégé par le
+// jeton d'administration.
+#[post("/probe-output", data = "<req>")]
+pub async fn probe_output(_admin: Admin, req: Json<ProbeOutputRequest>) -> Result<Json<OutputProbeResult>, ApiError> {
+    let req = req.into_inner();
+    let timeout_ms = req.timeout_ms.unwrap_or(DEFAULT_PROBE_TIMEOUT_MS);
+    if !(1..=MAX_PROBE_TIMEOUT_MS).contains(&timeout_ms) {
+        return Err(ApiError::from_status(Status::BadRequest, format!("timeout_ms must be between 1 and {}", MAX_PROBE_TIMEOUT_MS)));
+    }
+    Ok(Json(probe::probe_output(&req.output, TransportParams::default(), Duration::from_millis(timeout_ms)).await?))
+}
+
+#[derive(Responder)]
+pub struct PcapReply {
+    body: (ContentType, Vec<u8>),
+    disposition: Header<'static>,
+}
+
+// Capture pcap des datagrammes reçus par un relais pendant `seconds` (5 par défaut, 60 au plus) ou
+// jusqu'à `packets`, renvoyée en téléchargement. Tenue en mémoire et bornée en taille; le flux capturé
+// pouvant être sensible, la route est protégée par le jeton d'administration.
+#[get("/relays/<relay_id>/capture?<seconds>&<packets>")]
+pub async fn relay_capture(_admin: Admin, relay_id: &str, seconds: Option<u64>, packets: Option<u64>) -> Result<PcapReply, ApiError> {
+    let seconds = seconds.unwrap_or(DEFAULT_CAPTURE_SECS);
+    if !(1..=MAX_CAPTURE_SECS).contains(&seconds) {
+        return Err(ApiError::from_status(Status::BadRequest, format!("seconds must be between 1 and {}", MAX_CAPTURE_SECS)));
+    }
+    if packets.is_some_and(|p| !(1..=MAX_CAPTURE_PACKETS).contains(&p)) {
+        return Err(ApiError::from_status(Status::BadRequest, format!("packets must be between 1 and {}", MAX_CAPTURE_PACKETS)));
+    }
+    let pcap = RelayManager::global()
+        .capture(relay_id, Duration::from_secs(seconds), packets)
+        .await?
+        .ok_or_else(|| ApiError::from_status(Status::NotFound, format!("unknown relay {}", relay_id)))?;
+    let name: String = relay_id.chars().filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_')).collect();
+    Ok(PcapReply {
+        body: (ContentType::new("application", "vnd.tcpdump.pcap"), pcap),
+        disposition: Header::new("Content-Disposition", format!("attachment; filename=\"relay-{}.pcap\"", name)),
+    })
+}
+
+// Le client accepte-t-il une réponse gzip (en-tête Accept-Encoding) ?
+pub struct AcceptsGzip(bool);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AcceptsGzip {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(AcceptsGzip(req.headers().get("Accept-Encoding").any(gzip::accepts_gzip)))
+    }
+}
+
+#[derive(Responder)]
+pub enum MetricsReply {
+    Plain(RawText<String>, Header<'static>),
+    #[response(content_type = "plain")]
+    Gzip(Vec<u8>, Header<'static>, Header<'static>),
+}
+
+// Endpoint Prometheus /metrics; compressé en gzip seulement si le client le demande
+// ?only=relay écarte les séries du serveur HTTP lui-même (http_*), ?only=http ne garde qu'elles;
+// sans paramètre, tout le registry est exporté
+#[get("/metrics?<only>")]
+pub fn metrics_export(metrics: &State<Arc<Metrics>>, accepts: AcceptsGzip, only: Option<&str>) -> Result<MetricsReply, ApiError> {
+    let scope = MetricsScope::parse(only).map_err(|e| ApiError::from_status(Status::BadRequest, e))?;
+    let text = metrics.gather_text(scope);
+    let vary = Header::new("Vary", "Accept-Encoding");
+    Ok(if accepts.0 {
+        MetricsReply::Gzip(gzip::gzip(text.as_bytes()), Header::new("Content-Encoding", "gzip"), vary)
+    } else {
+        MetricsReply::Plain(RawText(text), vary)
+    })
+}
 
-    Json(StatsResponse { data, status: "ok" })
+// Séries Prometheus d'un seul relais, pour scraper chaque relais comme une cible distincte; 404 pour un
+// relais ni piloté par le RelayManager ni en cours
+#[get("/relays/<relay_id>/metrics")]
+pub fn relay_metrics(relay_id: &str, metrics: &State<Arc<Metrics>>, accepts: AcceptsGzip) -> Result<MetricsReply, ApiError> {
+    if RelayManager::global().info(relay_id).is_none() && metrics.relay_timing(relay_id).is_none() {
+        return Err(ApiError::from_status(Status::NotFound, format!("unknown relay {}", relay_id)));
+    }
+    let text = metrics.gather_relay_text(relay_id);
+    let vary = Header::new("Vary", "Accept-Encoding");
+    Ok(if accepts.0 {
+        MetricsReply::Gzip(gzip::gzip(text.as_bytes()), Header::new("Content-Encoding", "gzip"), vary)
+    } else {
+        MetricsReply::Plain(RawText(text), vary)
+    })
 }
 
-// Endpoint Prometheus /metrics
-#[get("/metrics")]
-pub fn metrics_export(metrics: &State<Arc<Metrics>>) -> RawText<String> {
-    RawText(metrics.gather_text())
+// Remise à zéro des compteurs runtime qui alimentent /stats (octets, paquets, timeouts, pertes).
+// Les séries Prometheus enregistrées (http_*, relay_*_total, ...) ne sont PAS touchées: remettre à zéro
+// un compteur monotone fausserait les rate() côté scraper. Protégé par le jeton d'administration.
+#[post("/metrics/reset")]
+pub fn metrics_reset(_admin: Admin, metrics: &State<Arc<Metrics>>) -> Json<HealthResponse> {
+    metrics.reset_io_counters();
+    info!(event = events::METRICS_RESET, subsystem = "http", msg = "Runtime I/O counters reset");
+    Json(HealthResponse::ok())
 }
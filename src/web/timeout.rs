@@ -0,0 +1,123 @@
+use std::time::Duration;
+use rocket::http::Status;
+use rocket::route::{Handler, Outcome, Route};
+use rocket::{Data, Request};
+use tracing::warn;
+
+use crate::common::logging::events;
+use crate::web::error::ErrorDetail;
+
+// Routes longues par construction (la capture dure jusqu'à 60 s et se borne elle-même): ni coupées par
+// le délai des requêtes, ni signalées comme lentes
+const LONG_RUNNING_ROUTES: &[&str] = &["relay_capture"];
+
+// Bornes des requêtes HTTP (--http-request-timeout-ms, --http-slow-request-ms); None = désactivé
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpLimits {
+    pub request_timeout: Option<Duration>,
+    pub slow_request: Option<Duration>,
+}
+
+pub fn is_long_running(req: &Request<'_>) -> bool {
+    req.route().and_then(|r| r.name.as_deref()).is_some_and(|name| LONG_RUNNING_ROUTES.contains(&name))
+}
+
+// Enveloppe de handler: au-delà du délai, le futur du handler est abandonné et la requête finit en 504
+// via le catcher. Un handler qui bloque son worker sans jamais céder la main n'est interrompu qu'à son
+// prochain point d'attente: les appels bloquants restent à déporter (spawn_blocking).
+#[derive(Clone)]
+struct TimeoutHandler {
+    inner: Box<dyn Handler>,
+    limit: Duration,
+}
+
+#[rocket::async_trait]
+impl Handler for TimeoutHandler {
+    async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        match tokio::time::timeout(self.limit, self.inner.handle(req, data)).await {
+            Ok(outcome) => outcome,
+            Err(_) => {
+                warn!(event = events::HTTP_TIMEOUT, subsystem = "http", method = req.method().as_str(), path = %req.uri().path(), timeout_ms = self.limit.as_millis() as u64, msg = "HTTP request timed out, handler aborted");
+                req.local_cache(|| ErrorDetail("request timed out"));
+                Outcome::Error(Status::GatewayTimeout)
+            }
+        }
+    }
+}
+
+// Applique le délai aux routes montées (hors LONG_RUNNING_ROUTES); sans délai, les routes sont inchangées
+pub fn with_timeout(routes: Vec<Route>, limits: HttpLimits) -> Vec<Route> {
+    let Some(limit) = limits.request_timeout else { return routes };
+    routes
+        .into_iter()
+        .map(|mut route| {
+            if !route.name.as_deref().is_some_and(|name| LONG_RUNNING_ROUTES.contains(&name)) {
+                route.handler = Box::new(TimeoutHandler { inner: route.handler, limit });
+            }
+            route
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use rocket::local::asynchronous::Client;
+    use rocket::{catchers, get, routes};
+
+    use crate::web::HttpMetricsFairing;
+
+    #[get("/slow")]
+    async fn slow() -> &'static str {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        "done"
+    }
+
+    // Au-delà du seuil « lent » mais sous le délai: servie et signalée
+    #[get("/sluggish")]
+    async fn sluggish() -> &'static str {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        "done"
+    }
+
+    // Journal JSON des événements émis pendant le test
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn slow_handler_gets_504_and_slow_requests_are_flagged() {
+        let logs = Captured::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt().json().with_writer(move || writer.clone()).finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let limits = HttpLimits { request_timeout: Some(Duration::from_millis(200)), slow_request: Some(Duration::from_millis(20)) };
+        let config = rocket::Config { log_level: rocket::config::LogLevel::Off, ..rocket::Config::debug_default() };
+        let rocket = rocket::custom(config)
+            .attach(HttpMetricsFairing { trust_proxy: false, slow_request: limits.slow_request })
+            .mount("/", with_timeout(routes![slow, sluggish], limits))
+            .register("/", catchers![crate::web::error::default_catcher]);
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let res = client.get("/slow").dispatch().await;
+        assert_eq!(res.status(), Status::GatewayTimeout);
+        assert!(res.into_string().await.unwrap().contains("request timed out"));
+        let res = client.get("/sluggish").dispatch().await;
+        assert_eq!(res.status(), Status::Ok);
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.lines().any(|l| l.contains(events::HTTP_TIMEOUT) && l.contains("/slow")), "{}", logs);
+        assert!(logs.lines().any(|l| l.contains(events::HTTP_SLOW_REQUEST) && l.contains("/sluggish")), "{}", logs);
+    }
+}